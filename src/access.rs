@@ -0,0 +1,282 @@
+use std::{collections::{HashMap, VecDeque}, net::IpAddr, sync::{Arc, Mutex, RwLock}, time::{Duration, Instant}};
+
+/* failures of an address in the window before it is banned */
+pub const DEFAULT_MAX_FAILURES : usize = 5;
+pub const DEFAULT_FAILURE_WINDOW : Duration = Duration::from_secs(60);
+pub const DEFAULT_BAN_TIME : Duration = Duration::from_secs(600);
+
+/* sessions served at once , in total and from one address */
+pub const DEFAULT_MAX_SESSIONS : usize = 1024;
+pub const DEFAULT_MAX_SESSIONS_PER_IP : usize = 64;
+
+/* forget addresses without failures or ban once the table grows past */
+const PRUNE_SIZE : usize = 4096;
+
+/* an address range like 192.168.0.0/16 or 2001:db8::/32 , a bare address is a single host */
+#[derive(Debug, PartialEq)]
+pub struct Cidr {
+	addr : IpAddr,
+	prefix : u8
+}
+
+impl Cidr {
+
+	pub fn parse(text : &str) -> Result<Cidr , String> {
+		let (addr , prefix) = match text.split_once('/') {
+			Some((a , p)) => (a , Some(p)),
+			None => (text , None),
+		};
+
+		let addr : IpAddr = match addr.trim().parse() {
+			Ok(p) => p,
+			Err(_) => return Err(format!("invalid address : {}" , text)),
+		};
+
+		let max = if addr.is_ipv4() { 32 } else { 128 };
+
+		let prefix = match prefix {
+			Some(p) => match p.trim().parse::<u8>() {
+				Ok(p) if p <= max => p,
+				_ => return Err(format!("invalid prefix : {}" , text)),
+			},
+			None => max,
+		};
+
+		Ok(Cidr { addr , prefix })
+	}
+
+	/* comma separated list of ranges */
+	pub fn parse_list(text : &str) -> Result<Vec<Cidr> , String> {
+		text.split(',').filter(|p| !p.trim().is_empty()).map(Cidr::parse).collect()
+	}
+
+	pub fn contains(&self , ip : &IpAddr) -> bool {
+		match (self.addr , ip.to_canonical()) {
+			(IpAddr::V4(net) , IpAddr::V4(ip)) => {
+				let mask = u32::MAX.checked_shl(32 - self.prefix as u32).unwrap_or(0);
+				u32::from(net) & mask == u32::from(ip) & mask
+			},
+			(IpAddr::V6(net) , IpAddr::V6(ip)) => {
+				let mask = u128::MAX.checked_shl(128 - self.prefix as u32).unwrap_or(0);
+				u128::from(net) & mask == u128::from(ip) & mask
+			},
+			_ => false,
+		}
+	}
+}
+
+#[derive(Default)]
+struct Failures {
+	times : VecDeque<Instant>,
+	banned_until : Option<Instant>
+}
+
+impl Failures {
+	fn banned(&self , now : Instant) -> bool {
+		match self.banned_until {
+			Some(p) => p > now,
+			None => false,
+		}
+	}
+}
+
+#[derive(Default)]
+struct Sessions {
+	total : usize,
+	per_ip : HashMap<IpAddr , usize>
+}
+
+/* the configured part of the access , replaced on reload */
+struct Rules {
+	allow : Vec<Cidr>,
+	deny : Vec<Cidr>,
+	max_failures : usize,
+	window : Duration,
+	ban_time : Duration,
+	max_sessions : usize,
+	max_sessions_per_ip : usize
+}
+
+/*
+	who may connect to the server . deny wins over allow , an empty allow
+	list allows every address . an address failing the handshake or the
+	login too often in the window is banned for a while . admitted
+	connections are served while the session limits are not reached .
+*/
+pub struct Access {
+	rules : RwLock<Rules>,
+	failures : Mutex<HashMap<IpAddr , Failures>>,
+	sessions : Mutex<Sessions>
+}
+
+/* a session being served , released when dropped */
+pub struct Slot {
+	access : Arc<Access>,
+	ip : IpAddr
+}
+
+impl Drop for Slot {
+	fn drop(&mut self) {
+		let mut sessions = self.access.sessions.lock().unwrap();
+		sessions.total = sessions.total.saturating_sub(1);
+		if let Some(p) = sessions.per_ip.get_mut(&self.ip) {
+			*p -= 1;
+			if *p == 0 {
+				sessions.per_ip.remove(&self.ip);
+			}
+		}
+	}
+}
+
+impl Access {
+
+	pub fn new(allow : Vec<Cidr> , deny : Vec<Cidr> , max_failures : usize , window : Duration , ban_time : Duration) -> Access {
+		Access {
+			rules : RwLock::new(Rules {
+				allow,
+				deny,
+				max_failures,
+				window,
+				ban_time,
+				max_sessions : DEFAULT_MAX_SESSIONS,
+				max_sessions_per_ip : DEFAULT_MAX_SESSIONS_PER_IP
+			}),
+			failures : Mutex::new(HashMap::new()),
+			sessions : Mutex::new(Sessions::default())
+		}
+	}
+
+	/* 0 is unlimited */
+	pub fn set_limits(&mut self , max_sessions : usize , max_sessions_per_ip : usize) {
+		let rules = self.rules.get_mut().unwrap();
+		rules.max_sessions = max_sessions;
+		rules.max_sessions_per_ip = max_sessions_per_ip;
+	}
+
+	/* take the rules of from , bans and running sessions are kept */
+	pub fn reload(&self , from : Access) {
+		*self.rules.write().unwrap() = from.rules.into_inner().unwrap();
+	}
+
+	/* take a session slot for ip , none when the server or the address is busy */
+	pub fn enter(self : &Arc<Access> , ip : &IpAddr) -> Option<Slot> {
+		let ip = ip.to_canonical();
+		let rules = self.rules.read().unwrap();
+		let mut sessions = self.sessions.lock().unwrap();
+
+		if rules.max_sessions != 0 && sessions.total >= rules.max_sessions {
+			log::warn!("reject {} , server busy with {} sessions" , ip , sessions.total);
+			return None;
+		}
+
+		let count = sessions.per_ip.get(&ip).copied().unwrap_or(0);
+		if rules.max_sessions_per_ip != 0 && count >= rules.max_sessions_per_ip {
+			log::warn!("reject {} , address busy with {} sessions" , ip , count);
+			return None;
+		}
+
+		sessions.total += 1;
+		sessions.per_ip.insert(ip, count + 1);
+
+		Some(Slot { access : self.clone() , ip })
+	}
+
+	/* check an accepted connection , log the reason it is refused */
+	pub fn admit(&self , ip : &IpAddr) -> bool {
+		let rules = self.rules.read().unwrap();
+
+		if rules.deny.iter().any(|p| p.contains(ip)) {
+			log::warn!("reject {} , address is denied" , ip);
+			return false;
+		}
+
+		if !rules.allow.is_empty() && !rules.allow.iter().any(|p| p.contains(ip)) {
+			log::warn!("reject {} , address is not allowed" , ip);
+			return false;
+		}
+
+		let failures = self.failures.lock().unwrap();
+		if let Some(p) = failures.get(&ip.to_canonical()) {
+			if p.banned(Instant::now()) {
+				log::warn!("reject {} , address is banned" , ip);
+				return false;
+			}
+		}
+
+		true
+	}
+
+	/* a failed handshake or login of ip , ban it when it failed too often */
+	pub fn fail(&self , ip : &IpAddr) {
+		let rules = self.rules.read().unwrap();
+
+		if rules.max_failures == 0 {
+			return;
+		}
+
+		let now = Instant::now();
+		let mut failures = self.failures.lock().unwrap();
+
+		if failures.len() > PRUNE_SIZE {
+			let window = rules.window;
+			failures.retain(|_ , p| p.banned(now) || p.times.back().is_some_and(|t| now.duration_since(*t) < window));
+		}
+
+		let entry = failures.entry(ip.to_canonical()).or_default();
+
+		while let Some(p) = entry.times.front() {
+			if now.duration_since(*p) < rules.window {
+				break;
+			}
+			entry.times.pop_front();
+		}
+
+		entry.times.push_back(now);
+
+		if entry.times.len() >= rules.max_failures {
+			log::warn!("ban {} for {} seconds after {} failures" , ip , rules.ban_time.as_secs() , entry.times.len());
+			entry.times.clear();
+			entry.banned_until = Some(now + rules.ban_time);
+		}
+	}
+}
+
+#[test]
+fn test_access() {
+	let lan = Cidr::parse("192.168.0.0/16").unwrap();
+	assert!(lan.contains(&"192.168.3.4".parse().unwrap()));
+	assert!(lan.contains(&"::ffff:192.168.3.4".parse().unwrap()));
+	assert!(!lan.contains(&"10.0.0.1".parse().unwrap()));
+	assert!(Cidr::parse("0.0.0.0/0").unwrap().contains(&"8.8.8.8".parse().unwrap()));
+	assert!(Cidr::parse("2001:db8::/32").unwrap().contains(&"2001:db8::1".parse().unwrap()));
+	assert!(Cidr::parse("10.0.0.1/33").is_err());
+	assert!(Cidr::parse("host").is_err());
+	assert_eq!(Cidr::parse_list("10.0.0.1 , 10.1.0.0/16").unwrap().len() , 2);
+
+	let access = Access::new(vec![lan], Cidr::parse_list("192.168.9.0/24").unwrap(), 2, DEFAULT_FAILURE_WINDOW, DEFAULT_BAN_TIME);
+	let ip : IpAddr = "192.168.3.4".parse().unwrap();
+	assert!(access.admit(&ip));
+	assert!(!access.admit(&"192.168.9.1".parse().unwrap()));
+	assert!(!access.admit(&"10.0.0.1".parse().unwrap()));
+
+	access.fail(&ip);
+	assert!(access.admit(&ip));
+	access.fail(&ip);
+	assert!(!access.admit(&ip));
+
+	let mut access = Access::new(vec![], vec![], 0, DEFAULT_FAILURE_WINDOW, DEFAULT_BAN_TIME);
+	access.set_limits(3, 2);
+	let access = Arc::new(access);
+	let other : IpAddr = "10.0.0.1".parse().unwrap();
+	let a = access.enter(&ip).unwrap();
+	let _b = access.enter(&ip).unwrap();
+	assert!(access.enter(&ip).is_none());
+	let _c = access.enter(&other).unwrap();
+	assert!(access.enter(&other).is_none());
+	drop(a);
+	assert!(access.enter(&ip).is_some());
+
+	access.reload(Access::new(vec![], Cidr::parse_list("10.0.0.0/8").unwrap(), 0, DEFAULT_FAILURE_WINDOW, DEFAULT_BAN_TIME));
+	assert!(!access.admit(&other));
+	assert!(access.enter(&ip).is_some());
+}
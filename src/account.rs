@@ -0,0 +1,507 @@
+use std::{collections::{HashMap, hash_map::RandomState}, fs, hash::BuildHasher, io, path::{Path, PathBuf}, sync::{Arc, Mutex}};
+
+use argon2::{Argon2, PasswordHash, PasswordHasher, PasswordVerifier};
+use password_hash::{SaltString, rand_core::OsRng};
+use path_absolutize::*;
+use serde::Deserialize;
+
+use crate::{audit::Trail, common::YaftpError, quota::{Charge, QUOTA_ROOT, QUOTA_USER, Quota}};
+
+pub const PERM_LIST : u8 = 0x01;
+pub const PERM_READ : u8 = 0x02;
+pub const PERM_WRITE : u8 = 0x04;
+pub const PERM_DELETE : u8 = 0x08;
+pub const PERM_MKDIR : u8 = 0x10;
+pub const PERM_RENAME : u8 = 0x20;
+pub const PERM_ALL : u8 = 0x3f;
+
+/* sessions and shutdown of the server , never given by all */
+pub const PERM_ADMIN : u8 = 0x40;
+
+const PERMISSIONS : [(&str , u8) ; 7] = [
+	("list" , PERM_LIST),
+	("read" , PERM_READ),
+	("write" , PERM_WRITE),
+	("delete" , PERM_DELETE),
+	("mkdir" , PERM_MKDIR),
+	("rename" , PERM_RENAME),
+	("admin" , PERM_ADMIN),
+];
+
+/* symlinks followed while checking a path before giving up */
+const MAX_LINK_HOPS : u32 = 40;
+
+/*
+	[[user]]
+	name = "alice"
+	password = "$argon2id$v=19$..."
+	root = "/srv/yaftp/alice"
+	home = "/"
+	permissions = ["list" , "read" , "write"]
+	quota_bytes = 1073741824
+	quota_files = 10000
+
+	[[root]]
+	path = "/srv/yaftp"
+	quota_bytes = 10737418240
+*/
+#[derive(Deserialize)]
+struct AccountsFile {
+	#[serde(default)]
+	user : Vec<AccountEntry>,
+	#[serde(default)]
+	root : Vec<RootEntry>
+}
+
+#[derive(Deserialize)]
+struct AccountEntry {
+	name : String,
+	password : String,
+	root : String,
+	#[serde(default = "default_home")]
+	home : String,
+	#[serde(default = "default_permissions")]
+	permissions : Vec<String>,
+	quota_bytes : Option<u64>,
+	quota_files : Option<u64>
+}
+
+/* limits of a folder shared by every account writing under it */
+#[derive(Deserialize)]
+struct RootEntry {
+	path : String,
+	quota_bytes : Option<u64>,
+	quota_files : Option<u64>
+}
+
+fn default_home() -> String {
+	String::from("/")
+}
+
+fn default_permissions() -> Vec<String> {
+	vec![String::from("list") , String::from("read")]
+}
+
+fn parse_permissions(names : &[String]) -> Result<u8 , String> {
+	let mut perms = 0u8;
+	for name in names {
+		if name == "all" {
+			perms |= PERM_ALL;
+			continue;
+		}
+		match PERMISSIONS.iter().find(|p| p.0 == name) {
+			Some(p) => perms |= p.1,
+			None => return Err(format!("unknown permission : {}" , name)),
+		}
+	}
+	Ok(perms)
+}
+
+fn permission_name(perm : u8) -> &'static str {
+	match PERMISSIONS.iter().find(|p| p.1 == perm) {
+		Some(p) => p.0,
+		None => "unknown",
+	}
+}
+
+fn io_error(e : io::Error) -> YaftpError {
+	if e.kind() == io::ErrorKind::PermissionDenied {
+		YaftpError::NoPermission
+	} else if e.kind() == io::ErrorKind::NotFound {
+		YaftpError::NotFound
+	} else {
+		log::error!("error : {}" , e);
+		YaftpError::UnknownError
+	}
+}
+
+/* hash a password for the accounts file */
+pub fn hash_password(password : &str) -> Result<String , String> {
+	let salt = SaltString::generate(&mut OsRng);
+	match Argon2::default().hash_password(password.as_bytes(), &salt) {
+		Ok(p) => Ok(p.to_string()),
+		Err(e) => Err(e.to_string()),
+	}
+}
+
+struct Account {
+	password : String,
+	root : PathBuf,
+	home : String,
+	perms : u8,
+	quota : Option<Arc<Quota>>
+}
+
+pub struct Accounts {
+	users : HashMap<String , Account>,
+	roots : Vec<Arc<Quota>>,
+	/*
+		every command is a new connection with its own login , remember the
+		password a user last logged in with as a keyed hash to skip argon2 .
+	*/
+	verified : Mutex<HashMap<String , u64>>,
+	key : RandomState
+}
+
+impl Accounts {
+
+	/* load and check the accounts file , every root must be an existing folder */
+	pub fn load(path : &Path) -> Result<Accounts , String> {
+
+		let data = match fs::read_to_string(path) {
+			Ok(p) => p,
+			Err(e) => return Err(format!("read {} faild : {}" , path.display() , e)),
+		};
+
+		let file : AccountsFile = match toml::from_str(&data) {
+			Ok(p) => p,
+			Err(e) => return Err(format!("parse {} faild : {}" , path.display() , e)),
+		};
+
+		let mut users = HashMap::new();
+
+		for entry in file.user {
+
+			if users.contains_key(&entry.name) {
+				return Err(format!("duplicate user : {}" , entry.name));
+			}
+
+			if let Err(e) = PasswordHash::new(&entry.password) {
+				return Err(format!("user [{}] password is not a valid hash : {}" , entry.name , e));
+			}
+
+			let root = match fs::canonicalize(&entry.root) {
+				Ok(p) if p.is_dir() => p,
+				_ => return Err(format!("user [{}] root is not a folder : {}" , entry.name , entry.root)),
+			};
+
+			if !entry.home.starts_with('/') {
+				return Err(format!("user [{}] home must be absolute in its root : {}" , entry.name , entry.home));
+			}
+
+			let perms = match parse_permissions(&entry.permissions) {
+				Ok(p) => p,
+				Err(e) => return Err(format!("user [{}] {}" , entry.name , e)),
+			};
+
+			let quota = if entry.quota_bytes.is_some() || entry.quota_files.is_some() {
+				Some(Arc::new(Quota::new(QUOTA_USER, root.clone(), entry.quota_bytes, entry.quota_files)))
+			} else {
+				None
+			};
+
+			users.insert(entry.name , Account { password : entry.password , root , home : entry.home , perms , quota });
+		}
+
+		let mut roots = vec![];
+
+		for entry in file.root {
+			let path = match fs::canonicalize(&entry.path) {
+				Ok(p) if p.is_dir() => p,
+				_ => return Err(format!("quota root is not a folder : {}" , entry.path)),
+			};
+			roots.push(Arc::new(Quota::new(QUOTA_ROOT, path, entry.quota_bytes, entry.quota_files)));
+		}
+
+		Ok(Accounts { users , roots , verified : Mutex::new(HashMap::new()) , key : RandomState::new() })
+	}
+
+	pub fn len(&self) -> usize {
+		self.users.len()
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.users.is_empty()
+	}
+
+	/* check the password of user , return its session */
+	pub fn login(&self , user : &str , password : &str) -> Option<Session> {
+
+		let account = self.users.get(user)?;
+
+		let digest = self.key.hash_one(password);
+		let cached = self.verified.lock().unwrap().get(user) == Some(&digest);
+
+		if !cached {
+			let hash = match PasswordHash::new(&account.password) {
+				Ok(p) => p,
+				Err(_) => return None,
+			};
+
+			if Argon2::default().verify_password(password.as_bytes(), &hash).is_err() {
+				return None;
+			}
+
+			self.verified.lock().unwrap().insert(user.to_string(), digest);
+		}
+
+		/* the account quota first , then the root quotas which may cover its files */
+		let mut quotas : Vec<Arc<Quota>> = account.quota.iter().cloned().collect();
+		for quota in &self.roots {
+			if quota.path.starts_with(&account.root) || account.root.starts_with(&quota.path) {
+				quotas.push(quota.clone());
+			}
+		}
+
+		Some(Session {
+			user : user.to_string(),
+			root : Some(account.root.clone()),
+			home : account.home.clone(),
+			perms : account.perms,
+			quotas,
+			trail : Arc::new(Mutex::new(Trail::default()))
+		})
+	}
+}
+
+/*
+	the acting user of a connection . with a root , client paths are virtual
+	paths under the root and never resolve out of it , even through symlinks .
+*/
+#[derive(Clone)]
+pub struct Session {
+	pub user : String,
+	root : Option<PathBuf>,
+	home : String,
+	perms : u8,
+	quotas : Vec<Arc<Quota>>,
+	trail : Arc<Mutex<Trail>>
+}
+
+impl Session {
+
+	/* server without accounts , every path and permission is allowed */
+	pub fn anonymous() -> Session {
+		Session { user : String::from("anonymous") , root : None , home : String::new() , perms : PERM_ALL , quotas : vec![] , trail : Arc::new(Mutex::new(Trail::default())) }
+	}
+
+	pub fn check(&self , perm : u8) -> Result<() , YaftpError> {
+		if self.perms & perm == perm {
+			return Ok(());
+		}
+		log::warn!("user [{}] has no {} permission" , self.user , permission_name(perm));
+		Err(YaftpError::NoPermission)
+	}
+
+	/* working directory given to the client */
+	pub fn cwd(&self) -> Result<String , YaftpError> {
+		if self.root.is_some() {
+			return Ok(self.home.clone());
+		}
+		match std::env::current_dir() {
+			Ok(p) => match p.to_str() {
+				Some(p) => Ok(p.to_string()),
+				None => Err(YaftpError::ReadCwdFaild),
+			},
+			Err(_) => Err(YaftpError::ReadCwdFaild),
+		}
+	}
+
+	/* real path of a client path , symlinks on the way are followed */
+	pub fn resolve(&self , path : &str) -> Result<PathBuf , YaftpError> {
+		self.resolve_path(path, true)
+	}
+
+	/* real path of a client path , a symlink at the end is not followed */
+	pub fn resolve_nofollow(&self , path : &str) -> Result<PathBuf , YaftpError> {
+		self.resolve_path(path, false)
+	}
+
+	fn resolve_path(&self , path : &str , follow : bool) -> Result<PathBuf , YaftpError> {
+
+		let root = match &self.root {
+			Some(p) => p,
+			None => return match Path::new(path).absolutize() {
+				Ok(p) => {
+					self.trace(&p);
+					Ok(p.to_path_buf())
+				},
+				Err(e) => Err(io_error(e)),
+			},
+		};
+
+		let virtual_path = match Path::new(path).absolutize_from(&self.home) {
+			Ok(p) => p.to_path_buf(),
+			Err(e) => return Err(io_error(e)),
+		};
+
+		let real = match virtual_path.strip_prefix("/") {
+			Ok(p) => root.join(p),
+			Err(_) => return Err(YaftpError::NoPermission),
+		};
+
+		self.trace(&real);
+
+		if !inside_root(root, &real, follow) {
+			log::warn!("user [{}] path escapes root : {}" , self.user , path);
+			return Err(YaftpError::NoPermission);
+		}
+
+		Ok(real)
+	}
+
+	/* the command touches real path , kept for the audit log even when refused */
+	fn trace(&self , real : &Path) {
+		self.trail.lock().unwrap().paths.push(real.to_path_buf());
+	}
+
+	/* the command moved n bytes of data */
+	pub fn transferred(&self , n : u64) {
+		self.trail.lock().unwrap().bytes += n;
+	}
+
+	/* the command is to move total bytes of data */
+	pub fn expect(&self , total : u64) {
+		self.trail.lock().unwrap().total = total;
+	}
+
+	/* paths and bytes of the running command , shared with the session registry */
+	pub fn trail(&self) -> Arc<Mutex<Trail>> {
+		self.trail.clone()
+	}
+
+	/* paths and bytes of the command , the next one starts empty */
+	pub fn take_trail(&self) -> Trail {
+		std::mem::take(&mut *self.trail.lock().unwrap())
+	}
+
+	/*
+		real path of a symlink target given by the client , an absolute target
+		is kept in the root . a relative one is stored as given .
+	*/
+	pub fn link_target(&self , target : &str) -> PathBuf {
+		match &self.root {
+			Some(root) if target.starts_with('/') => root.join(target.trim_start_matches('/')),
+			_ => PathBuf::from(target),
+		}
+	}
+
+	/* real folder of the user root , none when every path is allowed */
+	pub fn root(&self) -> Option<&Path> {
+		self.root.as_deref()
+	}
+
+	pub fn is_root(&self , real : &Path) -> bool {
+		match &self.root {
+			Some(p) => p == real,
+			None => false,
+		}
+	}
+
+	/* quotas a real path is counted in */
+	pub fn quotas(&self , real : &Path) -> Vec<Arc<Quota>> {
+		self.quotas.iter().filter(|p| real.starts_with(&p.path)).cloned().collect()
+	}
+
+	/* start counting a write to real path in its quotas */
+	pub fn charge(&self , real : &Path) -> Charge {
+		Charge::new(self.quotas(real))
+	}
+
+	/* start counting a move from one real path to another , a quota holding both already counts the file */
+	pub fn charge_move(&self , from : &Path , to : &Path) -> Charge {
+		let source = self.quotas(from);
+		Charge::new(self.quotas(to).into_iter().filter(|p| !source.iter().any(|q| Arc::ptr_eq(p, q))).collect())
+	}
+
+	/* files under real path were removed , its quotas count the disk again */
+	pub fn changed(&self , real : &Path) {
+		for quota in self.quotas(real) {
+			quota.invalidate();
+		}
+	}
+
+	/* path shown for a folder holding the session files , a folder above the root is the root */
+	pub fn display_folder(&self , real : &Path) -> String {
+		match &self.root {
+			Some(root) if root.starts_with(real) => String::from("/"),
+			_ => self.display(real),
+		}
+	}
+
+	/* path shown to the client , a path out of the root is reported as is */
+	pub fn display(&self , real : &Path) -> String {
+		if let Some(root) = &self.root {
+			if let Ok(p) = real.strip_prefix(root) {
+				return format!("/{}" , p.to_string_lossy());
+			}
+		}
+		real.to_string_lossy().to_string()
+	}
+}
+
+/*
+	check the deepest existing part of path stays in root once symlinks are
+	resolved , a dangling symlink is checked through its target . without
+	follow , the last component may be a symlink pointing anywhere .
+*/
+fn inside_root(root : &Path , path : &Path , follow : bool) -> bool {
+
+	let mut probe = path.to_path_buf();
+
+	if !follow && probe != root {
+		probe.pop();
+	}
+
+	let mut hops = 0;
+	loop {
+		if let Ok(p) = probe.canonicalize() {
+			return p.starts_with(root);
+		}
+
+		match fs::read_link(&probe) {
+			Ok(target) => {
+				hops += 1;
+				if hops > MAX_LINK_HOPS {
+					return false;
+				}
+				let parent = probe.parent().unwrap_or(root).to_path_buf();
+				probe = match parent.join(target).absolutize() {
+					Ok(p) => p.to_path_buf(),
+					Err(_) => return false,
+				};
+			},
+			Err(_) => {
+				if !probe.pop() {
+					return false;
+				}
+			},
+		}
+	}
+}
+
+#[test]
+fn test_session_resolve() {
+	let base = std::env::temp_dir().join("yaftp_test_account");
+	let _ = fs::remove_dir_all(&base);
+	fs::create_dir_all(base.join("root/home")).unwrap();
+	let root = fs::canonicalize(base.join("root")).unwrap();
+	crate::utils::make_symlink(Path::new("/etc"), &root.join("out")).unwrap();
+	crate::utils::make_symlink(Path::new("../missing"), &root.join("dangling")).unwrap();
+
+	let session = Session { user : String::from("test") , root : Some(root.clone()) , home : String::from("/home") , perms : PERM_LIST | PERM_READ , quotas : vec![Arc::new(Quota::new(QUOTA_ROOT, root.parent().unwrap().to_path_buf(), None, Some(1)))] , trail : Arc::new(Mutex::new(Trail::default())) };
+
+	assert_eq!(session.resolve("a.txt").unwrap() , root.join("home/a.txt"));
+	assert_eq!(session.resolve("/../../etc/passwd").unwrap() , root.join("etc/passwd"));
+	assert!(session.resolve("/out/passwd").is_err());
+	assert!(session.resolve("/out").is_err());
+	assert_eq!(session.resolve_nofollow("/out").unwrap() , root.join("out"));
+	assert!(session.resolve("/dangling").is_err());
+	assert_eq!(session.display(&root.join("home/a.txt")) , "/home/a.txt");
+	assert_eq!(session.display(Path::new("/etc")) , "/etc");
+	assert_eq!(session.display_folder(root.parent().unwrap()) , "/");
+	assert_eq!(session.quotas(&root.join("home")).len() , 1);
+	assert!(session.quotas(Path::new("/etc")).is_empty());
+
+	/* a move inside the quota is never refused by it , a copy is */
+	assert!(session.charge_move(&root.join("home/a.txt"), &root.join("b.txt")).reserve(5, 1).is_ok());
+	assert!(session.charge(&root.join("b.txt")).reserve(5, 1).is_err());
+	assert!(session.charge_move(Path::new("/etc/a.txt"), &root.join("b.txt")).reserve(5, 1).is_err());
+
+	assert!(session.check(PERM_READ).is_ok());
+	assert!(session.check(PERM_WRITE).is_err());
+	assert_eq!(parse_permissions(&[String::from("read") , String::from("mkdir")]).unwrap() , PERM_READ | PERM_MKDIR);
+	assert!(parse_permissions(&[String::from("root")]).is_err());
+
+	let _ = fs::remove_dir_all(&base);
+}
@@ -0,0 +1,107 @@
+use std::{path::Path, sync::Arc, time::Duration};
+
+use async_std::{io::BufReader, os::unix::net::{UnixListener, UnixStream}, task};
+use futures::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt};
+
+use crate::registry::Registry;
+
+/* a request line longer than this or slower than the timeout is dropped */
+const MAX_LINE_SIZE : u64 = 1024;
+const REQUEST_TIMEOUT : Duration = Duration::from_secs(5);
+
+/*
+	a local socket for operators , one line request and a text reply per
+	connection . the socket file is only readable by the server user .
+
+	sessions          one row per session , id|peer|user|command|paths|bytes|total|seconds
+	kill <id>         stop a session
+	shutdown          stop accepting and end once the running sessions ended
+*/
+pub async fn bind(path : &Path) -> Result<UnixListener , String> {
+	use std::os::unix::fs::{DirBuilderExt, FileTypeExt, PermissionsExt};
+
+	/* only a socket left by an old server is replaced , never another file */
+	if let Ok(meta) = std::fs::symlink_metadata(path) {
+		if !meta.file_type().is_socket() {
+			return Err(format!("admin socket {} faild : the path exists and is not a socket" , path.display()));
+		}
+		if let Err(e) = std::fs::remove_file(path) {
+			return Err(format!("remove old admin socket {} faild : {}" , path.display() , e));
+		}
+	}
+
+	/*
+		the socket is made in a private folder and moved in place once it is
+		0600 , nobody can connect to it between bind and chmod .
+	*/
+	let name = match path.file_name() {
+		Some(p) => p.to_string_lossy().to_string(),
+		None => return Err(format!("admin socket {} faild : not a file path" , path.display())),
+	};
+	let private = path.with_file_name(format!(".{}.{}" , name , std::process::id()));
+
+	if let Err(e) = std::fs::DirBuilder::new().mode(0o700).create(&private) {
+		return Err(format!("admin socket {} faild : {}" , private.display() , e));
+	}
+
+	let staged = private.join(&name);
+	let ret = async {
+		let listener = UnixListener::bind(&staged).await?;
+		std::fs::set_permissions(&staged, std::fs::Permissions::from_mode(0o600))?;
+		std::fs::rename(&staged, path)?;
+		Ok::<UnixListener , std::io::Error>(listener)
+	}.await;
+
+	let _ = std::fs::remove_file(&staged);
+	let _ = std::fs::remove_dir(&private);
+
+	ret.map_err(|e| format!("admin socket {} faild : {}" , path.display() , e))
+}
+
+fn reply(request : &str , registry : &Registry) -> String {
+	let words : Vec<&str> = request.split_whitespace().collect();
+
+	match words[..] {
+		["sessions"] => registry.list().iter().map(|p| p.row() + "\n").collect(),
+		["kill" , id] => match id.parse::<u64>() {
+			Ok(id) if registry.kill(id) => String::from("ok\n"),
+			Ok(_) => String::from("error : no such session\n"),
+			Err(_) => String::from("error : invalid session id\n"),
+		},
+		["shutdown"] => {
+			registry.shutdown();
+			String::from("ok\n")
+		},
+		_ => String::from("error : unknown command , use sessions , kill <id> or shutdown\n"),
+	}
+}
+
+async fn serve_request(stream : UnixStream , registry : Arc<Registry>) {
+	let mut line = String::new();
+	let mut reader = BufReader::new(&stream).take(MAX_LINE_SIZE);
+
+	match async_std::future::timeout(REQUEST_TIMEOUT, reader.read_line(&mut line)).await {
+		Ok(Ok(_)) => {},
+		_ => return,
+	};
+
+	log::info!("admin request [{}]" , line.trim());
+
+	if let Err(e) = (&stream).write_all(reply(&line, &registry).as_bytes()).await {
+		log::warn!("admin reply error : {}" , e);
+	}
+}
+
+pub async fn serve(listener : UnixListener , registry : Arc<Registry>) {
+	loop {
+		match listener.accept().await {
+			Ok((stream , _)) => {
+				task::spawn(serve_request(stream, registry.clone()));
+			},
+			Err(e) => {
+				log::error!("admin accept error : {}" , e);
+				task::sleep(Duration::from_secs(1)).await;
+			},
+		};
+	}
+}
@@ -0,0 +1,321 @@
+use std::{fs, io::{self, Read, Write}, path::{Component, Path, PathBuf}};
+
+use async_std::channel::{Receiver, Sender};
+use filetime::FileTime;
+use flate2::{Compression, read::GzDecoder, write::GzEncoder};
+use tar::{Archive, Builder, EntryType};
+
+use crate::quota::Charge;
+
+pub const COMPRESS_NONE : u8 = 0;
+pub const COMPRESS_GZIP : u8 = 1;
+pub const COMPRESS_ZSTD : u8 = 2;
+
+pub const CHUNK_SIZE : usize = 64 * 1024;
+
+/*
+	sync writer which forwards chunks to an async task , used to run the
+	blocking tar builder in a thread while the session streams its output .
+	a chunk is never bigger than CHUNK_SIZE , the reader rejects a bigger one .
+*/
+pub struct ChannelWriter {
+	sender : Sender<Vec<u8>>,
+	buf : Vec<u8>
+}
+
+impl ChannelWriter {
+	pub fn new(sender : Sender<Vec<u8>>) -> ChannelWriter {
+		ChannelWriter { sender , buf : Vec::with_capacity(CHUNK_SIZE) }
+	}
+
+	fn send(&mut self , chunk : Vec<u8>) -> io::Result<()> {
+		match self.sender.send_blocking(chunk){
+			Ok(_) => Ok(()),
+			Err(_) => Err(io::Error::new(io::ErrorKind::BrokenPipe, "archive receiver closed")),
+		}
+	}
+}
+
+impl Write for ChannelWriter {
+	fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+		self.buf.extend_from_slice(data);
+		while self.buf.len() >= CHUNK_SIZE {
+			let rest = self.buf.split_off(CHUNK_SIZE);
+			let chunk = std::mem::replace(&mut self.buf, rest);
+			self.send(chunk)?;
+		}
+		Ok(data.len())
+	}
+
+	fn flush(&mut self) -> io::Result<()> {
+		while !self.buf.is_empty() {
+			let rest = self.buf.split_off(std::cmp::min(CHUNK_SIZE, self.buf.len()));
+			let chunk = std::mem::replace(&mut self.buf, rest);
+			self.send(chunk)?;
+		}
+		Ok(())
+	}
+}
+
+impl Drop for ChannelWriter {
+	fn drop(&mut self) {
+		let _ = self.flush();
+	}
+}
+
+/*
+	sync reader fed by an async task , an empty chunk or a closed channel is
+	the end of the archive .
+*/
+pub struct ChannelReader {
+	receiver : Receiver<Vec<u8>>,
+	buf : Vec<u8>,
+	pos : usize
+}
+
+impl ChannelReader {
+	pub fn new(receiver : Receiver<Vec<u8>>) -> ChannelReader {
+		ChannelReader { receiver , buf : vec![] , pos : 0 }
+	}
+}
+
+impl Read for ChannelReader {
+	fn read(&mut self, data: &mut [u8]) -> io::Result<usize> {
+		while self.pos >= self.buf.len() {
+			self.buf = match self.receiver.recv_blocking(){
+				Ok(p) => p,
+				Err(_) => return Ok(0),
+			};
+			self.pos = 0;
+			if self.buf.is_empty() {
+				return Ok(0);
+			}
+		}
+		let n = std::cmp::min(data.len(), self.buf.len() - self.pos);
+		data[..n].copy_from_slice(&self.buf[self.pos..self.pos + n]);
+		self.pos += n;
+		Ok(n)
+	}
+}
+
+fn append_tree<W : Write>(builder : &mut Builder<W> , path : &Path) -> io::Result<()> {
+
+	let name = match path.file_name(){
+		Some(p) => p.to_os_string(),
+		None => ".".into(),
+	};
+
+	builder.follow_symlinks(false);
+
+	if fs::symlink_metadata(path)?.is_dir() {
+		builder.append_dir_all(&name, path)?;
+	} else {
+		builder.append_path_with_name(path, &name)?;
+	}
+
+	builder.finish()
+}
+
+/*
+	write a tar archive of path into writer , entries are named relative to
+	the parent of path so the archive unpacks into a folder of the same name .
+	symlinks are stored as links and never followed .
+*/
+pub fn build_archive<W : Write>(path : &Path , compress : u8 , writer : W) -> io::Result<()> {
+	match compress {
+		COMPRESS_GZIP => {
+			let mut builder = Builder::new(GzEncoder::new(writer, Compression::default()));
+			append_tree(&mut builder, path)?;
+			builder.into_inner()?.finish()?.flush()
+		},
+		COMPRESS_ZSTD => {
+			let mut builder = Builder::new(zstd::Encoder::new(writer, 0)?);
+			append_tree(&mut builder, path)?;
+			builder.into_inner()?.finish()?.flush()
+		},
+		_ => {
+			let mut builder = Builder::new(writer);
+			append_tree(&mut builder, path)?;
+			builder.into_inner()?.flush()
+		}
+	}
+}
+
+pub fn decompress_reader<'a , R : Read + 'a>(reader : R , compress : u8) -> io::Result<Box<dyn Read + 'a>> {
+	match compress {
+		COMPRESS_GZIP => Ok(Box::new(GzDecoder::new(reader))),
+		COMPRESS_ZSTD => Ok(Box::new(zstd::Decoder::new(reader)?)),
+		_ => Ok(Box::new(reader)),
+	}
+}
+
+pub const ENTRY_OK : &str = "ok";
+pub const ENTRY_REJECTED : &str = "rejected";
+pub const ENTRY_ERROR : &str = "error";
+pub const ENTRY_QUOTA : &str = "quota";
+
+pub struct EntryStatus {
+	pub status : &'static str,
+	pub path : String
+}
+
+fn escape_target(path : &Path) -> bool {
+	path.components().any(|p| !matches!(p, Component::Normal(_) | Component::CurDir))
+}
+
+/*
+	unpack an archive under target , keep mtimes , symlinks and the rwx bits .
+	setuid , setgid and sticky bits are kept only with special_bits , the
+	server never trusts them from a client . entries with an absolute path
+	or `..` are rejected and never written , an entry which fail to unpack
+	does not stop the others . extraction stops at the first entry which
+	does not fit in the quotas of charge .
+*/
+pub fn extract_archive<R : Read>(reader : R , compress : u8 , target : &Path , charge : &Charge , special_bits : bool) -> io::Result<Vec<EntryStatus>> {
+
+	let mut archive = Archive::new(decompress_reader(reader, compress)?);
+	archive.set_preserve_permissions(special_bits);
+	archive.set_preserve_mtime(true);
+
+	fs::create_dir_all(target)?;
+
+	/* folder mtimes change when children are written , restore them at last */
+	let mut folders : Vec<(PathBuf , u64)> = vec![];
+
+	let mut ret : Vec<EntryStatus> = vec![];
+	for entry in archive.entries()? {
+		let mut entry = entry?;
+
+		let path = entry.path()?.to_path_buf();
+		let name = path.to_string_lossy().to_string();
+
+		if escape_target(&path) {
+			log::warn!("reject archive entry : {}" , name);
+			ret.push(EntryStatus { status : ENTRY_REJECTED , path : name });
+			continue;
+		}
+
+		let typ = entry.header().entry_type();
+		let size = if typ.is_file() { entry.size() } else { 0 };
+
+		if charge.reserve(size, !typ.is_dir() as u64).is_err() {
+			ret.push(EntryStatus { status : ENTRY_QUOTA , path : name });
+			break;
+		}
+
+		let status = match entry.unpack_in(target){
+			Ok(true) => ENTRY_OK,
+			Ok(false) => ENTRY_REJECTED,
+			Err(e) => {
+				log::error!("unpack archive entry {} error : {}" , name , e);
+				ENTRY_ERROR
+			},
+		};
+
+		if status == ENTRY_OK && typ == EntryType::Directory {
+			folders.push((target.join(&path) , entry.header().mtime()?));
+		}
+
+		ret.push(EntryStatus { status , path : name });
+	}
+
+	for (path , mtime) in folders.iter().rev() {
+		let _ = filetime::set_symlink_file_times(path, FileTime::now(), FileTime::from_unix_time(*mtime as i64, 0));
+	}
+
+	Ok(ret)
+}
+
+#[test]
+fn test_archive() {
+	let root = std::env::temp_dir().join("yaftp_test_archive");
+	let _ = fs::remove_dir_all(&root);
+	fs::create_dir_all(root.join("src/sub")).unwrap();
+	fs::write(root.join("src/sub/file.txt"), "yaftp").unwrap();
+
+	for compress in [COMPRESS_NONE , COMPRESS_GZIP , COMPRESS_ZSTD] {
+		let mut data : Vec<u8> = vec![];
+		build_archive(&root.join("src"), compress, &mut data).unwrap();
+
+		let target = root.join(format!("dst{}", compress));
+		let ret = extract_archive(data.as_slice(), compress, &target, &Charge::new(vec![]), true).unwrap();
+		assert_eq!(ret.iter().filter(|p| p.status == ENTRY_OK).count() , 3);
+		assert_eq!(fs::read_to_string(target.join("src/sub/file.txt")).unwrap() , "yaftp");
+	}
+
+	/* a big file of noise crosses many chunks , compressed or not */
+	let mut seed = 1u32;
+	let noise : Vec<u8> = (0..CHUNK_SIZE * 5).map(|_| { seed = seed.wrapping_mul(1103515245).wrapping_add(12345); (seed >> 16) as u8 }).collect();
+	fs::write(root.join("src/sub/big.bin"), &noise).unwrap();
+
+	for compress in [COMPRESS_NONE , COMPRESS_GZIP , COMPRESS_ZSTD] {
+		let (sender , receiver) = async_std::channel::unbounded::<Vec<u8>>();
+		build_archive(&root.join("src"), compress, ChannelWriter::new(sender)).unwrap();
+
+		let (forward , chunks) = async_std::channel::unbounded::<Vec<u8>>();
+		let mut count = 0;
+		while let Ok(chunk) = receiver.try_recv() {
+			assert!(!chunk.is_empty() && chunk.len() <= CHUNK_SIZE);
+			forward.try_send(chunk).unwrap();
+			count += 1;
+		}
+		assert!(count > 1);
+		drop(forward);
+
+		let target = root.join(format!("chunked{}", compress));
+		let ret = extract_archive(ChannelReader::new(chunks), compress, &target, &Charge::new(vec![]), true).unwrap();
+		assert_eq!(ret.iter().filter(|p| p.status == ENTRY_OK).count() , 4);
+		assert_eq!(fs::read(target.join("src/sub/big.bin")).unwrap() , noise);
+	}
+
+	let mut data : Vec<u8> = vec![];
+	{
+		let mut builder = Builder::new(&mut data);
+		for name in ["../evil.txt" , "/abs.txt" , "good.txt"] {
+			let mut header = tar::Header::new_gnu();
+			header.as_gnu_mut().unwrap().name[..name.len()].copy_from_slice(name.as_bytes());
+			header.set_size(5);
+			header.set_mode(0o644);
+			header.set_cksum();
+			builder.append(&header, "yaftp".as_bytes()).unwrap();
+		}
+		builder.finish().unwrap();
+	}
+
+	let target = root.join("dst_escape");
+	let ret = extract_archive(data.as_slice(), COMPRESS_NONE, &target, &Charge::new(vec![]), true).unwrap();
+	assert_eq!(ret[0].status , ENTRY_REJECTED);
+	assert_eq!(ret[1].status , ENTRY_REJECTED);
+	assert_eq!(ret[2].status , ENTRY_OK);
+	assert!(!root.join("evil.txt").exists());
+	assert!(target.join("good.txt").exists());
+
+	let quota = std::sync::Arc::new(crate::quota::Quota::new(crate::quota::QUOTA_ROOT, target.clone(), Some(8), None));
+	let ret = extract_archive(data.as_slice(), COMPRESS_NONE, &target.join("sub"), &Charge::new(vec![quota]), true).unwrap();
+	assert_eq!(ret[2].status , ENTRY_QUOTA);
+	assert!(!target.join("sub/good.txt").exists());
+
+	/* a setuid entry from an untrusted archive keeps only its rwx bits */
+	#[cfg(unix)]
+	{
+		use std::os::unix::fs::PermissionsExt;
+
+		let mut data : Vec<u8> = vec![];
+		{
+			let mut builder = Builder::new(&mut data);
+			let mut header = tar::Header::new_gnu();
+			header.set_path("suid").unwrap();
+			header.set_size(5);
+			header.set_mode(0o4755);
+			header.set_cksum();
+			builder.append(&header, "yaftp".as_bytes()).unwrap();
+			builder.finish().unwrap();
+		}
+
+		let target = root.join("dst_mode");
+		extract_archive(data.as_slice(), COMPRESS_NONE, &target, &Charge::new(vec![]), false).unwrap();
+		assert_eq!(fs::metadata(target.join("suid")).unwrap().permissions().mode() & 0o7777 , 0o755);
+	}
+
+	fs::remove_dir_all(&root).unwrap();
+}
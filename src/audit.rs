@@ -0,0 +1,189 @@
+use std::{fs::{self, File, OpenOptions}, io::Write, net::SocketAddr, path::{Path, PathBuf}, sync::Mutex, time::Duration};
+
+use chrono::{DateTime, SecondsFormat, offset::Utc};
+
+/* the audit file is rotated once it grows past , keeping that many old files */
+pub const DEFAULT_MAX_SIZE : u64 = 100 * 1024 * 1024;
+pub const DEFAULT_KEEP : usize = 5;
+
+/* real paths and bytes a command touched , gathered by the session while it runs */
+#[derive(Default)]
+pub struct Trail {
+	pub paths : Vec<PathBuf>,
+	pub bytes : u64,
+	/* bytes the transfer is to move , 0 when unknown */
+	pub total : u64
+}
+
+/* one line of the audit log , a user is none before login */
+pub struct Entry<'a> {
+	pub time : DateTime<Utc>,
+	pub peer : Option<SocketAddr>,
+	pub user : Option<&'a str>,
+	pub command : u8,
+	pub name : &'a str,
+	pub trail : &'a Trail,
+	pub duration : Duration,
+	pub retcode : u8
+}
+
+impl Entry<'_> {
+
+	pub fn to_json(&self) -> String {
+		let peer = match self.peer {
+			Some(p) => quote(&p.to_string()),
+			None => String::from("null"),
+		};
+
+		let user = match self.user {
+			Some(p) => quote(p),
+			None => String::from("null"),
+		};
+
+		let paths : Vec<String> = self.trail.paths.iter().map(|p| quote(&p.to_string_lossy())).collect();
+
+		format!(
+			"{{\"time\":{},\"peer\":{},\"user\":{},\"command\":{},\"name\":{},\"paths\":[{}],\"bytes\":{},\"duration_ms\":{},\"retcode\":{}}}",
+			quote(&self.time.to_rfc3339_opts(SecondsFormat::Millis, true)),
+			peer,
+			user,
+			self.command,
+			quote(self.name),
+			paths.join(","),
+			self.trail.bytes,
+			self.duration.as_millis(),
+			self.retcode
+		)
+	}
+}
+
+/* json string of text */
+fn quote(text : &str) -> String {
+	let mut ret = String::with_capacity(text.len() + 2);
+	ret.push('"');
+	for c in text.chars() {
+		match c {
+			'"' => ret.push_str("\\\""),
+			'\\' => ret.push_str("\\\\"),
+			'\n' => ret.push_str("\\n"),
+			'\r' => ret.push_str("\\r"),
+			'\t' => ret.push_str("\\t"),
+			c if (c as u32) < 0x20 => ret.push_str(&format!("\\u{:04x}" , c as u32)),
+			c => ret.push(c),
+		}
+	}
+	ret.push('"');
+	ret
+}
+
+struct Sink {
+	path : PathBuf,
+	max_size : u64,
+	keep : usize,
+	file : File,
+	size : u64
+}
+
+impl Sink {
+
+	fn open(path : &Path) -> std::io::Result<(File , u64)> {
+		let file = OpenOptions::new().create(true).append(true).open(path)?;
+		let size = file.metadata()?.len();
+		Ok((file , size))
+	}
+
+	/* path.1 is the newest old file , the oldest past keep is dropped */
+	fn rotate(&mut self) -> std::io::Result<()> {
+		let old = |n : usize| PathBuf::from(format!("{}.{}" , self.path.display() , n));
+
+		if self.keep == 0 {
+			fs::remove_file(&self.path)?;
+		} else {
+			let _ = fs::remove_file(old(self.keep));
+			for n in (1..self.keep).rev() {
+				let _ = fs::rename(old(n), old(n + 1));
+			}
+			fs::rename(&self.path, old(1))?;
+		}
+
+		(self.file , self.size) = Sink::open(&self.path)?;
+		Ok(())
+	}
+
+	fn write(&mut self , line : &str) -> std::io::Result<()> {
+		if self.max_size != 0 && self.size > 0 && self.size + line.len() as u64 > self.max_size {
+			self.rotate()?;
+		}
+		self.file.write_all(line.as_bytes())?;
+		self.size += line.len() as u64;
+		Ok(())
+	}
+}
+
+/* where entries go , nothing is written until set */
+static AUDIT : Mutex<Option<Sink>> = Mutex::new(None);
+
+/* append entries to path , rotated past max_size bytes (0 never) keeping keep old files */
+pub fn set_audit(path : &Path , max_size : u64 , keep : usize) -> Result<() , String> {
+	let (file , size) = match Sink::open(path) {
+		Ok(p) => p,
+		Err(e) => return Err(format!("open audit file {} faild : {}" , path.display() , e)),
+	};
+
+	*AUDIT.lock().unwrap() = Some(Sink { path : path.to_path_buf() , max_size , keep , file , size });
+	Ok(())
+}
+
+/* stop writing entries , the file is closed */
+pub fn unset_audit() {
+	*AUDIT.lock().unwrap() = None;
+}
+
+pub fn record(entry : &Entry) {
+	let mut audit = AUDIT.lock().unwrap();
+
+	let sink = match audit.as_mut() {
+		Some(p) => p,
+		None => return,
+	};
+
+	if let Err(e) = sink.write(&(entry.to_json() + "\n")) {
+		log::error!("write audit file {} faild : {}" , sink.path.display() , e);
+	}
+}
+
+#[test]
+fn test_audit() {
+	let trail = Trail { paths : vec![PathBuf::from("/tmp/a \"b\"\n")] , bytes : 5 , total : 0 };
+	let entry = Entry {
+		time : DateTime::from_timestamp(0, 0).unwrap(),
+		peer : Some("127.0.0.1:8000".parse().unwrap()),
+		user : None,
+		command : 7,
+		name : "put",
+		trail : &trail,
+		duration : Duration::from_millis(12),
+		retcode : 0
+	};
+	assert_eq!(entry.to_json() , "{\"time\":\"1970-01-01T00:00:00.000Z\",\"peer\":\"127.0.0.1:8000\",\"user\":null,\"command\":7,\"name\":\"put\",\"paths\":[\"/tmp/a \\\"b\\\"\\n\"],\"bytes\":5,\"duration_ms\":12,\"retcode\":0}");
+	assert_eq!(quote("\u{1}") , "\"\\u0001\"");
+
+	let dir = std::env::temp_dir().join("yaftp_test_audit");
+	let _ = fs::remove_dir_all(&dir);
+	fs::create_dir_all(&dir).unwrap();
+	let path = dir.join("audit.log");
+
+	let line = entry.to_json().len() as u64 + 1;
+	set_audit(&path, line * 2, 2).unwrap();
+	for _ in 0..7 {
+		record(&entry);
+	}
+	unset_audit();
+
+	assert_eq!(fs::metadata(&path).unwrap().len() , line);
+	assert_eq!(fs::metadata(dir.join("audit.log.1")).unwrap().len() , line * 2);
+	assert_eq!(fs::metadata(dir.join("audit.log.2")).unwrap().len() , line * 2);
+	assert!(!dir.join("audit.log.3").exists());
+
+	fs::remove_dir_all(&dir).unwrap();
+}
@@ -1,20 +1,20 @@
-use futures::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt};
+use futures::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt, Stream, stream};
 use async_std::{fs::{self, File}, net::{TcpListener, TcpStream}};
-use indicatif::{ProgressBar, ProgressStyle};
 
-use std::{io::{Error, SeekFrom}, net::Shutdown};
-use crate::{common::{YaftpError, retcode_error}, println_err, println_info, utils::*};
+use std::{io::{Error, SeekFrom}, net::Shutdown, pin::Pin, sync::Arc};
+use crate::{archive::{CHUNK_SIZE, ENTRY_OK, ChannelReader, ChannelWriter, build_archive, extract_archive}, common::{DirEntry, FileInfo, FileKind, FindEntry, FindFilter, GrepLine, LINK_NOFOLLOW, QuotaUsage, SessionInfo, UntarEntry, YaftpError, from_timestamp, retcode_error}, progress::{Cancel, Meter, Progress, Silent}, quota::Charge, reverse::{self, Secret}, throttle::{DOWNLOAD, Throttle, UPLOAD}, utils::*, watch::WatchEvent};
 
 pub struct Client {
-	conn : TcpStream
+	conn : TcpStream,
+	auth : Option<(String , String)>,
+	limit : [u64 ; 2],
+	progress : Arc<dyn Progress>,
+	cancel : Cancel
 }
 
 impl Drop for Client{
     fn drop(&mut self) {
-		match self.conn.shutdown(Shutdown::Both){
-			Ok(_) => {},
-			Err(_) => {},
-		};
+		let _ = self.conn.shutdown(Shutdown::Both);
     }
 }
 
@@ -22,30 +22,73 @@ pub struct SpawnClient {
 	master : Option<TcpStream>,
 	slave : Option<TcpListener>,
 	ip : String ,
-	port : String 
-
+	port : String ,
+	auth : Option<(String , String)>,
+	limit : [u64 ; 2],
+	progress : Arc<dyn Progress>,
+	cancel : Cancel,
+	secret : Option<Secret>
 }
 
 impl SpawnClient {
 
-	pub async fn new(ip : &String , port : &String) -> SpawnClient {
-		SpawnClient{ip : ip.clone() , port : port.clone() , master : None , slave : None}
+	pub async fn new(ip : &str , port : &str) -> SpawnClient {
+		SpawnClient{ip : ip.to_string() , port : port.to_string() , master : None , slave : None , auth : None , limit : [0 , 0] , progress : Arc::new(Silent) , cancel : Cancel::new() , secret : None}
 	}
 
 	pub async fn new_t(master : TcpStream , slave : TcpListener) -> SpawnClient {
-		SpawnClient{ip : String::new() , port : String::new() , master : Some(master) , slave : Some(slave)}
+		SpawnClient{ip : String::new() , port : String::new() , master : Some(master) , slave : Some(slave) , auth : None , limit : [0 , 0] , progress : Arc::new(Silent) , cancel : Cancel::new() , secret : None}
+	}
+
+	/* every spawned client login as user before its command */
+	pub fn set_auth(self : &mut SpawnClient , user : &str , password : &str) {
+		self.auth = Some((user.to_string() , password.to_string()));
+	}
+
+	/* bytes per second of every spawned client , upload then download , 0 is unlimited */
+	pub fn set_limit(self : &mut SpawnClient , limit : [u64 ; 2]) {
+		self.limit = limit;
+	}
+
+	/* every spawned client tells progress how its transfers go */
+	pub fn set_progress(self : &mut SpawnClient , progress : Arc<dyn Progress>) {
+		self.progress = progress;
+	}
+
+	/* every spawned client stops its transfer once cancel is cancelled */
+	pub fn set_cancel(self : &mut SpawnClient , cancel : Cancel) {
+		self.cancel = cancel;
+	}
+
+	/* in reverse mode , a connection back is only used once the slave proved secret */
+	pub fn set_secret(self : &mut SpawnClient , secret : Secret) {
+		self.secret = Some(secret);
 	}
 
 	pub async fn spawn(self : &SpawnClient) -> Result<Client , Error> {
 
-		if self.ip.is_empty() {
-			self.master.as_ref().unwrap().write_all(&mut [0x55].to_vec()).await?;
-			let (stream , _) = self.slave.as_ref().unwrap().accept().await?;
+		let mut client = if self.ip.is_empty() {
+			self.master.as_ref().unwrap().write_all(&[0x55]).await?;
+			let stream = loop {
+				let (mut stream , _) = self.slave.as_ref().unwrap().accept().await?;
 
-			return Client::from(stream).await;
-		}
+				/* anyone may connect to the port , wait for the slave */
+				match &self.secret {
+					Some(secret) if reverse::prove_master(&mut stream, secret).await.is_err() => continue,
+					_ => break stream,
+				}
+			};
 
-		return Client::new(&self.ip , &self.port).await;
+			Client::from(stream).await?
+		} else {
+			Client::new(&self.ip , &self.port).await?
+		};
+
+		client.auth = self.auth.clone();
+		client.limit = self.limit;
+		client.progress = self.progress.clone();
+		client.cancel = self.cancel.clone();
+		Ok(client)
 	}
 }
 
@@ -53,16 +96,13 @@ impl Client {
 	
 	pub async fn new(ip : &String ,port : &String) -> Result<Client , Error>{
 		let fulladdr = format!("{}:{}", ip ,port);
-		let conn = match TcpStream::connect(fulladdr).await{
-			Ok(p) => p,
-			Err(e) => return Err(e),
-		};
+		let conn = TcpStream::connect(fulladdr).await?;
 		
-		Ok(Client{conn})
+		Ok(Client{conn , auth : None , limit : [0 , 0] , progress : Arc::new(Silent) , cancel : Cancel::new()})
 	}
 
 	pub async fn from(conn : TcpStream) -> Result<Client , Error> {
-		Ok(Client{conn})
+		Ok(Client{conn , auth : None , limit : [0 , 0] , progress : Arc::new(Silent) , cancel : Cancel::new()})
 	}
 
 	async fn handshake(self : &mut Client) -> Result<Vec<u8> , YaftpError>{
@@ -73,7 +113,7 @@ impl Client {
 		| 1(u8) |   1(u8)  | 1 to 255 (u8) |
 		+-------+----------+---------------+
 		*/
-		match self.conn.write_all(&[1u8, 8u8 , 1u8 , 2u8 , 3u8 , 4u8 , 5u8 , 6u8 , 7u8, 8u8]).await{
+		match self.conn.write_all(&[1u8, 25u8 , 1u8 , 2u8 , 3u8 , 4u8 , 5u8 , 6u8 , 7u8, 8u8, 9u8 , 10u8 , 11u8 , 12u8 , 13u8 , 14u8 , 15u8 , 16u8 , 17u8 , 18u8 , 19u8 , 20u8 , 21u8 , 22u8 , 23u8 , 24u8 , 25u8]).await{
 			Ok(_) => {},
 			Err(_) => {
 				return Err(YaftpError::UnknownNetwordError);
@@ -102,12 +142,26 @@ impl Client {
 		};
 
 		if !check_support_methods(&methods){
-			println_err!("the client has not support method");
+			log::warn!("the server has not support method of the client");
+		}
+
+		if let Some((user , password)) = self.auth.clone() {
+			self.login(&user, &password).await?;
 		}
 
 		Ok(methods.to_vec())
 	}
 
+	/* auth is sent right after the handshake , the command follows it */
+	async fn login(self : &mut Client , user : &String , password : &String) -> Result<u32 , YaftpError> {
+
+		self.send_command(21u8, 2).await?;
+		self.send_argument(&mut user.as_bytes().to_vec()).await?;
+		self.send_argument(&mut password.as_bytes().to_vec()).await?;
+
+		self.read_reply().await
+	}
+
 	async fn send_command(self : &mut Client , command : u8 , narg : u32) -> Result<Vec<u8>, YaftpError>{
 		/*
 		+-------+--------+
@@ -120,7 +174,7 @@ impl Client {
 		let mut command = vec![command];
 		command.append(&mut b_narg.to_vec());
 
-		match self.conn.write_all(&mut command).await{
+		match self.conn.write_all(&command).await{
 			Ok(_) => {},
 			Err(_) => {
 				return Err(YaftpError::UnknownNetwordError);
@@ -152,6 +206,17 @@ impl Client {
 		Ok(u32::from_be_bytes(reply[1..5].try_into().unwrap()))
 	}
 
+	/*
+		a write faild , the server may have refused the command (e.g. LOCKED or
+		NO_PERMISSION) and closed before reading all of it , return its code .
+	*/
+	async fn refused(self : &mut Client , err : YaftpError) -> YaftpError {
+		match self.read_reply().await{
+			Err(YaftpError::UnknownNetwordError) | Ok(_) => err,
+			Err(e) => e,
+		}
+	}
+
 	async fn send_argument(self : &mut Client , data :&mut Vec<u8>) -> Result<Vec<u8>, YaftpError>{
 		/*
 		+-----------------+---------------------+
@@ -166,7 +231,7 @@ impl Client {
 		argument.append(&mut b_size.to_vec());
 		argument.append(data);
 
-		match self.conn.write_all(&mut argument).await{
+		match self.conn.write_all(&argument).await{
 			Ok(_) => {},
 			Err(_) => {
 				return Err(YaftpError::UnknownNetwordError);
@@ -209,201 +274,147 @@ impl Client {
 		Ok(arg.to_vec())
 	}
 
-	pub async fn ls(self : &mut Client , path : &String) -> Result<Vec<String> ,YaftpError> {
+	/* an 8 bytes big endian argument , a shorter one is an error */
+	async fn read_u64_argument(self : &mut Client) -> Result<u64, YaftpError> {
 
-		match self.handshake().await{
-			Ok(_) => {},
-			Err(e) => {
-				println_err!("yaftp handshake error");
-				return Err(e);
-			},
-		};
+		let arg = self.read_argument(8).await?;
 
-		match self.send_command(1u8, 1).await{
-			Ok(_) => {},
-			Err(e) => {
-				println_err!("yaftp send command error");
-				return Err(e);
-			},
-		};
+		match arg.try_into(){
+			Ok(p) => Ok(u64::from_be_bytes(p)),
+			Err(_) => Err(YaftpError::ArgumentError),
+		}
+	}
 
-		match self.send_argument(&mut path.as_bytes().to_vec()).await{
-			Ok(_) => {},
-			Err(e) => {
-				println_err!("yaftp send argument error");
-				return Err(e);
-			},
-		};
+	async fn read_stream_rows(self : &mut Client , max_size : u64) -> Result<Vec<String>, YaftpError> {
 
-		let narg = match self.read_reply().await{
-			Ok(p) => p,
-			Err(e) => {
-				println_err!("server error code : {}" , e);
-				return Err(e);
-			},
-		};
-		
 		let mut ret : Vec<String> = vec![];
-		let mut i = 0 ; 
-		while i < narg {
-			let arg = match self.read_argument(2048).await{
+		loop {
+			let arg = self.read_argument(max_size).await?;
+
+			if arg.is_empty() {
+				break;
+			}
+
+			let row = match String::from_utf8(arg){
 				Ok(p) => p,
-				Err(e) => {
-					println_err!("yaftp read argument error");
-					return Err(e);
+				Err(_) => {
+					return Err(YaftpError::ArgumentError);
 				},
 			};
+			ret.push(row);
+		}
+
+		Ok(ret)
+	}
+
+	pub async fn ls(self : &mut Client , path : &String) -> Result<Vec<DirEntry> ,YaftpError> {
+
+		self.handshake().await?;
+
+		self.send_command(1u8, 1).await?;
+
+		self.send_argument(&mut path.as_bytes().to_vec()).await?;
+
+		let narg = self.read_reply().await?;
+		
+		let mut ret : Vec<DirEntry> = vec![];
+		let mut i = 0 ; 
+		while i < narg {
+			let arg = self.read_argument(2048).await?;
 
 			let row = match String::from_utf8(arg){
 				Ok(p) => p,
 				Err(_) => {
-					println_err!("format argument to utf8 string faild");
 					return Err(YaftpError::ArgumentError);
 				},
 			}; 
-			ret.push(row);
+			ret.push(DirEntry::parse(&row)?);
 			i +=1;
 		}
 
 		Ok(ret)
 	}
 
-	pub async fn info(self : &mut Client , path : &String) -> Result<(Vec<u64> , String),YaftpError> {
+	pub async fn info(self : &mut Client , path : &String) -> Result<FileInfo,YaftpError> {
+		self.stat(path, 0).await
+	}
 
-		match self.handshake().await{
-			Ok(_) => {},
-			Err(e) => {
-				println_err!("yaftp handshake error");
-				return Err(e);
-			},
-		};
+	/*
+		information of the path itself without follow a symlink , the link
+		target is in target (empty if path is not a symlink) .
+	*/
+	pub async fn lstat(self : &mut Client , path : &String) -> Result<FileInfo,YaftpError> {
+		self.stat(path, LINK_NOFOLLOW).await
+	}
 
-		match self.send_command(9u8, 1).await{
-			Ok(_) => {},
-			Err(e) => {
-				println_err!("yaftp send command error");
-				return Err(e);
-			},
-		};
+	async fn stat(self : &mut Client , path : &String , flags : u8) -> Result<FileInfo,YaftpError> {
 
-		match self.send_argument(&mut path.as_bytes().to_vec()).await{
-			Ok(_) => {},
-			Err(e) => {
-				println_err!("yaftp send argument error");
-				return Err(e);
-			},
-		};
+		self.handshake().await?;
 
-		let _ = match self.read_reply().await{
-			Ok(p) => p,
-			Err(e) => {
-				//println!("server error code : {}" , e);
-				return Err(e);
-			},
-		};
-		
-		let mut ret : Vec<u64> = vec![];
+		let narg = if flags != 0 { 2 } else { 1 };
 
-		let arg = match self.read_argument(1).await{
-			Ok(p) => p,
-			Err(e) => {
-				println_err!("yaftp read argument error");
-				return Err(e);
-			},
-		};
+		self.send_command(9u8, narg).await?;
 
-		ret.push(arg[0] as u64);
+		self.send_argument(&mut path.as_bytes().to_vec()).await?;
 
-		let arg = match self.read_argument(8).await{
-			Ok(p) => p,
-			Err(e) => {
-				println_err!("yaftp read argument error");
-				return Err(e);
-			},
-		};
+		if flags != 0 {
+			self.send_argument(&mut vec![flags]).await?;
+		}
 
-		let size = u64::from_be_bytes(arg[0..8].try_into().unwrap());
-		ret.push(size);
+		let narg = self.read_reply().await?;
+		
+		let arg = self.read_argument(1).await?;
 
-		let arg = match self.read_argument(8).await{
-			Ok(p) => p,
-			Err(e) => {
-				println_err!("yaftp read argument error");
-				return Err(e);
-			},
+		let kind = match arg.first() {
+			Some(p) => FileKind::from_code(*p),
+			None => return Err(YaftpError::ArgumentError),
 		};
 
-		let mt = u64::from_be_bytes(arg[0..8].try_into().unwrap());
-		ret.push(mt);
+		let size = self.read_u64_argument().await?;
 
-		let arg = match self.read_argument(8).await{
-			Ok(p) => p,
-			Err(e) => {
-				println_err!("yaftp read argument error");
-				return Err(e);
-			},
-		};
+		let mt = self.read_u64_argument().await?;
 
-		let at = u64::from_be_bytes(arg[0..8].try_into().unwrap());
-		ret.push(at);
+		let at = self.read_u64_argument().await?;
 
-		let arg = match self.read_argument(2048).await{
-			Ok(p) => p,
-			Err(e) => {
-				println_err!("yaftp read argument error");
-				return Err(e);
-			},
-		};
+		let arg = self.read_argument(2048).await?;
 
 		let path = match String::from_utf8(arg) {
 			Ok(p) => p,
 			Err(_) => {
-				println_err!("format argument to utf8 string faild");
 				return Err(YaftpError::ArgumentError);
 			}
 		};
+
+		/* older servers do not send the link target */
+		let mut target = String::new();
+
+		if narg > 5 {
+			let arg = self.read_argument(2048).await?;
+
+			target = match String::from_utf8(arg) {
+				Ok(p) => p,
+				Err(_) => {
+					return Err(YaftpError::ArgumentError);
+				}
+			};
+		}
 		
-		Ok((ret , path))
+		Ok(FileInfo { kind , size , modified : from_timestamp(mt) , accessed : from_timestamp(at) , path , target })
 	}
 
 	pub async fn cwd(self : &mut Client) -> Result<String,YaftpError> {
 
-		match self.handshake().await{
-			Ok(_) => {},
-			Err(e) => {
-				println_err!("yaftp handshake error");
-				return Err(e);
-			},
-		};
+		self.handshake().await?;
 
-		match self.send_command(2u8, 0).await{
-			Ok(_) => {},
-			Err(e) => {
-				println_err!("yaftp send command error");
-				return Err(e);
-			},
-		};
+		self.send_command(2u8, 0).await?;
 
-		match self.read_reply().await{
-			Ok(p) => p,
-			Err(e) => {
-				println_err!("server error code : {}" , e);
-				return Err(e);
-			},
-		};
+		self.read_reply().await?;
 
-		let arg = match self.read_argument(2048).await{
-			Ok(p) => p,
-			Err(e) => {
-				println_err!("yaftp read argument error");
-				return Err(e);
-			},
-		};
+		let arg = self.read_argument(2048).await?;
 		
 		let ret = match String::from_utf8(arg) {
 			Ok(p) => p,
 			Err(_) => {
-				println_err!("format argument to utf8 string faild");
 				return Err(YaftpError::ArgumentError);
 			}
 		};
@@ -411,208 +422,260 @@ impl Client {
 		Ok(ret)
 	}
 
-	pub async fn cp(self : &mut Client , srcpath : &String , targetpath : &String) -> Result<u32 , YaftpError> {
+	pub async fn cp(self : &mut Client , srcpath : &String , targetpath : &String , nofollow : bool) -> Result<u32 , YaftpError> {
 
-		match self.handshake().await{
-			Ok(_) => {},
-			Err(e) => {
-				println_err!("yaftp handshake error");
-				return Err(e);
-			},
-		};
+		self.handshake().await?;
 
-		match self.send_command(3u8, 2).await{
-			Ok(_) => {},
-			Err(e) => {
-				println_err!("yaftp send command error");
-				return Err(e);
-			},
-		};
+		let narg = if nofollow { 3 } else { 2 };
 
-		match self.send_argument(&mut srcpath.as_bytes().to_vec()).await{
-			Ok(_) => {},
-			Err(e) => {
-				println_err!("yaftp send argument error");
-				return Err(e);
-			},
-		};
+		self.send_command(3u8, narg).await?;
 
-		match self.send_argument(&mut targetpath.as_bytes().to_vec()).await{
-			Ok(_) => {},
-			Err(e) => {
-				println_err!("yaftp send argument error");
-				return Err(e);
-			},
-		};
+		self.send_argument(&mut srcpath.as_bytes().to_vec()).await?;
 
-		match self.read_reply().await{
-			Ok(p) => {
-				Ok(p)
-			},
-			Err(e) => {
-				println_err!("server error code : {}" , e);
-				Err(e)
-			},
+		self.send_argument(&mut targetpath.as_bytes().to_vec()).await?;
+
+		if nofollow {
+			self.send_argument(&mut vec![LINK_NOFOLLOW]).await?;
 		}
+
+		self.read_reply().await
 	}
 
 	pub async fn mv(self : &mut Client , srcpath : &String , targetpath : &String) -> Result<u32 , YaftpError> {
 
-		match self.handshake().await{
-			Ok(_) => {},
-			Err(e) => {
-				println_err!("yaftp handshake error");
-				return Err(e);
-			},
-		};
+		self.handshake().await?;
 
-		match self.send_command(5u8, 2).await{
-			Ok(_) => {},
-			Err(e) => {
-				println_err!("yaftp send command error");
-				return Err(e);
-			},
-		};
+		self.send_command(5u8, 2).await?;
 
-		match self.send_argument(&mut srcpath.as_bytes().to_vec()).await{
-			Ok(_) => {},
-			Err(e) => {
-				println_err!("yaftp send argument error");
-				return Err(e);
-			},
-		};
+		self.send_argument(&mut srcpath.as_bytes().to_vec()).await?;
 
-		match self.send_argument(&mut targetpath.as_bytes().to_vec()).await{
-			Ok(_) => {},
-			Err(e) => {
-				println_err!("yaftp send argument error");
-				return Err(e);
-			},
-		};
+		self.send_argument(&mut targetpath.as_bytes().to_vec()).await?;
 
-		match self.read_reply().await{
-			Ok(p) => {
-				Ok(p)
-			},
-			Err(e) => {
-				println_err!("server error code : {}" , e);
-				Err(e)
-			},
-		}
+		self.read_reply().await
 	}
 
 	pub async fn mkd(self : &mut Client , path : &String) -> Result<u32 , YaftpError> {
 
-		match self.handshake().await{
-			Ok(_) => {},
-			Err(e) => {
-				println_err!("yaftp handshake error");
-				return Err(e);
-			},
-		};
+		self.handshake().await?;
 
-		match self.send_command(4u8, 1).await{
-			Ok(_) => {},
-			Err(e) => {
-				println_err!("yaftp send command error");
-				return Err(e);
-			},
-		};
+		self.send_command(4u8, 1).await?;
 
-		match self.send_argument(&mut path.as_bytes().to_vec()).await{
-			Ok(_) => {},
-			Err(e) => {
-				println_err!("yaftp send argument error");
-				return Err(e);
-			},
-		};
+		self.send_argument(&mut path.as_bytes().to_vec()).await?;
 
-		match self.read_reply().await{
-			Ok(p) => {
-				Ok(p)
-			},
-			Err(e) => {
-				println_err!("server error code : {}" , e);
-				Err(e)
-			},
+		self.read_reply().await
+	}
+
+	pub async fn rm(self : &mut Client , path : &String , nofollow : bool) -> Result<u32 , YaftpError> {
+
+		self.handshake().await?;
+
+		let narg = if nofollow { 2 } else { 1 };
+
+		self.send_command(6u8, narg).await?;
+
+		self.send_argument(&mut path.as_bytes().to_vec()).await?;
+
+		if nofollow {
+			self.send_argument(&mut vec![LINK_NOFOLLOW]).await?;
 		}
+
+		self.read_reply().await
 	}
 
-	pub async fn rm(self : &mut Client , path : &String) -> Result<u32 , YaftpError> {
+	/*
+		create a link at linkpath , a symbolic link keeps target as given and a
+		hard link needs target to be an existing file .
+	*/
+	pub async fn ln(self : &mut Client , target : &String , linkpath : &String , symbolic : bool) -> Result<u32 , YaftpError> {
 
-		match self.handshake().await{
-			Ok(_) => {},
-			Err(e) => {
-				println_err!("yaftp handshake error");
-				return Err(e);
-			},
-		};
+		self.handshake().await?;
 
-		match self.send_command(6u8, 1).await{
-			Ok(_) => {},
-			Err(e) => {
-				println_err!("yaftp send command error");
-				return Err(e);
-			},
-		};
+		self.send_command(16u8, 3).await?;
 
-		match self.send_argument(&mut path.as_bytes().to_vec()).await{
-			Ok(_) => {},
-			Err(e) => {
-				println_err!("yaftp send argument error");
-				return Err(e);
-			},
-		};
+		self.send_argument(&mut target.as_bytes().to_vec()).await?;
 
-		match self.read_reply().await{
-			Ok(p) => {
-				Ok(p)
-			},
-			Err(e) => {
-				println_err!("server error code : {}" , e);
-				Err(e)
-			},
+		self.send_argument(&mut linkpath.as_bytes().to_vec()).await?;
+
+		self.send_argument(&mut vec![symbolic as u8]).await?;
+
+		self.read_reply().await
+	}
+
+	/* token is the one of a lock held on path , or 0 */
+	pub async fn truncate(self : &mut Client , path : &String , length : u64 , token : u64) -> Result<u32 , YaftpError> {
+
+		self.handshake().await?;
+
+		let narg = if token != 0 { 3 } else { 2 };
+
+		self.send_command(15u8, narg).await?;
+
+		self.send_argument(&mut path.as_bytes().to_vec()).await?;
+
+		self.send_argument(&mut length.to_be_bytes().to_vec()).await?;
+
+		if token != 0 {
+			self.send_argument(&mut token.to_be_bytes().to_vec()).await?;
 		}
+
+		self.read_reply().await
 	}
 
-	pub async fn put(self : &mut Client , localpath : &String ,remotepath : &String , start_pos : u64) -> Result<String,YaftpError> {
+	/*
+		lock path for lease seconds (0 for the server default) , a token of 0
+		asks a new lock , the token of a held lock renews it . return the token
+		to pass to put , truncate and unlock .
+	*/
+	pub async fn lock(self : &mut Client , path : &String , lease : u64 , token : u64) -> Result<u64 , YaftpError> {
 
-		match self.handshake().await{
-			Ok(_) => {},
-			Err(e) => {
-				println_err!("yaftp handshake error");
-				return Err(e);
-			},
-		};
+		self.handshake().await?;
 
-		match self.send_command(7u8, 3).await{
-			Ok(_) => {},
-			Err(e) => {
-				println_err!("yaftp send command error");
-				return Err(e);
-			},
-		};
+		let narg = if token != 0 { 3 } else { 2 };
 
-		match self.send_argument(&mut remotepath.as_bytes().to_vec()).await{
-			Ok(_) => {},
-			Err(e) => {
-				println_err!("yaftp send argument error");
-				return Err(e);
-			},
-		};
+		self.send_command(19u8, narg).await?;
 
-		match self.send_argument(&mut start_pos.to_be_bytes().to_vec()).await{
-			Ok(_) => {},
-			Err(e) => {
-				println_err!("yaftp send argument error");
-				return Err(e);
-			},
+		self.send_argument(&mut path.as_bytes().to_vec()).await?;
+
+		self.send_argument(&mut lease.to_be_bytes().to_vec()).await?;
+
+		if token != 0 {
+			self.send_argument(&mut token.to_be_bytes().to_vec()).await?;
+		}
+
+		self.read_reply().await?;
+
+		let arg = self.read_argument(8).await?;
+
+		match arg.try_into(){
+			Ok(p) => Ok(u64::from_be_bytes(p)),
+			Err(_) => Err(YaftpError::ArgumentError),
+		}
+	}
+
+	pub async fn unlock(self : &mut Client , path : &String , token : u64) -> Result<u32 , YaftpError> {
+
+		self.handshake().await?;
+
+		self.send_command(20u8, 2).await?;
+
+		self.send_argument(&mut path.as_bytes().to_vec()).await?;
+
+		self.send_argument(&mut token.to_be_bytes().to_vec()).await?;
+
+		self.read_reply().await
+	}
+
+	/* usage and limits of the quotas covering path , one row per quota */
+	pub async fn quota(self : &mut Client , path : &String) -> Result<Vec<QuotaUsage> , YaftpError> {
+
+		self.handshake().await?;
+
+		self.send_command(22u8, 1).await?;
+
+		self.send_argument(&mut path.as_bytes().to_vec()).await?;
+
+		let narg = self.read_reply().await?;
+
+		let mut ret : Vec<QuotaUsage> = vec![];
+		let mut i = 0 ;
+		while i < narg {
+			let arg = self.read_argument(2048).await?;
+
+			let row = match String::from_utf8(arg){
+				Ok(p) => p,
+				Err(_) => {
+					return Err(YaftpError::ArgumentError);
+				},
+			};
+			ret.push(QuotaUsage::parse(&row)?);
+			i +=1;
+		}
+
+		Ok(ret)
+	}
+
+	/* sessions running on the server , needs the admin permission */
+	pub async fn sessions(self : &mut Client) -> Result<Vec<SessionInfo> , YaftpError> {
+
+		self.handshake().await?;
+
+		self.send_command(23u8, 0).await?;
+
+		let narg = self.read_reply().await?;
+
+		let mut ret : Vec<SessionInfo> = vec![];
+		let mut i = 0 ;
+		while i < narg {
+			let arg = self.read_argument(65536).await?;
+
+			let row = match String::from_utf8(arg){
+				Ok(p) => p,
+				Err(_) => {
+					return Err(YaftpError::ArgumentError);
+				},
+			};
+			ret.push(SessionInfo::parse(&row)?);
+			i +=1;
+		}
+
+		Ok(ret)
+	}
+
+	pub async fn kill(self : &mut Client , id : u64) -> Result<u32 , YaftpError> {
+
+		self.handshake().await?;
+
+		self.send_command(24u8, 1).await?;
+
+		self.send_argument(&mut id.to_be_bytes().to_vec()).await?;
+
+		self.read_reply().await
+	}
+
+	/* the server stops accepting and exits once its sessions ended */
+	pub async fn shutdown(self : &mut Client) -> Result<u32 , YaftpError> {
+
+		self.handshake().await?;
+
+		self.send_command(25u8, 0).await?;
+
+		self.read_reply().await
+	}
+
+	/*
+		upload localpath from start_pos with PUT_FLAG_* flags , PUT_FLAG_APPEND
+		appends the whole file to the end of the remote file . token is the one
+		of a lock held on the remote path , or 0 .
+	*/
+	pub async fn put(self : &mut Client , localpath : &str , remotepath : &str , start_pos : u64 , flags : u8 , token : u64) -> Result<String,YaftpError> {
+
+		self.handshake().await?;
+
+		let narg = if token != 0 {
+			5
+		} else if flags != 0 {
+			4
+		} else {
+			3
 		};
 
-		let mut f = match fs::File::open(localpath.clone()).await{
+		self.send_command(7u8, narg).await?;
+
+		self.send_argument(&mut remotepath.as_bytes().to_vec()).await?;
+
+		self.send_argument(&mut start_pos.to_be_bytes().to_vec()).await?;
+
+		if narg >= 4 {
+			self.send_argument(&mut vec![flags]).await?;
+		}
+
+		if narg == 5 {
+			self.send_argument(&mut token.to_be_bytes().to_vec()).await?;
+		}
+
+		let mut f = match fs::File::open(localpath).await{
 			Ok(f) => f,
 			Err(_) => {
-				println_err!("open local file faild : {}" , localpath);
 				return Err(YaftpError::UnknownError);
 			},
 		};
@@ -627,11 +690,10 @@ impl Client {
 
 		let size = f.metadata().await.unwrap().len() - start_pos;
 
-		match self.conn.write_all(&size.to_be_bytes().to_vec()).await{
+		match self.conn.write_all(&size.to_be_bytes()).await{
 			Ok(_) => {},
-			Err(e) => {
-				println_err!("file transfer faild : {}" ,e);
-				return Err(YaftpError::UnknownNetwordError);
+			Err(_) => {
+				return Err(self.refused(YaftpError::UnknownNetwordError).await);
 			}
 		};
 
@@ -640,24 +702,28 @@ impl Client {
 
 		match f.seek(SeekFrom::Start(start_pos)).await{
 			Ok(_) => {},
-			Err(e) => {
-				println_err!("local file seek pos faild : {}" ,e);
+			Err(_) => {
 				return Err(YaftpError::StartPosError);
 			}
 		};
 
-		let pb = ProgressBar::new(size);
-		pb.set_style(ProgressStyle::default_bar()
-			.template("[{elapsed_precise}] [{wide_bar:.cyan/blue}] {bytes}/{total_bytes} ({eta})")
-			.progress_chars("#>-"));
-	
+		let meter = Meter::new(size);
+		self.progress.start(size);
+
+		/* the bar moves at the throttled rate so its eta counts the limit */
+		let throttle = Throttle::new(self.limit[UPLOAD]);
 
 		loop{
+			/* the server keeps what was sent , a later put resumes it */
+			if self.cancel.is_cancelled() {
+				self.progress.finish(false);
+				return Err(YaftpError::Cancelled);
+			}
+
 			let a = match f.read(&mut buf).await{
 				Ok(p) => p,
-				Err(e) => {
-					pb.finish_with_message("[-] file transfer faild");
-					println_err!("file transfer faild : {}" , e);
+				Err(_) => {
+					self.progress.finish(false);
 					return Err(YaftpError::UnknownError);
 				},
 			};
@@ -666,78 +732,43 @@ impl Client {
 				break;
 			}
 
+			throttle.take(a).await;
+
 			match self.conn.write_all(&buf[..a]).await{
 				Ok(p) => p,
-				Err(e) => {
-					pb.finish_with_message("[-] file transfer faild");
-					println_err!("file transfer faild : {}" , e);
-					return Err(YaftpError::UnknownError);
+				Err(_) => {
+					self.progress.finish(false);
+					return Err(self.refused(YaftpError::UnknownError).await);
 				},
 			};
-			pb.set_position(sum);
 			sum += a as u64;
+			self.progress.update(&meter.at(sum));
 
 			if sum >= size {
 				break
 			}
 		}
 
-		pb.finish_with_message("[+] finished");
+		self.progress.finish(true);
 
 		f.close().await.unwrap();
 
-		let _ = match self.read_reply().await{
-			Ok(p) => p,
-			Err(e) => {
-				println_err!("server error code : {}" , e);
-				return Err(e);
-			},
-		};
+		let _ = self.read_reply().await?;
 	
-		Ok(remotepath.clone())
+		Ok(remotepath.to_string())
 	}
 
-	pub async fn get(self : &mut Client ,localpath : &String ,remotepath : &String , start_pos : u64) -> Result<String,YaftpError> {
+	pub async fn get(self : &mut Client , localpath : &str , remotepath : &str , start_pos : u64) -> Result<String,YaftpError> {
 
-		match self.handshake().await{
-			Ok(_) => {},
-			Err(e) => {
-				println_err!("yaftp handshake error");
-				return Err(e);
-			},
-		};
+		self.handshake().await?;
 
-		match self.send_command(8u8, 2).await{
-			Ok(_) => {},
-			Err(e) => {
-				println_err!("yaftp send command error");
-				return Err(e);
-			},
-		};
+		self.send_command(8u8, 2).await?;
 
-		match self.send_argument(&mut remotepath.as_bytes().to_vec()).await{
-			Ok(_) => {},
-			Err(e) => {
-				println_err!("yaftp send argument error");
-				return Err(e);
-			},
-		};
+		self.send_argument(&mut remotepath.as_bytes().to_vec()).await?;
 
-		match self.send_argument(&mut start_pos.to_be_bytes().to_vec()).await{
-			Ok(_) => {},
-			Err(e) => {
-				println_err!("yaftp send argument error");
-				return Err(e);
-			},
-		};
+		self.send_argument(&mut start_pos.to_be_bytes().to_vec()).await?;
 
-		let _ = match self.read_reply().await{
-			Ok(p) => p,
-			Err(e) => {
-				println_err!("server error code : {}" , e);
-				return Err(e);
-			},
-		};
+		let _ = self.read_reply().await?;
 	
 		/*
 		+-----------------+---------------------+
@@ -751,7 +782,6 @@ impl Client {
 		match self.conn.read_exact(&mut argument_size).await{
 			Ok(_) => {},
 			Err(_) => {
-				println_err!("read file size faild!");
 				return Err(YaftpError::UnknownNetwordError);
 			},
 		};
@@ -761,18 +791,16 @@ impl Client {
 		let mut f : File;
 
 		if start_pos == 0{
-			f = match fs::File::create(localpath.clone()).await{
+			f = match fs::File::create(localpath).await{
 				Ok(f) => f,
 				Err(_) => {
-					println_err!("create local file faild : {}" , localpath);
 					return Err(YaftpError::UnknownError);
 				},
 			};
 		} else {
-			f = match async_std::fs::OpenOptions::new().write(true).read(true).open(localpath.clone()).await{
+			f = match async_std::fs::OpenOptions::new().write(true).read(true).open(localpath).await{
 				Ok(p) => p,
 				Err(_) => {
-					println_err!("open local file faild : {}" , localpath);
 					return Err(YaftpError::UnknownError);
 				}
 			};
@@ -780,118 +808,87 @@ impl Client {
 
 		if start_pos != 0 {
 
-			let pos : SeekFrom;
-
-			if start_pos == f.metadata().await.unwrap().len(){
-				pos = SeekFrom::End(0);
+			let pos = if start_pos == f.metadata().await.unwrap().len(){
+				SeekFrom::End(0)
 			} else {
-				pos = SeekFrom::Start(start_pos);
-			}
+				SeekFrom::Start(start_pos)
+			};
 
 			match f.seek(pos).await{
 				Ok(_) => {},
-				Err(e) => {
-					println_info!("seek local file faild : {}" , e);
+				Err(_) => {
 					return Err(YaftpError::StartPosError);
 				},
 			};
 		}
 
-		let pb = ProgressBar::new(size);
-		pb.set_style(ProgressStyle::default_bar()
-			.template("[{elapsed_precise}] [{wide_bar:.cyan/blue}] {bytes}/{total_bytes} ({eta})")
-			.progress_chars("#>-"));
+		let meter = Meter::new(size);
+		self.progress.start(size);
 
+		let throttle = Throttle::new(self.limit[DOWNLOAD]);
 		let mut buf = [0;2048];
 		let mut sum = 0u64;
 		loop{
+			/* the local copy keeps what was received , a later get resumes it */
+			if self.cancel.is_cancelled() {
+				self.progress.finish(false);
+				return Err(YaftpError::Cancelled);
+			}
+
 			let a = match self.conn.read(&mut buf).await{
+				Ok(0) => {
+					self.progress.finish(false);
+					return Err(YaftpError::UnknownNetwordError);
+				},
 				Ok(p) => p,
-				Err(e) => {
-					pb.finish_with_message("[-] finished");
-					println_err!("file transfer faild : {}" , e);
+				Err(_) => {
+					self.progress.finish(false);
 					return Err(YaftpError::UnknownError);
 				},
 			};
 
+			throttle.take(a).await;
+
 			match f.write_all(&buf[..a]).await{
 				Ok(p) => p,
-				Err(e) => {
-					pb.finish_with_message("[-] finished");
-					println_err!("file transfer faild : {}" , e);
+				Err(_) => {
+					self.progress.finish(false);
 					return Err(YaftpError::UnknownError);
 				},
 			};
 
-			pb.set_position(sum);
 			sum += a as u64;
+			self.progress.update(&meter.at(sum));
 
 			if sum >= size {
 				break
 			}
 		}
 
-		pb.finish_with_message("[+] finished");
+		self.progress.finish(true);
 
 		f.close().await.unwrap();
 
-		Ok(localpath.clone())
+		Ok(localpath.to_string())
 	}
 
 	pub async fn hash(self : &mut Client , path : &String , end_pos : u64) -> Result<String,YaftpError> {
 
-		match self.handshake().await{
-			Ok(_) => {},
-			Err(e) => {
-				println_err!("yaftp handshake error");
-				return Err(e);
-			},
-		};
+		self.handshake().await?;
 
-		match self.send_command(10u8, 2).await{
-			Ok(_) => {},
-			Err(e) => {
-				println_err!("yaftp send command error");
-				return Err(e);
-			},
-		};
+		self.send_command(10u8, 2).await?;
 
-		match self.send_argument(&mut path.as_bytes().to_vec()).await{
-			Ok(_) => {},
-			Err(e) => {
-				println_err!("yaftp send argument error");
-				return Err(e);
-			},
-		};
+		self.send_argument(&mut path.as_bytes().to_vec()).await?;
 
-		match self.send_argument(&mut end_pos.to_be_bytes().to_vec()).await{
-			Ok(_) => {},
-			Err(e) => {
-				println_err!("yaftp send argument error");
-				return Err(e);
-			},
-		};
+		self.send_argument(&mut end_pos.to_be_bytes().to_vec()).await?;
 
-		match self.read_reply().await{
-			Ok(p) => p,
-			Err(e) => {
-				println_err!("server error code : {}" , e);
-				return Err(e);
-			},
-		};
+		self.read_reply().await?;
 
-		let arg = match self.read_argument(32).await{
-			Ok(p) => p,
-			Err(e) => {
-				println_err!("yaftp read argument error");
-				return Err(e);
-			},
-		};
+		let arg = self.read_argument(32).await?;
 		
 		let ret = match String::from_utf8(arg) {
 			Ok(p) => p,
 			Err(_) => {
-				println_err!("format argument to utf8 string faild");
 				return Err(YaftpError::ArgumentError);
 			}
 		};
@@ -899,49 +896,320 @@ impl Client {
 		Ok(ret)
 	}
 
-	pub async fn cat(self : &mut Client , remotepath : &String) -> Result<String,YaftpError> {
+	pub async fn find(self : &mut Client , path : &String , filter : &FindFilter) -> Result<Vec<FindEntry>,YaftpError> {
 
-		match self.handshake().await{
-			Ok(_) => {},
-			Err(e) => {
-				println_err!("yaftp handshake error");
-				return Err(e);
-			},
-		};
+		self.handshake().await?;
 
-		match self.send_command(8u8, 2).await{
-			Ok(_) => {},
-			Err(e) => {
-				println_err!("yaftp send command error");
-				return Err(e);
-			},
-		};
+		self.send_command(11u8, 10).await?;
+
+		let mut args : Vec<Vec<u8>> = vec![
+			path.as_bytes().to_vec(),
+			filter.name.as_bytes().to_vec(),
+			vec![filter.regex as u8],
+			vec![filter.typ]
+		];
+
+		for num in [filter.min_size , filter.max_size , filter.newer , filter.older , filter.max_depth , filter.max_results] {
+			args.push(num.to_be_bytes().to_vec());
+		}
+
+		for mut arg in args {
+			self.send_argument(&mut arg).await?;
+		}
+
+		self.read_reply().await?;
+
+		self.read_stream_rows(4096).await?.iter().map(|p| FindEntry::parse(p)).collect()
+	}
+
+	/*
+		subscribe to changes under path , the client is moved into the
+		returned stream . dropping the stream closes the connection and the
+		server stops watching .
+	*/
+	pub async fn watch(mut self , path : &String , recursive : bool) -> Result<Pin<Box<dyn Stream<Item = Result<WatchEvent , YaftpError>> + Send>> , YaftpError> {
+
+		self.handshake().await?;
+
+		self.send_command(17u8, 2).await?;
+
+		self.send_argument(&mut path.as_bytes().to_vec()).await?;
+
+		self.send_argument(&mut vec![recursive as u8]).await?;
+
+		self.read_reply().await?;
+
+		Ok(self.into_stream(4096, |arg| match String::from_utf8(arg){
+			Ok(p) => WatchEvent::from_row(&p).ok_or(YaftpError::ArgumentError),
+			Err(_) => Err(YaftpError::UTF8FormatError),
+		}))
+	}
+
+	/*
+		read the last lines of a file , with follow the server keeps sending
+		new data as the file grows until the stream is dropped .
+	*/
+	pub async fn tail(mut self , path : &String , lines : u64 , follow : bool) -> Result<Pin<Box<dyn Stream<Item = Result<Vec<u8> , YaftpError>> + Send>> , YaftpError> {
 
-		match self.send_argument(&mut remotepath.as_bytes().to_vec()).await{
+		self.handshake().await?;
+
+		self.send_command(18u8, 3).await?;
+
+		self.send_argument(&mut path.as_bytes().to_vec()).await?;
+
+		self.send_argument(&mut lines.to_be_bytes().to_vec()).await?;
+
+		self.send_argument(&mut vec![follow as u8]).await?;
+
+		self.read_reply().await?;
+
+		Ok(self.into_stream(CHUNK_SIZE as u64, Ok))
+	}
+
+	/* turn a stream reply into items , the empty argument ends it */
+	fn into_stream<T : 'static>(self , max_size : u64 , parse : fn(Vec<u8>) -> Result<T , YaftpError>) -> Pin<Box<dyn Stream<Item = Result<T , YaftpError>> + Send>> {
+		Box::pin(stream::unfold(Some(self), move |client| async move {
+			let mut client = client?;
+
+			let arg = match client.read_argument(max_size).await{
+				Ok(p) => p,
+				Err(e) => return Some((Err(e) , None)),
+			};
+
+			if arg.is_empty() {
+				return None;
+			}
+
+			Some((parse(arg) , Some(client)))
+		}))
+	}
+
+	pub async fn grep(self : &mut Client , path : &String , pattern : &String , regex : bool , ignore_case : bool , context : u64 , max_matches : u64) -> Result<Vec<GrepLine>,YaftpError> {
+
+		self.handshake().await?;
+
+		self.send_command(12u8, 6).await?;
+
+		let args : Vec<Vec<u8>> = vec![
+			path.as_bytes().to_vec(),
+			pattern.as_bytes().to_vec(),
+			vec![regex as u8],
+			vec![ignore_case as u8],
+			context.to_be_bytes().to_vec(),
+			max_matches.to_be_bytes().to_vec()
+		];
+
+		for mut arg in args {
+			self.send_argument(&mut arg).await?;
+		}
+
+		self.read_reply().await?;
+
+		self.read_stream_rows(8192).await?.iter().map(|p| GrepLine::parse(p)).collect()
+	}
+
+	pub async fn get_tar(self : &mut Client , localpath : &String , remotepath : &String , compress : u8 , extract : bool) -> Result<u64,YaftpError> {
+
+		self.handshake().await?;
+
+		self.send_command(13u8, 2).await?;
+
+		self.send_argument(&mut remotepath.as_bytes().to_vec()).await?;
+
+		self.send_argument(&mut vec![compress]).await?;
+
+		self.read_reply().await?;
+
+		let mut f : Option<File> = None;
+		let mut sender = None;
+		let mut extractor = None;
+
+		if extract {
+			let (tx , rx) = async_std::channel::bounded::<Vec<u8>>(16);
+			let target = std::path::PathBuf::from(localpath);
+			extractor = Some(async_std::task::spawn_blocking(move || {
+				extract_archive(ChannelReader::new(rx), compress, &target, &Charge::new(vec![]), true)
+			}));
+			sender = Some(tx);
+		} else {
+			f = match fs::File::create(localpath.clone()).await{
+				Ok(f) => Some(f),
+				Err(_) => {
+					return Err(YaftpError::UnknownError);
+				},
+			};
+		}
+
+		let meter = Meter::new(0);
+		self.progress.start(0);
+
+		let throttle = Throttle::new(self.limit[DOWNLOAD]);
+		let mut sum = 0u64;
+		loop {
+			/* dropping sender stops the extractor with what it got */
+			if self.cancel.is_cancelled() {
+				self.progress.finish(false);
+				return Err(YaftpError::Cancelled);
+			}
+
+			let arg = match self.read_argument(CHUNK_SIZE as u64).await{
+				Ok(p) => p,
+				Err(e) => {
+					self.progress.finish(false);
+					return Err(e);
+				},
+			};
+
+			if arg.is_empty() {
+				break;
+			}
+
+			throttle.take(arg.len()).await;
+
+			sum += arg.len() as u64;
+			self.progress.update(&meter.at(sum));
+
+			if let Some(f) = f.as_mut() {
+				match f.write_all(&arg).await{
+					Ok(_) => {},
+					Err(_) => {
+						self.progress.finish(false);
+						return Err(YaftpError::UnknownError);
+					},
+				};
+			}
+
+			/* extractor stopped early , keep draining the stream and report its result below */
+			let stopped = match sender.as_ref() {
+				Some(tx) => tx.send(arg).await.is_err(),
+				None => false,
+			};
+
+			if stopped {
+				sender = None;
+			}
+		}
+
+		self.progress.finish(true);
+
+		drop(sender);
+
+		let mut count = sum;
+
+		if let Some(mut f) = f {
+			f.close().await.unwrap();
+		}
+
+		if let Some(extractor) = extractor {
+			count = match extractor.await{
+				Ok(p) => p.iter().filter(|p| p.status == ENTRY_OK).count() as u64,
+				Err(_) => {
+					return Err(YaftpError::WriteFileError);
+				},
+			};
+		}
+
+		self.read_reply().await?;
+
+		Ok(count)
+	}
+
+	pub async fn put_tar(self : &mut Client , localpath : &String , remotepath : &String , compress : u8) -> Result<Vec<UntarEntry>,YaftpError> {
+
+		self.handshake().await?;
+
+		self.send_command(14u8, 3).await?;
+
+		self.send_argument(&mut remotepath.as_bytes().to_vec()).await?;
+
+		self.send_argument(&mut vec![compress]).await?;
+
+		let (sender , receiver) = async_std::channel::bounded::<Vec<u8>>(16);
+		let source = std::path::PathBuf::from(localpath);
+		let builder = async_std::task::spawn_blocking(move || {
+			build_archive(&source, compress, ChannelWriter::new(sender))
+		});
+
+		let meter = Meter::new(0);
+		self.progress.start(0);
+
+		let throttle = Throttle::new(self.limit[UPLOAD]);
+		let mut sum = 0u64;
+		let mut sent = true;
+		while let Ok(mut chunk) = receiver.recv().await {
+			if self.cancel.is_cancelled() {
+				break;
+			}
+			throttle.take(chunk.len()).await;
+			sum += chunk.len() as u64;
+			if self.send_argument(&mut chunk).await.is_err() {
+				sent = false;
+				break;
+			}
+			self.progress.update(&meter.at(sum));
+		}
+
+		drop(receiver);
+
+		/* the builder fails once receiver is dropped , the server keeps the entries it got */
+		if self.cancel.is_cancelled() {
+			let _ = builder.await;
+			self.progress.finish(false);
+			return Err(YaftpError::Cancelled);
+		}
+
+		match builder.await{
 			Ok(_) => {},
-			Err(e) => {
-				println_err!("yaftp send argument error");
-				return Err(e);
+			Err(_) => {
+				self.progress.finish(false);
+				return Err(YaftpError::ReadFileError);
 			},
 		};
 
+		if sent && self.send_argument(&mut vec![]).await.is_err() {
+			sent = false;
+		}
+
+		self.progress.finish(sent);
+
+		/* server may reply an error and close before read all data */
+		let narg = self.read_reply().await?;
+
+		if !sent {
+			return Err(YaftpError::UnknownNetwordError);
+		}
+
+		let mut ret : Vec<UntarEntry> = vec![];
+		let mut i = 0 ;
+		while i < narg {
+			let arg = self.read_argument(4096).await?;
+
+			let row = match String::from_utf8(arg){
+				Ok(p) => p,
+				Err(_) => {
+					return Err(YaftpError::ArgumentError);
+				},
+			};
+			ret.push(UntarEntry::parse(&row)?);
+			i +=1;
+		}
+
+		Ok(ret)
+	}
+
+	pub async fn cat(self : &mut Client , remotepath : &String) -> Result<String,YaftpError> {
+
+		self.handshake().await?;
+
+		self.send_command(8u8, 2).await?;
+
+		self.send_argument(&mut remotepath.as_bytes().to_vec()).await?;
+
 		let start_pos : u64 = 0 ;
 
-		match self.send_argument(&mut start_pos.to_be_bytes().to_vec()).await{
-			Ok(_) => {},
-			Err(e) => {
-				println_err!("yaftp send argument error");
-				return Err(e);
-			},
-		};
+		self.send_argument(&mut start_pos.to_be_bytes().to_vec()).await?;
 
-		let _ = match self.read_reply().await{
-			Ok(p) => p,
-			Err(e) => {
-				println_err!("server error code : {}" , e);
-				return Err(e);
-			},
-		};
+		let _ = self.read_reply().await?;
 	
 		/*
 		+-----------------+---------------------+
@@ -955,7 +1223,6 @@ impl Client {
 		match self.conn.read_exact(&mut argument_size).await{
 			Ok(_) => {},
 			Err(_) => {
-				println_err!("read file size faild!");
 				return Err(YaftpError::UnknownNetwordError);
 			},
 		};
@@ -967,7 +1234,6 @@ impl Client {
 		match self.conn.read_exact(&mut buf).await{
 			Ok(_) => {},
 			Err(_) => {
-				println_err!("read file faild!");
 				return Err(YaftpError::UnknownNetwordError);
 			},
 		};
@@ -975,7 +1241,6 @@ impl Client {
 		let ret = match String::from_utf8(buf.to_vec()){
 			Ok(p) => p,
 			Err(_) => {
-				println_err!("format argument to utf8 string faild!");
 				return Err(YaftpError::UTF8FormatError);
 			},
 		};
@@ -1,22 +1,106 @@
 use std::fmt::Debug;
 use std::fmt;
 use std::error;
-use std::io::Write;
-
-use crate::client::SpawnClient;
-use crate::common::error_retcode;
-use crate::println_err;
-use crate::println_info;
-use crate::utils::calc_md5;
-
-use async_std::fs;
+use std::io::{BufRead, Write};
+use std::pin::Pin;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+
+use yaftp::client::SpawnClient;
+use yaftp::progress::{Cancel, Progress, Transfer};
+use yaftp::archive::{COMPRESS_GZIP, COMPRESS_NONE, COMPRESS_ZSTD};
+use yaftp::common::{FileKind, FindFilter, FIND_TYPE_FILE, FIND_TYPE_FOLDER, FIND_TYPE_SYMLINK, PUT_FLAG_APPEND, PUT_FLAG_WAIT, YaftpError, error_retcode};
+use yaftp::utils::{calc_md5, parse_size};
+use crate::signal;
+
+use async_std::{fs, task};
+use chrono::{DateTime, Utc};
 use console::Term;
 use console::style;
-use futures::AsyncWriteExt;
+use indicatif::{HumanBytes, ProgressBar, ProgressStyle};
+use futures::{AsyncWriteExt, Stream, StreamExt, future::{self, Either}};
 use tabled::{Tabled, Table};
 
+#[macro_export]
+macro_rules! println_err {
+    () => ($crate::print!("\n"));
+    ($($arg:tt)*) => ({
+		print!("[-] ");
+        println!($($arg)*);
+    })
+}
+
+#[macro_export]
+macro_rules! println_info {
+    () => ($crate::print!("\n"));
+    ($($arg:tt)*) => ({
+		print!("[+] ");
+        println!($($arg)*);
+    })
+}
+
+/* a time as the shell shows it , in utc */
+pub fn format_time(time : SystemTime) -> String {
+	let time : DateTime<Utc> = time.into();
+	time.format("%Y-%m-%d %H:%M:%S").to_string()
+}
+
+/* draws the transfers of the client as a progress bar , a spinner for archives */
+#[derive(Default)]
+pub struct Bar {
+	bar : Mutex<Option<ProgressBar>>
+}
+
+impl Bar {
+
+	/* a transfer is running */
+	pub fn busy(&self) -> bool {
+		self.bar.lock().unwrap().is_some()
+	}
+}
+
+impl Progress for Bar {
+	fn start(&self , total : u64) {
+		let pb = if total == 0 {
+			let pb = ProgressBar::new_spinner();
+			pb.set_style(ProgressStyle::default_spinner()
+				.template("{spinner} [{elapsed_precise}] {bytes} {msg}"));
+			pb
+		} else {
+			let pb = ProgressBar::new(total);
+			pb.set_style(ProgressStyle::default_bar()
+				.template("[{elapsed_precise}] [{wide_bar:.cyan/blue}] {bytes}/{total_bytes} {msg} ({eta})")
+				.progress_chars("#>-"));
+			pb
+		};
+		*self.bar.lock().unwrap() = Some(pb);
+	}
+
+	fn update(&self , transfer : &Transfer) {
+		if let Some(pb) = self.bar.lock().unwrap().as_ref() {
+			pb.set_position(transfer.done);
+			pb.set_message(format!("{}/s" , HumanBytes(transfer.rate)));
+		}
+	}
+
+	fn finish(&self , ok : bool) {
+		if let Some(pb) = self.bar.lock().unwrap().take() {
+			/* a spinner has no length , finishing it would show u64::MAX bytes */
+			if ok && pb.length() == u64::MAX {
+				pb.set_message("[+] finished");
+				pb.finish_at_current_pos();
+			} else if ok {
+				pb.finish_with_message("[+] finished");
+			} else {
+				pb.abandon_with_message("[-] file transfer faild");
+			}
+		}
+	}
+}
+
 #[derive(Tabled)]
-struct FileInfo {
+struct FileRow {
 	name: String,
 	typ: String,
 	size: String,
@@ -24,6 +108,36 @@ struct FileInfo {
 	accesstime : String
 }
 
+#[derive(Tabled)]
+struct QuotaRow {
+	kind: String,
+	path: String,
+	bytes: String,
+	max_bytes: String,
+	files: String,
+	max_files : String
+}
+
+#[derive(Tabled)]
+struct SessionRow {
+	id: String,
+	peer: String,
+	user: String,
+	command: String,
+	paths: String,
+	progress: String,
+	seconds : String
+}
+
+#[derive(Tabled)]
+struct FindRow {
+	index: usize,
+	typ: String,
+	size: String,
+	modified : String,
+	path : String
+}
+
 pub enum CmdError {
 	NotFindQuotaUntilEnd
 }
@@ -176,17 +290,10 @@ pub fn cmd_format(input : String) -> Result<Vec<String> , CmdError>{
 		}
 	};
 
-	let ret = match parser(tokens) {
-		Ok(p) => p,
-		Err(e) => {
-			return Err(e)
-		},
-	};
-
-	Ok(ret)
+	parser(tokens)
 }
 
-fn pre_handle_path (path : &String , cwd : &String) -> String{
+pub fn pre_handle_path (path : &String , cwd : &String) -> String{
 
 	let mut cdpath : String;
 
@@ -205,7 +312,7 @@ fn pre_handle_path (path : &String , cwd : &String) -> String{
 			cdpath = cwd.split_at(pos).0.to_string();
 
 			if cdpath.len() == 2 {
-				cdpath += &"\\".to_string();
+				cdpath += "\\";
 			}
 
 		} else {
@@ -218,70 +325,368 @@ fn pre_handle_path (path : &String , cwd : &String) -> String{
 			}
 		}
 		
+	} else if is_windows {
+		if path.len() > 1 && path.as_bytes()[1] == b':' {
+			cdpath = path.clone();
+		} else if cwd.len() == 3 {
+			cdpath = cwd.clone() + path;
+		} else {
+			cdpath = [cwd.clone() , path.clone()].join("\\");
+		}
+	} else if path.as_bytes()[0] == b'/' {
+		cdpath = path.clone();
+	} else if cwd == "/" {
+		cdpath = cwd.clone() + path;
 	} else {
+		cdpath = [cwd.clone() , path.clone()].join("/");
+	}
 
-		loop {
+	cdpath
+}
+
+fn parse_find_args(cmd : &[String] , cwd : &String) -> Result<(String , FindFilter) , String> {
+
+	let mut path = cwd.clone();
+	let mut filter = FindFilter{ max_results : 1000 , ..Default::default() };
+	let now = chrono::Utc::now().timestamp() as u64;
+
+	let mut i = 1;
+	while i < cmd.len() {
+
+		if !cmd[i].starts_with('-') {
+			path = pre_handle_path(&cmd[i], cwd);
+			i += 1;
+			continue;
+		}
+
+		if i + 1 >= cmd.len() {
+			return Err(format!("option '{}' need a value", cmd[i]));
+		}
 
-			if is_windows {
-				if path.len() > 1 && path.as_bytes()[1] == b':' {
-    						cdpath = path.clone();
-    						break;
-    					}
+		let value = &cmd[i + 1];
 
-				if cwd.len() == 3 {
-					cdpath = cwd.clone() + &path.clone();
+		match cmd[i].as_str() {
+			"-name" => {
+				filter.name = value.clone();
+				filter.regex = false;
+			},
+			"-regex" => {
+				filter.name = value.clone();
+				filter.regex = true;
+			},
+			"-type" => {
+				filter.typ = match value.as_str() {
+					"f" => FIND_TYPE_FILE,
+					"d" => FIND_TYPE_FOLDER,
+					"l" => FIND_TYPE_SYMLINK,
+					_ => return Err(format!("unknown type '{}' , use f , d or l", value)),
+				};
+			},
+			"-minsize" | "-maxsize" => {
+				let num = match parse_size(value) {
+					Some(p) => p,
+					None => return Err(format!("option '{}' value '{}' not a size", cmd[i], value)),
+				};
+				if cmd[i] == "-minsize" {
+					filter.min_size = num;
 				} else {
-					cdpath = [cwd.clone() , path.clone()].join("\\");
+					filter.max_size = num;
 				}
-			} else {
-				if path.as_bytes()[0] == b'/'{
-					cdpath = path.clone();
-					break;
+			},
+			"-newer" | "-older" | "-maxdepth" | "-limit" => {
+				let num = match value.parse::<u64>() {
+					Ok(p) => p,
+					Err(_) => return Err(format!("option '{}' value '{}' not a number", cmd[i], value)),
+				};
+				match cmd[i].as_str() {
+					"-newer" | "-older" => {
+						/* minutes ago */
+						let secs = match num.checked_mul(60) {
+							Some(p) => p,
+							None => return Err(format!("option '{}' value '{}' too big", cmd[i], value)),
+						};
+						if cmd[i] == "-newer" {
+							filter.newer = now.saturating_sub(secs);
+						} else {
+							filter.older = now.saturating_sub(secs);
+						}
+					},
+					"-maxdepth" => filter.max_depth = num,
+					_ => filter.max_results = num,
+				};
+			},
+			_ => return Err(format!("unknown option '{}'", cmd[i])),
+		}
+		i += 2;
+	}
+
+	Ok((path , filter))
+}
+
+fn parse_tail_args(cmd : &[String] , cwd : &String) -> Result<(String , u64 , bool) , String> {
+
+	let mut path = String::new();
+	let mut lines = 10u64;
+	let mut follow = false;
+
+	let mut i = 1;
+	while i < cmd.len() {
+		match cmd[i].as_str() {
+			"-f" => follow = true,
+			"-n" => {
+				i += 1;
+				lines = match cmd.get(i).and_then(|p| p.parse::<u64>().ok()) {
+					Some(p) => p,
+					None => return Err(String::from("option '-n' need a number")),
+				};
+			},
+			_ => {
+				if !path.is_empty() {
+					return Err(String::from("command 'tail' need 1 path"));
 				}
-				if cwd == "/" {
-					cdpath = cwd.clone() + &path.clone();
+				path = pre_handle_path(&cmd[i], cwd);
+			},
+		}
+		i += 1;
+	}
+
+	if path.is_empty() {
+		return Err(String::from("command 'tail' need 1 path"));
+	}
+
+	Ok((path , lines , follow))
+}
+
+/*
+	print items of a long lived stream until the user press enter , dropping
+	the stream closes the connection so the server stops sending . false when
+	the stream ended by an error instead .
+*/
+async fn follow_stream<T>(mut items : Pin<Box<dyn Stream<Item = Result<T , YaftpError>> + Send>> , print : impl Fn(T)) -> bool {
+
+	/* the line read here is the stop signal , never a command */
+	let mut stop = task::spawn_blocking(|| {
+		let mut line = String::new();
+		let _ = std::io::stdin().read_line(&mut line);
+	});
+
+	loop {
+		match future::select(items.next(), &mut stop).await {
+			Either::Left((Some(Ok(item)) , _)) => {
+				print(item);
+			},
+			Either::Left((Some(Err(e)) , _)) => {
+				println_err!("error : {} , press enter to continue" , e);
+				stop.await;
+				return false;
+			},
+			Either::Left((None , _)) => {
+				println_err!("closed by server , press enter to continue");
+				stop.await;
+				return false;
+			},
+			Either::Right(_) => {
+				return true;
+			},
+		}
+	}
+}
+
+struct GrepArgs {
+	path : String,
+	pattern : String,
+	regex : bool,
+	ignore_case : bool,
+	context : u64,
+	max_matches : u64
+}
+
+fn parse_grep_args(cmd : &[String] , cwd : &String) -> Result<GrepArgs , String> {
+
+	let mut args = GrepArgs{ path : cwd.clone() , pattern : String::new() , regex : false , ignore_case : false , context : 0 , max_matches : 1000 };
+	let mut positional : Vec<String> = vec![];
+
+	let mut i = 1;
+	while i < cmd.len() {
+		match cmd[i].as_str() {
+			"-i" => args.ignore_case = true,
+			"-E" => args.regex = true,
+			"-C" | "-m" => {
+				let num = match cmd.get(i + 1).and_then(|p| p.parse::<u64>().ok()){
+					Some(p) => p,
+					None => return Err(format!("option '{}' need a number", cmd[i])),
+				};
+				if cmd[i] == "-C" {
+					args.context = num;
 				} else {
-					cdpath = [cwd.clone() , path.clone()].join("/");
+					args.max_matches = num;
 				}
+				i += 1;
+			},
+			_ => positional.push(cmd[i].clone()),
+		}
+		i += 1;
+	}
+
+	match positional.len() {
+		1 => {},
+		2 => args.path = pre_handle_path(&positional[1], cwd),
+		_ => return Err(String::from("command 'grep' need a pattern and an optional path")),
+	}
+
+	args.pattern = positional[0].clone();
+
+	Ok(args)
+}
+
+/* commands of the shell , others are reported as unknown */
+const COMMANDS : [&str ; 25] = [
+	"help" , "ls" , "cd" , "cp" , "mv" , "rm" , "ln" , "stat" , "watch" , "tail" , "hash" , "mkdir" , "get" ,
+	"put" , "truncate" , "lock" , "unlock" , "quota" , "sessions" , "kill" , "shutdown" , "cat" , "find" , "grep" , "set"
+];
+
+/*
+	shell commands read from a script or a pipe instead of the terminal .
+	each command is echoed and tells whether it failed . after
+	set -e the first failed command stops the script .
+*/
+pub struct Batch {
+	input : Box<dyn BufRead>,
+	line : usize,
+	stop_on_error : bool,
+	commands : usize,
+	failed : usize,
+	stopped : bool
+}
+
+impl Batch {
+
+	pub fn new(input : Box<dyn BufRead>) -> Batch {
+		Batch { input , line : 0 , stop_on_error : false , commands : 0 , failed : 0 , stopped : false }
+	}
+
+	/* next command , blank lines and # comments are skipped , none at the end */
+	fn next_command(&mut self) -> Option<String> {
+		loop {
+			let mut line = String::new();
+			match self.input.read_line(&mut line) {
+				Ok(0) | Err(_) => return None,
+				Ok(_) => self.line += 1,
+			};
+
+			let line = line.trim();
+			if !line.is_empty() && !line.starts_with('#') {
+				return Some(line.to_string());
 			}
-			break;
 		}
 	}
 
-	cdpath
+	/* count a command , false when the script stops there */
+	fn done(&mut self , failed : bool) -> bool {
+		self.commands += 1;
+		if !failed {
+			return true;
+		}
+		self.failed += 1;
+		if self.stop_on_error {
+			self.stopped = true;
+			return false;
+		}
+		true
+	}
+
+	pub fn ok(&self) -> bool {
+		self.failed == 0
+	}
+
+	pub fn summary(&self) -> String {
+		if self.stopped {
+			format!("stopped at line {} , {} commands run , {} failed" , self.line , self.commands , self.failed)
+		} else {
+			format!("{} commands run , {} failed" , self.commands , self.failed)
+		}
+	}
 }
 
-pub async fn handle_cmd(spawn : SpawnClient){
+/* run the shell , from the terminal or a batch . false when a command of the batch failed */
+pub async fn handle_cmd(mut spawn : SpawnClient , mut batch : Option<Batch>) -> bool {
+
+	let bar = Arc::new(Bar::default());
+	let cancel = Cancel::new();
+	spawn.set_progress(bar.clone());
+	spawn.set_cancel(cancel.clone());
+
+	/* in the terminal ctrl-c stops the transfer instead of the shell */
+	if batch.is_none() {
+		let bar = bar.clone();
+		task::spawn(signal::interrupt(move || bar.busy(), cancel.clone()));
+	}
 
 	let mut term = Term::stdout();
 	let mut client = match spawn.spawn().await{
 		Ok(p) => p,
 		Err(_) => {
             println_err!("connect faild!");
-            return;
+            return false;
         } ,
 	};
 	let mut cwd = match client.cwd().await{
 		Ok(p) => p,
 		Err(e) => {
 			println_err!("error code : {}" , error_retcode(e));
-			return;
+			return false;
 		},
 	};
+	let mut found : Vec<String> = vec![];
+	/* tokens of the locks taken by this shell , by remote path */
+	let mut locks : HashMap<String , u64> = HashMap::new();
+	/* a command of the batch ran , failed is set by its error paths */
+	let mut ran = false;
+	let mut failed = false;
 	loop {
-		term.set_title("yaftp");
-		let wt = format!("yaftp @ {} > ", style(cwd.clone()).red());
-		term.write_all(wt.as_bytes()).unwrap();
-		let mut cmd = String::new();
-		match std::io::stdin().read_line(&mut cmd){
-			Ok(_) => {},
-			Err(_) => return ,
+		if let (Some(batch) , true) = (batch.as_mut() , ran) {
+			if !batch.done(failed) {
+				break;
+			}
+		}
+		ran = false;
+		failed = false;
+
+		let cmd = match batch.as_mut() {
+			Some(batch) => {
+				let cmd = match batch.next_command() {
+					Some(p) => p,
+					None => break,
+				};
+				println!("yaftp @ {} > {}" , cwd , cmd);
+				ran = true;
+				cmd
+			},
+			None => {
+				term.set_title("yaftp");
+				let wt = format!("yaftp @ {} > ", style(cwd.clone()).red());
+				term.write_all(wt.as_bytes()).unwrap();
+				let mut cmd = String::new();
+				match std::io::stdin().read_line(&mut cmd){
+					/* end of input , like ctrl-d */
+					Ok(0) => {
+						println!();
+						break;
+					},
+					Ok(_) => {},
+					Err(_) => break,
+				};
+				cmd.trim().to_string()
+			},
 		};
-		let cmd = cmd.trim().to_string();
+
+		/* a ctrl-c of the last command does not stop the next one */
+		cancel.reset();
 
 		let cmd = match cmd_format(cmd){
 			Ok(p) => p,
 			Err(_) => {
+				failed = true;
 				println_err!("command parser faild");
 				continue;
 			},
@@ -291,19 +696,68 @@ pub async fn handle_cmd(spawn : SpawnClient){
 			continue;
 		}
 
+		if !COMMANDS.contains(&cmd[0].as_str()) {
+			failed = true;
+			println_err!("unknown command '{}' , type help for the command list" , cmd[0]);
+			continue;
+		}
+
+		if cmd[0] == "set" {
+			let batch = match batch.as_mut() {
+				Some(p) => p,
+				None => {
+					failed = true;
+					println_err!("command 'set' only works in a script");
+					continue;
+				},
+			};
+			match cmd[1..] {
+				[ref p] if p == "-e" => batch.stop_on_error = true,
+				[ref p] if p == "+e" => batch.stop_on_error = false,
+				_ => {
+					failed = true;
+					println_err!("command 'set' need -e or +e . eg : set -e");
+				},
+			};
+			continue;
+		}
+
 		if cmd[0] == "help" {
 			println!("-------------------------- yaftp client command list ------------------------------");
 			println!("help                          - command usage information");
 			println!("ls                            - list of current work directory");
 			println!("cd    [path]                  - change current work directory");
-			println!("cp    [srcpath] [targetpath]  - copy file to target path");
+			println!("cp    [-P] [srcpath] [targetpath] - copy file to target path , -P copy a symlink itself");
 			println!("mv    [srcpath] [targetpath]  - move file to target path");
-			println!("rm    [path]                  - remove a file or folder");
+			println!("rm    [path]                  - remove a file or folder , a symlink is removed itself");
+			println!("ln    [-s] [target] [link]    - make a hard link , or a symbolic link with -s");
+			println!("stat  [path]                  - show information of a path without follow symlink");
+			println!("watch [-r] [path]             - print changes of a folder live , -r include sub folders");
+			println!("tail  [-n N] [-f] [path]      - print last N lines (default 10) , -f keep printing new data");
 			println!("hash  [path]                  - calcatute a file hash");
 			println!("mkdir [path]                  - make directory");
 			println!("get   [remote path]           - download a file to local");
 			println!("put   [local path]            - upload a file to remote work directory");
+			println!("put   -a [local path] [remote path] - append a file to the end of remote file");
+			println!("put   -w ...                  - wait for the write lock of the remote file");
+			println!("truncate [path] [size]        - set remote file length , size accept k/m/g");
+			println!("lock  [path] [lease]          - lock a remote file for lease seconds (default 60) ,");
+			println!("                                put and truncate of this shell use the lock");
+			println!("unlock [path]                 - release a lock taken by lock");
+			println!("quota [path]                  - show storage used and limits of a remote path");
+			println!("sessions                      - list sessions running on the server (admin)");
+			println!("kill  [id]                    - stop a session of the server (admin)");
+			println!("shutdown                      - stop the server once its sessions ended (admin)");
 			println!("cat   [remote path]           - read a file and print content");
+			println!("find  [path] [options]        - search files , options : -name -regex -type -minsize");
+			println!("                                -maxsize -newer -older (minutes) -maxdepth -limit");
+			println!("get   #[index]                - download a file of last find result");
+			println!("get   -r [-z|--zstd] [-x] [remote path] [local path] - download a folder as tar archive");
+			println!("                                -x extract it to local path on the fly");
+			println!("put   -r --tar [-z|--zstd] [local path] [remote path] - upload a folder as tar archive");
+			println!("                                and extract it in remote path");
+			println!("grep  [options] [pattern] [path] - search lines in remote files , options : -i -E");
+			println!("                                -C (context lines) -m (max matches)");
 			println!("-----------------------------------------------------------------------------------");
 			continue;
 		}
@@ -311,6 +765,7 @@ pub async fn handle_cmd(spawn : SpawnClient){
 		if cmd[0] == "ls" {
 
 			if cmd.len() != 1{
+				failed = true;
 				println_err!("command 'ls' not any argument . eg : ls");
 				continue;
 			}
@@ -318,23 +773,24 @@ pub async fn handle_cmd(spawn : SpawnClient){
 			let mut client = match spawn.spawn().await{
 				Ok(p) => p,
 				Err(e) => {
+					failed = true;
 					println_err!("connect error : {}", e);
 					continue;
 				},
 			};
 			let result = match client.ls(&cwd).await{
 				Ok(p) => p,
-				Err(_) => {
-					println_err!("command execute faild");
+				Err(e) => {
+					failed = true;
+					println_err!("command execute faild : {}" , e);
 					continue;
 				},
 			};
 
-			let mut files : Vec<FileInfo> = vec![];
+			let mut files : Vec<FileRow> = vec![];
 
 			for i in result {
-				let col : Vec<&str> = i.split('|').collect();
-				files.push(FileInfo{name : col[0].to_string() , typ : col[1].to_string() , size : col[2].to_string() , modified : col[3].to_string() , accesstime : col[4].to_string()});
+				files.push(FileRow{name : i.name , typ : i.kind.to_string() , size : i.size.to_string() , modified : format_time(i.modified) , accesstime : format_time(i.accessed)});
 			}
 
 			let table = Table::new(files).to_string();
@@ -343,6 +799,7 @@ pub async fn handle_cmd(spawn : SpawnClient){
 		
 		if cmd[0] == "cd" {
 			if cmd.len() != 2{
+				failed = true;
 				println_err!("command 'cd' need 1 argument . eg : cd /var");
 				continue;
 			}
@@ -360,40 +817,48 @@ pub async fn handle_cmd(spawn : SpawnClient){
 			let mut client = match spawn.spawn().await{
 				Ok(p) => p,
 				Err(e) => {
+					failed = true;
 					println_err!("connect error : {}", e);
 					continue;
 				},
 			};
 
-			let (ret, path) = match client.info(&cdpath).await{
+			let info = match client.info(&cdpath).await{
 				Ok(p) => p,
 				Err(e) => {
+					failed = true;
                     println_err!("get cd target path information faild : {}" , e);
 					continue;
 				},
 			};
 
-			if ret[0] == 0 {
-				cwd = path;
+			if info.is_folder() {
+				cwd = info.path;
 			} else {
-				println_err!("'{}' not path" , path);
+				failed = true;
+				println_err!("'{}' not path" , info.path);
 			}
 
 		}
 
 		if cmd[0] == "cp" {
-			if cmd.len() != 3{
-				println!("command 'cp' need 2 argument . eg : cp /var/file1 /var/file2");
+			/* -P copy a symlink as a link instead of its target */
+			let nofollow = cmd.len() > 1 && cmd[1] == "-P";
+			let args : Vec<String> = cmd.iter().skip(if nofollow { 2 } else { 1 }).cloned().collect();
+
+			if args.len() != 2{
+				failed = true;
+				println_err!("command 'cp' need 2 argument . eg : cp [-P] /var/file1 /var/file2");
 				continue;
 			}
 
-			let srcpath = pre_handle_path(&cmd[1], &cwd);
+			let srcpath = pre_handle_path(&args[0], &cwd);
 
 			if srcpath.is_empty(){
 				continue;
 			}
 
-			let targetpath = pre_handle_path(&cmd[2], &cwd);
+			let targetpath = pre_handle_path(&args[1], &cwd);
 
 			if targetpath.is_empty(){
 				continue;
@@ -402,20 +867,29 @@ pub async fn handle_cmd(spawn : SpawnClient){
 			let mut client = match spawn.spawn().await{
 				Ok(p) => p,
 				Err(e) => {
+					failed = true;
 					println_err!("connect error : {}", e);
 					continue;
 				},
 			};
 
-			let (ret, _) = match client.info(&srcpath).await{
+			let info = if nofollow {
+				client.lstat(&srcpath).await
+			} else {
+				client.info(&srcpath).await
+			};
+
+			let info = match info{
 				Ok(p) => p,
 				Err(e) => {
+					failed = true;
                     println_err!("read source path infomation faild : {}" , e);
 					continue;
 				},
 			};
 			
-			if ret[0] != 1 {
+			if !(info.is_file() || (nofollow && info.kind == FileKind::Symlink)) {
+				failed = true;
 				println_err!("'{}' not file" , srcpath);
 				continue;
 			}
@@ -423,16 +897,19 @@ pub async fn handle_cmd(spawn : SpawnClient){
 			let mut client = match spawn.spawn().await{
 				Ok(p) => p,
 				Err(e) => {
+					failed = true;
 					println_err!("connect error : {}", e);
 					continue;
 				},
 			};
 
-			let _ = match client.cp(&srcpath , &targetpath).await{
+			match client.cp(&srcpath , &targetpath , nofollow).await{
 				Ok(_) => {
-					println_err!("copy file '{}' to '{}' success" , srcpath , targetpath);
+					println_info!("copy file '{}' to '{}' success" , srcpath , targetpath);
 				},
-				Err(_) => {
+				Err(e) => {
+					failed = true;
+					println_err!("command execute faild : {}" , e);
 					continue;
 				},
 			};
@@ -441,6 +918,7 @@ pub async fn handle_cmd(spawn : SpawnClient){
 
 		if cmd[0] == "mkdir" {
 			if cmd.len() != 2{
+				failed = true;
 				println_err!("command 'mkdir' need 1 argument . eg : mkdir /var/folder1/folder2");
 				continue;
 			}
@@ -454,16 +932,19 @@ pub async fn handle_cmd(spawn : SpawnClient){
 			let mut client = match spawn.spawn().await{
 				Ok(p) => p,
 				Err(e) => {
+					failed = true;
 					println_err!("connect error : {}", e);
 					continue;
 				},
 			};
 
-			let _ = match client.mkd(&path).await{
+			match client.mkd(&path).await{
 				Ok(_) => {
 					println_info!("mkdir '{}' success" , path);
 				},
-				Err(_) => {
+				Err(e) => {
+					failed = true;
+					println_err!("command execute faild : {}" , e);
 					continue;
 				},
 			};
@@ -472,6 +953,7 @@ pub async fn handle_cmd(spawn : SpawnClient){
 
 		if cmd[0] == "mv" {
 			if cmd.len() != 3{
+				failed = true;
 				println_err!("command 'mv' need 2 argument . eg : mv /var/file1 /var/file2");
 				continue;
 			}
@@ -479,6 +961,7 @@ pub async fn handle_cmd(spawn : SpawnClient){
 			let srcpath = pre_handle_path(&cmd[1], &cwd);
 
 			if srcpath.is_empty(){
+				failed = true;
                 println_err!("command error , please check argument format");
 				continue;
 			}
@@ -486,6 +969,7 @@ pub async fn handle_cmd(spawn : SpawnClient){
 			let targetpath = pre_handle_path(&cmd[2], &cwd);
 
 			if targetpath.is_empty(){
+				failed = true;
                 println_err!("command error , please check argument format");
 				continue;
 			}
@@ -493,20 +977,23 @@ pub async fn handle_cmd(spawn : SpawnClient){
 			let mut client = match spawn.spawn().await{
 				Ok(p) => p,
 				Err(e) => {
+					failed = true;
 					println_err!("connect error : {}", e);
 					continue;
 				},
 			};
 
-			let (ret, _) = match client.info(&srcpath).await{
+			let info = match client.info(&srcpath).await{
 				Ok(p) => p,
 				Err(e) => {
+					failed = true;
                     println_err!("read source path infomation faild : {}" , e);
 					continue;
 				},
 			};
 			
-			if ret[0] != 1 {
+			if !info.is_file() {
+				failed = true;
 				println_err!("'{}' not a file" , srcpath);
 				continue;
 			}
@@ -514,16 +1001,19 @@ pub async fn handle_cmd(spawn : SpawnClient){
 			let mut client = match spawn.spawn().await{
 				Ok(p) => p,
 				Err(e) => {
+					failed = true;
 					println_err!("connect error : {}", e);
 					continue;
 				},
 			};
 
-			let _ = match client.mv(&srcpath , &targetpath).await{
+			match client.mv(&srcpath , &targetpath).await{
 				Ok(_) => {
 					println_info!("move file '{}' to '{}' success" , srcpath , targetpath);
 				},
-				Err(_) => {
+				Err(e) => {
+					failed = true;
+					println_err!("command execute faild : {}" , e);
 					continue;
 				},
 			};
@@ -531,6 +1021,7 @@ pub async fn handle_cmd(spawn : SpawnClient){
 		
 		if cmd[0] == "rm" {
 			if cmd.len() != 2{
+				failed = true;
 				println_err!("command 'rm' need 1 argument . eg : rm /var/folder1/file2");
 				continue;
 			}
@@ -544,132 +1035,223 @@ pub async fn handle_cmd(spawn : SpawnClient){
 			let mut client = match spawn.spawn().await{
 				Ok(p) => p,
 				Err(e) => {
+					failed = true;
 					println_err!("connect error : {}", e);
 					continue;
 				},
 			};
 
-			let _ = match client.rm(&path).await{
+			match client.rm(&path , true).await{
 				Ok(_) => {
 					println_info!("remove '{}' success" , path);
 				},
-				Err(_) => {
+				Err(e) => {
+					failed = true;
+					println_err!("command execute faild : {}" , e);
 					continue;
 				},
 			};
 		}
 
-		if cmd[0] == "hash" {
-			if cmd.len() != 2{
-				println_err!("command 'hash' need 1 argument . eg : hash /var/folder1/file2");
+		if cmd[0] == "ln" {
+			let symbolic = cmd.len() > 1 && cmd[1] == "-s";
+			let args : Vec<String> = cmd.iter().skip(if symbolic { 2 } else { 1 }).cloned().collect();
+
+			if args.len() != 2{
+				failed = true;
+				println_err!("command 'ln' need 2 argument . eg : ln [-s] /var/file1 /var/link1");
 				continue;
 			}
 
-			let path = pre_handle_path(&cmd[1], &cwd);
+			/* a symlink target is sent as typed , relative to the link folder */
+			let target = if symbolic {
+				args[0].clone()
+			} else {
+				pre_handle_path(&args[0], &cwd)
+			};
 
-			if path.is_empty(){
+			let linkpath = pre_handle_path(&args[1], &cwd);
+
+			if target.is_empty() || linkpath.is_empty(){
 				continue;
 			}
 
 			let mut client = match spawn.spawn().await{
 				Ok(p) => p,
 				Err(e) => {
+					failed = true;
 					println_err!("connect error : {}", e);
 					continue;
 				},
 			};
 
-			let (info, abspath) = match client.info(&path).await{
-				Ok(p) => p,
+			match client.ln(&target , &linkpath , symbolic).await{
+				Ok(_) => {
+					println_info!("link '{}' to '{}' success" , linkpath , target);
+				},
 				Err(e) => {
-                    println_err!("read file information faild : {}" , e);
+					failed = true;
+					println_err!("command execute faild : {}" , e);
 					continue;
 				},
 			};
+		}
+
+		if cmd[0] == "stat" {
+			if cmd.len() != 2{
+				failed = true;
+				println_err!("command 'stat' need 1 argument . eg : stat /var/link1");
+				continue;
+			}
+
+			let path = pre_handle_path(&cmd[1], &cwd);
 
-			if info[0] != 1 {
-				println_err!("'{}' not a file" , abspath);
+			if path.is_empty(){
 				continue;
 			}
 
 			let mut client = match spawn.spawn().await{
 				Ok(p) => p,
 				Err(e) => {
+					failed = true;
 					println_err!("connect error : {}", e);
 					continue;
 				},
 			};
 
-			match client.hash(&path , info[1]).await{
-				Ok(p) => {
-					println_info!("{}", p);
-				},
-				Err(_) => {
+			let info = match client.lstat(&path).await{
+				Ok(p) => p,
+				Err(e) => {
+					failed = true;
+					println_err!("read file information faild : {}" , e);
 					continue;
 				},
 			};
 
+			println!("path     : {}" , info.path);
+			println!("type     : {}" , info.kind);
+			println!("size     : {}" , info.size);
+			println!("modified : {}" , format_time(info.modified));
+			println!("accessed : {}" , format_time(info.accessed));
+			if !info.target.is_empty() {
+				println!("target   : {}" , info.target);
+			}
 		}
 
-		if cmd[0] == "cat" {
-			if cmd.len() != 2{
-				println_err!("command 'cat' need 1 argument . eg : cat /var/folder1/file2");
+		if cmd[0] == "watch" {
+			if batch.is_some() {
+				failed = true;
+				println_err!("command 'watch' needs a terminal to stop it , it can not run in a script");
 				continue;
 			}
 
-			let path = pre_handle_path(&cmd[1], &cwd);
+			let recursive = cmd.len() > 1 && cmd[1] == "-r";
+			let args : Vec<String> = cmd.iter().skip(if recursive { 2 } else { 1 }).cloned().collect();
+
+			if args.len() > 1 {
+				failed = true;
+				println_err!("command 'watch' need 0 or 1 argument . eg : watch [-r] /var/drop");
+				continue;
+			}
+
+			let path = match args.first() {
+				Some(p) => pre_handle_path(p, &cwd),
+				None => cwd.clone(),
+			};
 
 			if path.is_empty(){
 				continue;
 			}
 
-			let mut client = match spawn.spawn().await{
+			let client = match spawn.spawn().await{
 				Ok(p) => p,
 				Err(e) => {
+					failed = true;
 					println_err!("connect error : {}", e);
 					continue;
 				},
 			};
 
-			let (info, abspath) = match client.info(&path).await{
+			let events = match client.watch(&path , recursive).await{
 				Ok(p) => p,
 				Err(e) => {
-                    println_err!("read file infomation faild : {}" , e);
+					failed = true;
+					println_err!("command execute faild : {}" , e);
 					continue;
 				},
 			};
 
-			if info[0] != 1 {
-				println_err!("'{}' not a file" , abspath);
-				continue;
-			}
+			println_info!("watching '{}' , press enter to stop" , path);
+
+			failed = !follow_stream(events, |event| {
+				println!("{} {:<12} {}" , chrono::Local::now().format("%H:%M:%S") , event.kind , event.path);
+			}).await;
+			continue;
+		}
+
+		if cmd[0] == "tail" {
+			let (path , lines , follow) = match parse_tail_args(&cmd, &cwd){
+				Ok(p) => p,
+				Err(e) => {
+					failed = true;
+					println_err!("{} . eg : tail -n 20 -f /var/log/app.log" , e);
+					continue;
+				},
+			};
 
-			if info[1] > 1024 * 100 {
-				println_err!("'{}' file size > 100kb , command cat cant read" , abspath);
+			if follow && batch.is_some() {
+				failed = true;
+				println_err!("command 'tail -f' needs a terminal to stop it , it can not run in a script");
 				continue;
 			}
 
-			let mut client = match spawn.spawn().await{
+			let client = match spawn.spawn().await{
 				Ok(p) => p,
 				Err(e) => {
+					failed = true;
 					println_err!("connect error : {}", e);
 					continue;
 				},
 			};
 
-			match client.cat(&path).await{
-				Ok(p) => {
-					println!("{}" , p);
-				},
-				Err(_) => {
+			let mut data = match client.tail(&path , lines , follow).await{
+				Ok(p) => p,
+				Err(e) => {
+					failed = true;
+					println_err!("command execute faild : {}" , e);
 					continue;
 				},
 			};
+
+			let print = |chunk : Vec<u8>| {
+				let mut out = std::io::stdout();
+				let _ = out.write_all(&chunk);
+				let _ = out.flush();
+			};
+
+			if follow {
+				println_info!("following '{}' , press enter to stop" , path);
+				failed = !follow_stream(data, print).await;
+				continue;
+			}
+
+			while let Some(chunk) = data.next().await {
+				match chunk {
+					Ok(p) => print(p),
+					Err(e) => {
+						failed = true;
+						println_err!("tail error : {}" , e);
+						break;
+					},
+				}
+			}
+			continue;
 		}
 
-		if cmd[0] == "get" {
-			if cmd.len() != 2{
-				println_err!("command 'get' need 1 argument . eg : get /var/folder1/file2");
+		if cmd[0] == "truncate" {
+			if cmd.len() != 3{
+				failed = true;
+				println_err!("command 'truncate' need 2 argument . eg : truncate /var/log/app.log 0");
 				continue;
 			}
 
@@ -679,42 +1261,622 @@ pub async fn handle_cmd(spawn : SpawnClient){
 				continue;
 			}
 
+			let length = match parse_size(&cmd[2]){
+				Some(p) => p,
+				None => {
+					failed = true;
+					println_err!("invalid size : {}" , cmd[2]);
+					continue;
+				}
+			};
+
 			let mut client = match spawn.spawn().await{
 				Ok(p) => p,
 				Err(e) => {
+					failed = true;
 					println_err!("connect error : {}", e);
 					continue;
 				},
 			};
 
-			let (info, abspath) = match client.info(&path).await{
-				Ok(p) => p,
+			let token = locks.get(&path).copied().unwrap_or(0);
+
+			match client.truncate(&path , length , token).await{
+				Ok(_) => {
+					println_info!("truncate '{}' to {} bytes success" , path , length);
+				},
 				Err(e) => {
-                    println_err!("read file infomation faild : {}" , e);
+					failed = true;
+					println_err!("command execute faild : {}" , e);
 					continue;
 				},
 			};
+		}
 
-			if info[0] != 1 {
-				println_err!("'{}' not a file" , abspath);
+		if cmd[0] == "lock" {
+			if cmd.len() != 2 && cmd.len() != 3 {
+				failed = true;
+				println_err!("command 'lock' need 1 or 2 arguments . eg : lock /var/folder1/file2 120");
 				continue;
 			}
 
-			let filename : String;
+			let path = pre_handle_path(&cmd[1], &cwd);
 
-			if path.as_bytes()[0] == b'/' {
-				filename = path.split_at(path.rfind('/').unwrap() + 1).1.to_string();
-			} else {
-				filename = path.split_at(path.rfind('\\').unwrap() + 1).1.to_string();
+			let lease = match cmd.get(2) {
+				Some(p) => match p.parse::<u64>() {
+					Ok(p) => p,
+					Err(_) => {
+						failed = true;
+						println_err!("invalid lease seconds : {}" , p);
+						continue;
+					}
+				},
+				None => 0,
+			};
+
+			let mut client = match spawn.spawn().await{
+				Ok(p) => p,
+				Err(e) => {
+					failed = true;
+					println_err!("connect error : {}", e);
+					continue;
+				},
+			};
+
+			/* locking a path again renews the lease */
+			let token = locks.get(&path).copied().unwrap_or(0);
+
+			match client.lock(&path , lease , token).await{
+				Ok(p) => {
+					locks.insert(path.clone(), p);
+					println_info!("lock '{}' success , token : {:016x}" , path , p);
+				},
+				Err(e) => {
+					failed = true;
+					println_err!("command execute faild : {}" , e);
+					continue;
+				},
+			};
+		}
+
+		if cmd[0] == "unlock" {
+			if cmd.len() != 2 {
+				failed = true;
+				println_err!("command 'unlock' need 1 argument . eg : unlock /var/folder1/file2");
+				continue;
+			}
+
+			let path = pre_handle_path(&cmd[1], &cwd);
+
+			let token = match locks.remove(&path) {
+				Some(p) => p,
+				None => {
+					failed = true;
+					println_err!("'{}' is not locked by this shell" , path);
+					continue;
+				}
+			};
+
+			let mut client = match spawn.spawn().await{
+				Ok(p) => p,
+				Err(e) => {
+					failed = true;
+					println_err!("connect error : {}", e);
+					continue;
+				},
+			};
+
+			match client.unlock(&path , token).await{
+				Ok(_) => {
+					println_info!("unlock '{}' success" , path);
+				},
+				Err(e) => {
+					failed = true;
+					println_err!("command execute faild : {}" , e);
+					continue;
+				},
+			};
+		}
+
+		if cmd[0] == "sessions" {
+			if cmd.len() != 1 {
+				failed = true;
+				println_err!("command 'sessions' need 0 argument");
+				continue;
+			}
+
+			let mut client = match spawn.spawn().await{
+				Ok(p) => p,
+				Err(e) => {
+					failed = true;
+					println_err!("connect error : {}", e);
+					continue;
+				},
+			};
+
+			let result = match client.sessions().await{
+				Ok(p) => p,
+				Err(e) => {
+					failed = true;
+					println_err!("command execute faild : {}" , e);
+					continue;
+				},
+			};
+
+			let mut rows : Vec<SessionRow> = vec![];
+
+			let known = |p : Option<String>| p.unwrap_or_else(|| String::from("-"));
+
+			for i in result {
+				/* total is 0 when the command is not a transfer */
+				let progress = match i.total {
+					0 => i.bytes.to_string(),
+					total => format!("{}/{} ({}%)" , i.bytes , total , i.bytes * 100 / total),
+				};
+				let paths = if i.paths.is_empty() { String::from("-") } else { i.paths.join(",") };
+				rows.push(SessionRow{id : i.id.to_string() , peer : known(i.peer) , user : known(i.user) , command : known(i.command) , paths , progress , seconds : i.seconds.to_string()});
+			}
+
+			let table = Table::new(rows).to_string();
+			print!("{}",table);
+		}
+
+		if cmd[0] == "kill" {
+			if cmd.len() != 2 {
+				failed = true;
+				println_err!("command 'kill' need 1 argument . eg : kill 12");
+				continue;
+			}
+
+			let id = match cmd[1].parse::<u64>(){
+				Ok(p) => p,
+				Err(_) => {
+					failed = true;
+					println_err!("invalid session id : {}" , cmd[1]);
+					continue;
+				}
+			};
+
+			let mut client = match spawn.spawn().await{
+				Ok(p) => p,
+				Err(e) => {
+					failed = true;
+					println_err!("connect error : {}", e);
+					continue;
+				},
+			};
+
+			match client.kill(id).await{
+				Ok(_) => {
+					println_info!("kill session {} success" , id);
+				},
+				Err(e) => {
+					failed = true;
+					println_err!("command execute faild : {}" , e);
+					continue;
+				},
+			};
+		}
+
+		if cmd[0] == "shutdown" {
+			if cmd.len() != 1 {
+				failed = true;
+				println_err!("command 'shutdown' need 0 argument");
+				continue;
+			}
+
+			let mut client = match spawn.spawn().await{
+				Ok(p) => p,
+				Err(e) => {
+					failed = true;
+					println_err!("connect error : {}", e);
+					continue;
+				},
+			};
+
+			match client.shutdown().await{
+				Ok(_) => {
+					println_info!("server is shutting down");
+				},
+				Err(e) => {
+					failed = true;
+					println_err!("command execute faild : {}" , e);
+					continue;
+				},
+			};
+		}
+
+		if cmd[0] == "quota" {
+			if cmd.len() > 2 {
+				failed = true;
+				println_err!("command 'quota' need 0 or 1 argument . eg : quota /var/folder1");
+				continue;
+			}
+
+			let path = match cmd.get(1) {
+				Some(p) => pre_handle_path(p, &cwd),
+				None => cwd.clone(),
+			};
+
+			let mut client = match spawn.spawn().await{
+				Ok(p) => p,
+				Err(e) => {
+					failed = true;
+					println_err!("connect error : {}", e);
+					continue;
+				},
+			};
+
+			let result = match client.quota(&path).await{
+				Ok(p) => p,
+				Err(e) => {
+					failed = true;
+					println_err!("command execute faild : {}" , e);
+					continue;
+				},
+			};
+
+			let mut rows : Vec<QuotaRow> = vec![];
+
+			/* a limit of - is unlimited */
+			let limit = |p : Option<u64>| match p {
+				Some(p) => p.to_string(),
+				None => String::from("-"),
+			};
+
+			for i in result {
+				rows.push(QuotaRow{kind : i.kind , path : i.path , bytes : i.bytes.to_string() , max_bytes : limit(i.max_bytes) , files : i.files.to_string() , max_files : limit(i.max_files)});
+			}
+
+			if rows.is_empty() {
+				println_info!("no quota on '{}'" , path);
+				continue;
+			}
+
+			let table = Table::new(rows).to_string();
+			print!("{}",table);
+		}
+
+		if cmd[0] == "hash" {
+			if cmd.len() != 2{
+				failed = true;
+				println_err!("command 'hash' need 1 argument . eg : hash /var/folder1/file2");
+				continue;
+			}
+
+			let path = pre_handle_path(&cmd[1], &cwd);
+
+			if path.is_empty(){
+				continue;
+			}
+
+			let mut client = match spawn.spawn().await{
+				Ok(p) => p,
+				Err(e) => {
+					failed = true;
+					println_err!("connect error : {}", e);
+					continue;
+				},
+			};
+
+			let info = match client.info(&path).await{
+				Ok(p) => p,
+				Err(e) => {
+					failed = true;
+                    println_err!("read file information faild : {}" , e);
+					continue;
+				},
+			};
+
+			if !info.is_file() {
+				failed = true;
+				println_err!("'{}' not a file" , info.path);
+				continue;
+			}
+
+			let mut client = match spawn.spawn().await{
+				Ok(p) => p,
+				Err(e) => {
+					failed = true;
+					println_err!("connect error : {}", e);
+					continue;
+				},
+			};
+
+			match client.hash(&path , info.size).await{
+				Ok(p) => {
+					println_info!("{}", p);
+				},
+				Err(e) => {
+					failed = true;
+					println_err!("command execute faild : {}" , e);
+					continue;
+				},
+			};
+
+		}
+
+		if cmd[0] == "cat" {
+			if cmd.len() != 2{
+				failed = true;
+				println_err!("command 'cat' need 1 argument . eg : cat /var/folder1/file2");
+				continue;
+			}
+
+			let path = pre_handle_path(&cmd[1], &cwd);
+
+			if path.is_empty(){
+				continue;
+			}
+
+			let mut client = match spawn.spawn().await{
+				Ok(p) => p,
+				Err(e) => {
+					failed = true;
+					println_err!("connect error : {}", e);
+					continue;
+				},
+			};
+
+			let info = match client.info(&path).await{
+				Ok(p) => p,
+				Err(e) => {
+					failed = true;
+                    println_err!("read file infomation faild : {}" , e);
+					continue;
+				},
+			};
+
+			if !info.is_file() {
+				failed = true;
+				println_err!("'{}' not a file" , info.path);
+				continue;
+			}
+
+			if info.size > 1024 * 100 {
+				failed = true;
+				println_err!("'{}' file size > 100kb , command cat cant read" , info.path);
+				continue;
+			}
+
+			let mut client = match spawn.spawn().await{
+				Ok(p) => p,
+				Err(e) => {
+					failed = true;
+					println_err!("connect error : {}", e);
+					continue;
+				},
+			};
+
+			match client.cat(&path).await{
+				Ok(p) => {
+					println!("{}" , p);
+				},
+				Err(e) => {
+					failed = true;
+					println_err!("command execute faild : {}" , e);
+					continue;
+				},
+			};
+		}
+
+		if cmd[0] == "find" {
+
+			let (path , filter) = match parse_find_args(&cmd, &cwd){
+				Ok(p) => p,
+				Err(e) => {
+					failed = true;
+					println_err!("{} . eg : find /var -name \"*.log\" -minsize 1m -newer 60", e);
+					continue;
+				},
+			};
+
+			let mut client = match spawn.spawn().await{
+				Ok(p) => p,
+				Err(e) => {
+					failed = true;
+					println_err!("connect error : {}", e);
+					continue;
+				},
+			};
+
+			let result = match client.find(&path , &filter).await{
+				Ok(p) => p,
+				Err(e) => {
+					failed = true;
+					println_err!("command execute faild : {}" , e);
+					continue;
+				},
+			};
+
+			found.clear();
+			let mut rows : Vec<FindRow> = vec![];
+
+			for i in result {
+				found.push(i.path.clone());
+				rows.push(FindRow{index : found.len() , typ : i.kind.to_string() , size : i.size.to_string() , modified : format_time(i.modified) , path : i.path});
+			}
+
+			if rows.is_empty() {
+				println_info!("no file matched");
+				continue;
+			}
+
+			let table = Table::new(rows).to_string();
+			print!("{}",table);
+		}
+
+		if cmd[0] == "grep" {
+
+			let args = match parse_grep_args(&cmd, &cwd){
+				Ok(p) => p,
+				Err(e) => {
+					failed = true;
+					println_err!("{} . eg : grep -i -C 2 error /var/log", e);
+					continue;
+				},
+			};
+
+			let mut client = match spawn.spawn().await{
+				Ok(p) => p,
+				Err(e) => {
+					failed = true;
+					println_err!("connect error : {}", e);
+					continue;
+				},
+			};
+
+			let result = match client.grep(&args.path , &args.pattern , args.regex , args.ignore_case , args.context , args.max_matches).await{
+				Ok(p) => p,
+				Err(e) => {
+					failed = true;
+					println_err!("command execute faild : {}" , e);
+					continue;
+				},
+			};
+
+			if result.is_empty() {
+				println_info!("no line matched");
+				continue;
+			}
+
+			for row in result {
+				println!("{}", row);
+			}
+		}
+
+		if cmd[0] == "get" && cmd.len() > 1 && cmd[1] == "-r" {
+
+			let mut compress = COMPRESS_NONE;
+			let mut extract = false;
+			let mut positional : Vec<String> = vec![];
+
+			for arg in &cmd[2..] {
+				match arg.as_str() {
+					"-z" | "--gzip" => compress = COMPRESS_GZIP,
+					"--zstd" => compress = COMPRESS_ZSTD,
+					"-x" => extract = true,
+					_ => positional.push(arg.clone()),
+				}
+			}
+
+			if positional.is_empty() || positional.len() > 2 {
+				failed = true;
+				println_err!("command 'get -r' need 1 or 2 argument . eg : get -r --zstd -x /var/folder1 ./local");
+				continue;
+			}
+
+			let path = pre_handle_path(&positional[0], &cwd);
+
+			if path.is_empty(){
+				continue;
+			}
+
+			let localpath = match positional.get(1) {
+				Some(p) => p.clone(),
+				None => {
+					if extract {
+						String::from(".")
+					} else {
+						let name = path.rsplit(['/', '\\']).find(|p| !p.is_empty()).unwrap_or("archive").to_string();
+						match compress {
+							COMPRESS_GZIP => name + ".tar.gz",
+							COMPRESS_ZSTD => name + ".tar.zst",
+							_ => name + ".tar",
+						}
+					}
+				},
+			};
+
+			let mut client = match spawn.spawn().await{
+				Ok(p) => p,
+				Err(e) => {
+					failed = true;
+					println_err!("connect error : {}", e);
+					continue;
+				},
+			};
+
+			match client.get_tar(&localpath , &path , compress , extract).await{
+				Ok(p) => {
+					if extract {
+						println_info!("extract {} entries to '{}' success!" , p , localpath);
+					} else {
+						println_info!("save archive to '{}' success!" , localpath);
+					}
+				},
+				Err(e) => {
+					failed = true;
+					println_err!("command execute faild : {}" , e);
+					continue;
+				},
+			};
+			continue;
+		}
+
+		if cmd[0] == "get" {
+			if cmd.len() != 2{
+				failed = true;
+				println_err!("command 'get' need 1 argument . eg : get /var/folder1/file2");
+				continue;
+			}
+
+			let path = match cmd[1].strip_prefix('#') {
+				Some(index) => {
+					match index.parse::<usize>(){
+						Ok(p) if p >= 1 && p <= found.len() => found[p - 1].clone(),
+						_ => {
+							failed = true;
+							println_err!("index '{}' not in last find result", index);
+							continue;
+						},
+					}
+				},
+				None => pre_handle_path(&cmd[1], &cwd),
+			};
+
+			if path.is_empty(){
+				continue;
+			}
+
+			let mut client = match spawn.spawn().await{
+				Ok(p) => p,
+				Err(e) => {
+					failed = true;
+					println_err!("connect error : {}", e);
+					continue;
+				},
+			};
+
+			let info = match client.info(&path).await{
+				Ok(p) => p,
+				Err(e) => {
+					failed = true;
+                    println_err!("read file infomation faild : {}" , e);
+					continue;
+				},
+			};
+
+			if !info.is_file() {
+				failed = true;
+				println_err!("'{}' not a file" , info.path);
+				continue;
 			}
 
+			let filename = if path.as_bytes()[0] == b'/' {
+				path.split_at(path.rfind('/').unwrap() + 1).1.to_string()
+			} else {
+				path.split_at(path.rfind('\\').unwrap() + 1).1.to_string()
+			};
+
 			match fs::metadata(filename.clone()).await{
 				Ok(p) => {
 					println_info!("local file '{}' already exists" , filename);
-					let remote_file_size = info[1];
+					let remote_file_size = info.size;
 					let local_file_size = p.len();
 
 					if local_file_size >= remote_file_size{
+						failed = true;
 						println_err!("local file and remote file size equal or bigger. please remove local file before execute command put.");
 						continue;
 					}
@@ -722,6 +1884,7 @@ pub async fn handle_cmd(spawn : SpawnClient){
 					let mut client = match spawn.spawn().await{
 						Ok(p) => p,
 						Err(e) => {
+							failed = true;
 							println_err!("connect error : {}", e);
 							continue;
 						},
@@ -735,6 +1898,7 @@ pub async fn handle_cmd(spawn : SpawnClient){
 							let mut f = match fs::File::open(&filename).await{
 								Ok(p) => p,
 								Err(e) => {
+									failed = true;
 									println_err!("open local file faild : {}" , e);
 									continue;
 								},
@@ -745,12 +1909,14 @@ pub async fn handle_cmd(spawn : SpawnClient){
 							match f.close().await{
 								Ok(_) => {},
 								Err(e) => {
+									failed = true;
 									println_err!("close local file faild : {}" , e);
 									continue;
 								},
 							};
 
 							if md5_str.is_empty() {
+								failed = true;
 								println_err!("calc local file hash faild !");
 								continue;
 							}
@@ -758,6 +1924,7 @@ pub async fn handle_cmd(spawn : SpawnClient){
 							println_info!("local file hash : {}" , md5_str);
 
 							if md5_str != p{
+								failed = true;
 								println_err!("remote file and local file hash not equal. please remove remote file before execute command put.");
 								continue;
 							}
@@ -767,6 +1934,7 @@ pub async fn handle_cmd(spawn : SpawnClient){
 							let mut client = match spawn.spawn().await{
 								Ok(p) => p,
 								Err(e) => {
+									failed = true;
 									println_err!("connect error : {}", e);
 									continue;
 								},
@@ -776,7 +1944,9 @@ pub async fn handle_cmd(spawn : SpawnClient){
 								Ok(_) => {
 									println_info!("file transfer success!");
 								},
-								Err(_) => {
+								Err(e) => {
+									failed = true;
+									println_err!("command execute faild : {}" , e);
 									continue;
 								},
 							};
@@ -796,6 +1966,7 @@ pub async fn handle_cmd(spawn : SpawnClient){
 			let mut client = match spawn.spawn().await{
 				Ok(p) => p,
 				Err(e) => {
+					failed = true;
 					println_err!("connect error : {}", e);
 					continue;
 				},
@@ -805,15 +1976,149 @@ pub async fn handle_cmd(spawn : SpawnClient){
 				Ok(_) => {
                     println_info!("file transfer success!");
                 },
-				Err(_) => {
+				Err(e) => {
+					failed = true;
+					println_err!("command execute faild : {}" , e);
+					continue;
+				},
+			};
+
+		}
+
+		/* put -w waits for the write lock of the remote file instead of failing */
+		let (cmd , put_flags) = if cmd[0] == "put" && cmd.len() > 1 && cmd[1] == "-w" {
+			([&cmd[..1], &cmd[2..]].concat() , PUT_FLAG_WAIT)
+		} else {
+			(cmd , 0u8)
+		};
+
+		if cmd.is_empty() {
+			continue;
+		}
+
+		if cmd[0] == "put" && cmd.len() > 1 && cmd[1] == "-a" {
+			if cmd.len() != 3 && cmd.len() != 4 {
+				failed = true;
+				println_err!("command 'put -a' need 1 or 2 argument . eg : put -a ./app.log /var/log/app.log");
+				continue;
+			}
+
+			let localpath = cmd[2].clone();
+
+			let remotepath = match cmd.get(3) {
+				Some(p) => pre_handle_path(p, &cwd),
+				None => {
+					let filename = match std::path::Path::new(&localpath).file_name(){
+						Some(p) => p.to_string_lossy().to_string(),
+						None => {
+							failed = true;
+							println_err!("command 'put -a' need a local file . eg : put -a ./app.log");
+							continue;
+						}
+					};
+					pre_handle_path(&filename, &cwd)
+				},
+			};
+
+			if remotepath.is_empty(){
+				continue;
+			}
+
+			let mut client = match spawn.spawn().await{
+				Ok(p) => p,
+				Err(e) => {
+					failed = true;
+					println_err!("connect error : {}", e);
+					continue;
+				},
+			};
+
+			let token = locks.get(&remotepath).copied().unwrap_or(0);
+
+			match client.put(&localpath , &remotepath , 0 , PUT_FLAG_APPEND | put_flags , token).await{
+				Ok(p) => {
+					println_info!("append '{}' to '{}' success" , localpath , p);
+				},
+				Err(e) => {
+					failed = true;
+					println_err!("command execute faild : {}" , e);
+					continue;
+				},
+			};
+			continue;
+		}
+
+		if cmd[0] == "put" && cmd.len() > 1 && cmd[1] == "-r" {
+
+			let mut compress = COMPRESS_NONE;
+			let mut positional : Vec<String> = vec![];
+
+			for arg in &cmd[2..] {
+				match arg.as_str() {
+					"--tar" => {},
+					"-z" | "--gzip" => compress = COMPRESS_GZIP,
+					"--zstd" => compress = COMPRESS_ZSTD,
+					_ => positional.push(arg.clone()),
+				}
+			}
+
+			if positional.is_empty() || positional.len() > 2 {
+				failed = true;
+				println_err!("command 'put -r' need 1 or 2 argument . eg : put -r --tar ./build /var/www");
+				continue;
+			}
+
+			let localpath = positional[0].clone();
+
+			match fs::metadata(&localpath).await{
+				Ok(_) => {},
+				Err(e) => {
+					failed = true;
+					println_err!("read local path '{}' faild : {}" , localpath , e);
+					continue;
+				},
+			};
+
+			let remotepath = match positional.get(1) {
+				Some(p) => pre_handle_path(p, &cwd),
+				None => cwd.clone(),
+			};
+
+			let mut client = match spawn.spawn().await{
+				Ok(p) => p,
+				Err(e) => {
+					failed = true;
+					println_err!("connect error : {}", e);
+					continue;
+				},
+			};
+
+			let result = match client.put_tar(&localpath , &remotepath , compress).await{
+				Ok(p) => p,
+				Err(e) => {
+					failed = true;
+					println_err!("command execute faild : {}" , e);
 					continue;
 				},
 			};
 
+			let mut ok = 0;
+			for entry in &result {
+				if entry.is_ok() {
+					ok += 1;
+				} else {
+					failed = true;
+					println_err!("{} : {}" , entry.status , entry.path);
+				}
+			}
+
+			println_info!("extract {}/{} entries to '{}'" , ok , result.len() , remotepath);
+			continue;
 		}
 
 		if cmd[0] == "put" {
 			if cmd.len() != 2{
+				failed = true;
 				println_err!("command 'put' need 1 argument . eg : put /localfile/file1");
 				continue;
 			}
@@ -828,6 +2133,7 @@ pub async fn handle_cmd(spawn : SpawnClient){
 						p
 					},
 					None => {
+						failed = true;
 						println_err!("command 'put' need 1 argument . eg : put /localfile/file1");
 						continue;
 					}
@@ -839,6 +2145,7 @@ pub async fn handle_cmd(spawn : SpawnClient){
 						p
 					},
 					None => {
+						failed = true;
 						println_err!("command 'put' need 1 argument . eg : put /localfile/file1");
 						continue;
 					}
@@ -855,111 +2162,138 @@ pub async fn handle_cmd(spawn : SpawnClient){
 			let mut client = match spawn.spawn().await{
 				Ok(p) => p,
 				Err(e) => {
+					failed = true;
 					println_err!("connect error : {}", e);
 					continue;
 				},
 			};
 
-			match client.info(&remotepath).await{
-				Ok(p) => {
-					println_info!("remote file '{}' already exists" , p.1);
-					let size = p.0[1];
+			if let Ok(p) = client.info(&remotepath).await{
+				println_info!("remote file '{}' already exists" , p.path);
+				let size = p.size;
 
-					let mut client = match spawn.spawn().await{
-						Ok(p) => p,
-						Err(e) => {
-							println_err!("connect error : {}", e);
-							continue;
-						},
-					};
+				let mut client = match spawn.spawn().await{
+					Ok(p) => p,
+					Err(e) => {
+						failed = true;
+						println_err!("connect error : {}", e);
+						continue;
+					},
+				};
 
-					match client.hash(&p.1, size).await{
-						Ok(p) => {
-							println_info!("remote file hash : {}" , p);
+				match client.hash(&p.path, size).await{
+					Ok(p) => {
+						println_info!("remote file hash : {}" , p);
 
-							let mut f = match fs::File::open(&localpath).await{
-								Ok(p) => p,
-								Err(e) => {
-									println_err!("open local file faild : {}" , e);
-									continue;
-								},
-							};
-					
-							if size >= f.metadata().await.unwrap().len(){
-								println_err!("remote file and local file size equal or bigger. please remove remote file before execute command put.");
+						let mut f = match fs::File::open(&localpath).await{
+							Ok(p) => p,
+							Err(e) => {
+								failed = true;
+								println_err!("open local file faild : {}" , e);
 								continue;
-							}
-					
-							let md5_str = calc_md5(&mut f , size).await;
-
-							match f.close().await{
-								Ok(_) => {},
-								Err(e) => {
-									println_err!("close local file faild : {}" , e);
-									continue;
-								},
-							};
+							},
+						};
+				
+						if size >= f.metadata().await.unwrap().len(){
+							failed = true;
+							println_err!("remote file and local file size equal or bigger. please remove remote file before execute command put.");
+							continue;
+						}
+				
+						let md5_str = calc_md5(&mut f , size).await;
 
-							if md5_str.is_empty() {
-								println_err!("calc local file hash faild !");
+						match f.close().await{
+							Ok(_) => {},
+							Err(e) => {
+								failed = true;
+								println_err!("close local file faild : {}" , e);
 								continue;
-							}
-
-							println_info!("local file hash : {}" , md5_str);
+							},
+						};
 
-							if md5_str != p{
-								println_err!("remote file and local file hash not equal. please remove remote file before execute command put.");
-								continue;
-							}
-						},
-						Err(e) => {
-							println_err!("calc remote file hash faild : {}" , e );
+						if md5_str.is_empty() {
+							failed = true;
+							println_err!("calc local file hash faild !");
 							continue;
-						},
-					};
+						}
 
-					println_info!("start resume broken transfer!");
+						println_info!("local file hash : {}" , md5_str);
 
-					let mut client = match spawn.spawn().await{
-						Ok(p) => p,
-						Err(e) => {
-							println_err!("connect error : {}", e);
+						if md5_str != p{
+							failed = true;
+							println_err!("remote file and local file hash not equal. please remove remote file before execute command put.");
 							continue;
-						},
-					};
+						}
+					},
+					Err(e) => {
+						failed = true;
+						println_err!("calc remote file hash faild : {}" , e );
+						continue;
+					},
+				};
 
-					match client.put(&localpath , &remotepath , size).await{
-						Ok(_) => {
-							println_info!("file transfer success!");
-						},
-						Err(_) => {
-							continue;
-						},
-					};
-					continue;
-				},
-				Err(_) => {},
-			};
+				println_info!("start resume broken transfer!");
+
+				let mut client = match spawn.spawn().await{
+					Ok(p) => p,
+					Err(e) => {
+						failed = true;
+						println_err!("connect error : {}", e);
+						continue;
+					},
+				};
+
+				let token = locks.get(&remotepath).copied().unwrap_or(0);
+
+				match client.put(&localpath , &remotepath , size , put_flags , token).await{
+					Ok(_) => {
+						println_info!("file transfer success!");
+					},
+					Err(e) => {
+						failed = true;
+						println_err!("command execute faild : {}" , e);
+						continue;
+					},
+				};
+				continue;
+			}
 
 			let mut client = match spawn.spawn().await{
 				Ok(p) => p,
 				Err(e) => {
+					failed = true;
 					println_err!("connect error : {}", e);
 					continue;
 				},
 			};
 
-			match client.put(&localpath , &remotepath , 0).await{
+			let token = locks.get(&remotepath).copied().unwrap_or(0);
+
+			match client.put(&localpath , &remotepath , 0 , put_flags , token).await{
 				Ok(_) => {
                     println_info!("file transfer success!");
                 },
-				Err(_) => {
+				Err(e) => {
+					failed = true;
+					println_err!("command execute faild : {}" , e);
 					continue;
 				},
 			};
 
 		}
 	}
+
+	match batch {
+		Some(batch) => {
+			if batch.ok() {
+				println_info!("{}" , batch.summary());
+			} else {
+				println_err!("{}" , batch.summary());
+			}
+			batch.ok()
+		},
+		None => true,
+	}
 }
 
 #[test]
@@ -973,4 +2307,45 @@ fn test_cmd() {
 
 	assert_eq!(ret[0] , String::from("ls"));
 	assert_eq!(ret[1] , String::from("test"));
+
+	let mut batch = Batch::new(Box::new(std::io::Cursor::new("# backup\n\nls\n  hash a  \nget b\n")));
+	assert_eq!(batch.next_command().unwrap() , "ls");
+	assert!(batch.done(false));
+	assert_eq!(batch.next_command().unwrap() , "hash a");
+	assert!(batch.done(true));
+	batch.stop_on_error = true;
+	assert_eq!(batch.next_command().unwrap() , "get b");
+	assert!(!batch.done(true));
+	assert!(batch.next_command().is_none());
+	assert!(!batch.ok());
+	assert_eq!(batch.summary() , "stopped at line 5 , 3 commands run , 2 failed");
+}
+
+#[test]
+fn test_find_args() {
+	assert_eq!(parse_size("10"), Some(10));
+	assert_eq!(parse_size("2k"), Some(2048));
+	assert_eq!(parse_size("1MB"), Some(1024 * 1024));
+	assert_eq!(parse_size("1x"), None);
+
+	let cmd = cmd_format("find sub -name \"*.log\" -type f -minsize 1k -limit 5".to_string()).unwrap();
+	let (path , filter) = parse_find_args(&cmd, &"/var".to_string()).unwrap();
+	assert_eq!(path , String::from("/var/sub"));
+	assert_eq!(filter.name , String::from("*.log"));
+	assert_eq!(filter.typ , FIND_TYPE_FILE);
+	assert_eq!(filter.min_size , 1024);
+	assert_eq!(filter.max_results , 5);
+
+	let cmd = cmd_format("find -type x".to_string()).unwrap();
+	assert!(parse_find_args(&cmd, &"/var".to_string()).is_err());
+
+	/* minutes are a plain number , never a size , and never overflow */
+	let cmd = cmd_format("find -newer 999999999999999999".to_string()).unwrap();
+	assert!(parse_find_args(&cmd, &"/var".to_string()).is_err());
+	let cmd = cmd_format("find -newer 1g".to_string()).unwrap();
+	assert!(parse_find_args(&cmd, &"/var".to_string()).is_err());
+	let cmd = cmd_format("find -older 10 -maxsize 1g".to_string()).unwrap();
+	let (_ , filter) = parse_find_args(&cmd, &"/var".to_string()).unwrap();
+	assert!(filter.older <= chrono::Utc::now().timestamp() as u64 - 600);
+	assert_eq!(filter.max_size , 1024 * 1024 * 1024);
 }
\ No newline at end of file
@@ -1,4 +1,8 @@
-use std::{error, fmt::{self, Debug}};
+use std::{error, fmt::{self, Debug}, time::{Duration, SystemTime, UNIX_EPOCH}};
+
+use chrono::NaiveDateTime;
+
+use crate::archive::ENTRY_OK;
 
 pub enum YaftpError {
 	OK,
@@ -18,6 +22,12 @@ pub enum YaftpError {
 	WriteFileError,
 	CalcMd5Error,
 	UnknownNetwordError,
+	Locked,
+	AuthFaild,
+	QuotaExceeded,
+	ServerBusy,
+	Cancelled,
+	TimedOut,
 	UnknownError
 }
 
@@ -41,6 +51,12 @@ impl fmt::Display for YaftpError {
 			Self::WriteFileError => write!(f, "WRITE_FILE_ERROR"),
 			Self::CalcMd5Error => write!(f, "CALC_MD5_ERROR"),
 			Self::UnknownNetwordError => write!(f, "UNKNOWN_NETWORD_ERROR"),
+			Self::Locked => write!(f, "LOCKED"),
+			Self::AuthFaild => write!(f, "AUTH_FAILD"),
+			Self::QuotaExceeded => write!(f, "QUOTA_EXCEEDED"),
+			Self::ServerBusy => write!(f, "SERVER_BUSY"),
+			Self::Cancelled => write!(f, "CANCELLED"),
+			Self::TimedOut => write!(f, "TIMED_OUT"),
 			Self::UnknownError => write!(f, "UNKNOWN_ERROR"),
 		}
 	}
@@ -72,6 +88,12 @@ impl Debug for YaftpError {
 			Self::WriteFileError => write!(f, "WRITE_FILE_ERROR"),
 			Self::CalcMd5Error => write!(f, "CALC_MD5_ERROR"),
 			Self::UnknownNetwordError => write!(f, "UNKNOWN_NETWORD_ERROR"),
+			Self::Locked => write!(f, "LOCKED"),
+			Self::AuthFaild => write!(f, "AUTH_FAILD"),
+			Self::QuotaExceeded => write!(f, "QUOTA_EXCEEDED"),
+			Self::ServerBusy => write!(f, "SERVER_BUSY"),
+			Self::Cancelled => write!(f, "CANCELLED"),
+			Self::TimedOut => write!(f, "TIMED_OUT"),
 			Self::UnknownError => write!(f, "UNKNOWN_ERROR"),
 		}
 	}
@@ -96,6 +118,12 @@ pub fn retcode_error(retcode : u8) -> YaftpError {
 		0x0e => YaftpError::WriteFileError,
 		0x0f => YaftpError::CalcMd5Error,
 		0x10 => YaftpError::UnknownNetwordError,
+		0x11 => YaftpError::Locked,
+		0x12 => YaftpError::AuthFaild,
+		0x13 => YaftpError::QuotaExceeded,
+		0x14 => YaftpError::ServerBusy,
+		0x15 => YaftpError::Cancelled,
+		0x16 => YaftpError::TimedOut,
 		_ =>	YaftpError::UnknownError
 	}
 }
@@ -119,6 +147,399 @@ pub fn error_retcode(code : YaftpError) -> u8 {
 		YaftpError::WriteFileError => 0x0e,
 		YaftpError::CalcMd5Error => 0x0f,
 		YaftpError::UnknownNetwordError => 0x10,
+		YaftpError::Locked => 0x11,
+		YaftpError::AuthFaild => 0x12,
+		YaftpError::QuotaExceeded => 0x13,
+		YaftpError::ServerBusy => 0x14,
+		YaftpError::Cancelled => 0x15,
+		YaftpError::TimedOut => 0x16,
 		YaftpError::UnknownError => 0xff,
 	}
-}
\ No newline at end of file
+}
+
+/* reply narg of a command which streams arguments until an empty argument */
+pub const STREAM_NARG : u32 = u32::MAX;
+
+pub const FIND_TYPE_ANY : u8 = 0;
+pub const FIND_TYPE_FILE : u8 = 1;
+pub const FIND_TYPE_FOLDER : u8 = 2;
+pub const FIND_TYPE_SYMLINK : u8 = 3;
+
+pub struct FindFilter {
+	pub name : String,
+	pub regex : bool,
+	pub typ : u8,
+	pub min_size : u64,
+	pub max_size : u64,
+	pub newer : u64,
+	pub older : u64,
+	pub max_depth : u64,
+	pub max_results : u64
+}
+
+impl Default for FindFilter {
+	fn default() -> Self {
+		FindFilter {
+			name : String::new(),
+			regex : false,
+			typ : FIND_TYPE_ANY,
+			min_size : 0,
+			max_size : u64::MAX,
+			newer : 0,
+			older : u64::MAX,
+			max_depth : 0,
+			max_results : 0
+		}
+	}
+}
+
+/* flags argument of put , append data to the end of the file */
+pub const PUT_FLAG_APPEND : u8 = 0x01;
+/* flags argument of put , wait for the write lock instead of failing with LOCKED */
+pub const PUT_FLAG_WAIT : u8 = 0x02;
+
+/* flags argument of info , cp and rm , operate on a symlink itself */
+pub const LINK_NOFOLLOW : u8 = 0x01;
+
+/* type of a path as info and ls tell it */
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FileKind {
+	Folder,
+	File,
+	Symlink,
+	Other
+}
+
+impl FileKind {
+
+	/* filetype argument of info */
+	pub fn from_code(code : u8) -> FileKind {
+		match code {
+			0 => FileKind::Folder,
+			1 => FileKind::File,
+			2 => FileKind::Symlink,
+			_ => FileKind::Other,
+		}
+	}
+
+	/* type column of an ls row */
+	pub fn from_name(name : &str) -> FileKind {
+		match name {
+			"folder" => FileKind::Folder,
+			"file" => FileKind::File,
+			"symlink" => FileKind::Symlink,
+			_ => FileKind::Other,
+		}
+	}
+}
+
+impl fmt::Display for FileKind {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			FileKind::Folder => write!(f, "folder"),
+			FileKind::File => write!(f, "file"),
+			FileKind::Symlink => write!(f, "symlink"),
+			FileKind::Other => write!(f, "other"),
+		}
+	}
+}
+
+/* a path as info tells it , target is the link of a symlink read by lstat and empty else */
+#[derive(Clone, Debug, PartialEq)]
+pub struct FileInfo {
+	pub kind : FileKind,
+	pub size : u64,
+	pub modified : SystemTime,
+	pub accessed : SystemTime,
+	pub path : String,
+	pub target : String
+}
+
+impl FileInfo {
+	pub fn is_file(&self) -> bool {
+		self.kind == FileKind::File
+	}
+
+	pub fn is_folder(&self) -> bool {
+		self.kind == FileKind::Folder
+	}
+}
+
+/* an entry of a folder as ls tells it */
+#[derive(Clone, Debug, PartialEq)]
+pub struct DirEntry {
+	pub name : String,
+	pub kind : FileKind,
+	pub size : u64,
+	pub modified : SystemTime,
+	pub accessed : SystemTime
+}
+
+impl DirEntry {
+
+	/*
+		a row of ls , name type size modified and accessed split by | , times
+		in utc . split from the end since a name may hold a | .
+	*/
+	pub fn parse(row : &str) -> Result<DirEntry , YaftpError> {
+		let mut col : Vec<&str> = row.rsplitn(5, '|').collect();
+		if col.len() != 5 {
+			return Err(YaftpError::ArgumentError);
+		}
+		col.reverse();
+
+		Ok(DirEntry {
+			name : col[0].to_string(),
+			kind : FileKind::from_name(col[1]),
+			size : parse_number(col[2])?,
+			modified : parse_time(col[3])?,
+			accessed : parse_time(col[4])?
+		})
+	}
+}
+
+/* a path matched by find */
+#[derive(Clone, Debug, PartialEq)]
+pub struct FindEntry {
+	pub kind : FileKind,
+	pub size : u64,
+	pub modified : SystemTime,
+	pub path : String
+}
+
+impl FindEntry {
+
+	/* a row of find , type size modified and path split by | , the path last since it may hold a | */
+	pub fn parse(row : &str) -> Result<FindEntry , YaftpError> {
+		let col : Vec<&str> = row.splitn(4, '|').collect();
+		if col.len() != 4 {
+			return Err(YaftpError::ArgumentError);
+		}
+
+		Ok(FindEntry {
+			kind : FileKind::from_name(col[0]),
+			size : parse_number(col[1])?,
+			modified : parse_time(col[2])?,
+			path : col[3].to_string()
+		})
+	}
+}
+
+/* a line found by grep , context lines around a match are not matched */
+#[derive(Clone, Debug, PartialEq)]
+pub struct GrepLine {
+	pub path : String,
+	pub line : u64,
+	pub matched : bool,
+	pub text : String
+}
+
+impl GrepLine {
+
+	/*
+		a row of grep , `path:line:text` for a match and `path-line-text` for
+		context . the path ends at the first separator , line number and
+		same separator , like the output of grep it is ambiguous when a path
+		holds such a part .
+	*/
+	pub fn parse(row : &str) -> Result<GrepLine , YaftpError> {
+		for (i , sep) in row.char_indices().filter(|(_, c)| *c == ':' || *c == '-') {
+			let rest = &row[i + 1..];
+			let digits = rest.chars().take_while(|c| c.is_ascii_digit()).count();
+			if digits == 0 || !rest[digits..].starts_with(sep) {
+				continue;
+			}
+
+			return Ok(GrepLine {
+				path : row[..i].to_string(),
+				line : parse_number(&rest[..digits])?,
+				matched : sep == ':',
+				text : rest[digits + 1..].to_string()
+			});
+		}
+
+		Err(YaftpError::ArgumentError)
+	}
+}
+
+impl fmt::Display for GrepLine {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		let sep = if self.matched { ':' } else { '-' };
+		write!(f, "{}{}{}{}{}", self.path , sep , self.line , sep , self.text)
+	}
+}
+
+/* a quota covering a path , a limit of None is unlimited */
+#[derive(Clone, Debug, PartialEq)]
+pub struct QuotaUsage {
+	pub kind : String,
+	pub path : String,
+	pub bytes : u64,
+	pub max_bytes : Option<u64>,
+	pub files : u64,
+	pub max_files : Option<u64>
+}
+
+impl QuotaUsage {
+
+	/* a row of quota , kind path bytes max_bytes files and max_files split by | , a limit of - is unlimited */
+	pub fn parse(row : &str) -> Result<QuotaUsage , YaftpError> {
+		let (kind , rest) = row.split_once('|').ok_or(YaftpError::ArgumentError)?;
+
+		let mut col : Vec<&str> = rest.rsplitn(5, '|').collect();
+		if col.len() != 5 {
+			return Err(YaftpError::ArgumentError);
+		}
+		col.reverse();
+
+		Ok(QuotaUsage {
+			kind : kind.to_string(),
+			path : col[0].to_string(),
+			bytes : parse_number(col[1])?,
+			max_bytes : parse_limit(col[2])?,
+			files : parse_number(col[3])?,
+			max_files : parse_limit(col[4])?
+		})
+	}
+}
+
+/* a session the server runs , what is not known yet is None or empty */
+#[derive(Clone, Debug, PartialEq)]
+pub struct SessionInfo {
+	pub id : u64,
+	pub peer : Option<String>,
+	pub user : Option<String>,
+	pub command : Option<String>,
+	pub paths : Vec<String>,
+	pub bytes : u64,
+	pub total : u64,
+	pub seconds : u64
+}
+
+impl SessionInfo {
+
+	/*
+		a row of sessions , id peer user command paths bytes total and seconds
+		split by | , paths are joined by , and may hold a | . - is unknown .
+	*/
+	pub fn parse(row : &str) -> Result<SessionInfo , YaftpError> {
+		let head : Vec<&str> = row.splitn(5, '|').collect();
+		if head.len() != 5 {
+			return Err(YaftpError::ArgumentError);
+		}
+
+		let mut tail : Vec<&str> = head[4].rsplitn(4, '|').collect();
+		if tail.len() != 4 {
+			return Err(YaftpError::ArgumentError);
+		}
+		tail.reverse();
+
+		let known = |p : &str| if p == "-" { None } else { Some(p.to_string()) };
+
+		Ok(SessionInfo {
+			id : parse_number(head[0])?,
+			peer : known(head[1]),
+			user : known(head[2]),
+			command : known(head[3]),
+			paths : match tail[0] {
+				"-" => vec![],
+				p => p.split(',').map(String::from).collect(),
+			},
+			bytes : parse_number(tail[1])?,
+			total : parse_number(tail[2])?,
+			seconds : parse_number(tail[3])?
+		})
+	}
+}
+
+/* an entry of an archive sent by put -r , status is ok , rejected , quota or error */
+#[derive(Clone, Debug, PartialEq)]
+pub struct UntarEntry {
+	pub status : String,
+	pub path : String
+}
+
+impl UntarEntry {
+
+	/* a row of untar , status and path split by | */
+	pub fn parse(row : &str) -> Result<UntarEntry , YaftpError> {
+		match row.split_once('|') {
+			Some((status , path)) => Ok(UntarEntry { status : status.to_string() , path : path.to_string() }),
+			None => Err(YaftpError::ArgumentError),
+		}
+	}
+
+	pub fn is_ok(&self) -> bool {
+		self.status == ENTRY_OK
+	}
+}
+
+/* seconds since the epoch as info sends them */
+pub fn from_timestamp(secs : u64) -> SystemTime {
+	UNIX_EPOCH + Duration::from_secs(secs)
+}
+
+fn parse_number(text : &str) -> Result<u64 , YaftpError> {
+	text.parse::<u64>().map_err(|_| YaftpError::ArgumentError)
+}
+
+/* a limit of - is unlimited */
+fn parse_limit(text : &str) -> Result<Option<u64> , YaftpError> {
+	match text {
+		"-" => Ok(None),
+		p => parse_number(p).map(Some),
+	}
+}
+
+fn parse_time(text : &str) -> Result<SystemTime , YaftpError> {
+	match NaiveDateTime::parse_from_str(text, "%Y-%m-%d %H:%M:%S") {
+		Ok(p) => Ok(p.and_utc().into()),
+		Err(_) => Err(YaftpError::ArgumentError),
+	}
+}
+
+#[test]
+fn test_file_info() {
+	let entry = DirEntry::parse("a|b.txt|file|42|1970-01-01 00:01:00|1970-01-01 00:00:01").unwrap();
+	assert_eq!(entry.name , "a|b.txt");
+	assert_eq!(entry.kind , FileKind::File);
+	assert_eq!(entry.size , 42);
+	assert_eq!(entry.modified , from_timestamp(60));
+	assert_eq!(entry.accessed , from_timestamp(1));
+
+	assert_eq!(FileKind::from_code(0) , FileKind::Folder);
+	assert_eq!(FileKind::from_code(7) , FileKind::Other);
+	assert_eq!(FileKind::from_name("symlink").to_string() , "symlink");
+	assert!(DirEntry::parse("c|folder|x|1970-01-01 00:00:00|1970-01-01 00:00:00").is_err());
+	assert!(DirEntry::parse("c|folder|1970-01-01 00:00:00").is_err());
+
+	let entry = FindEntry::parse("symlink|5|1970-01-01 00:01:00|/a|b.log").unwrap();
+	assert_eq!(entry.kind , FileKind::Symlink);
+	assert_eq!(entry.size , 5);
+	assert_eq!(entry.modified , from_timestamp(60));
+	assert_eq!(entry.path , "/a|b.log");
+	assert!(FindEntry::parse("file|5|1970-01-01 00:01:00").is_err());
+
+	let line = GrepLine::parse("/var/log/a-b.log:12:error: 3-4-5").unwrap();
+	assert_eq!((line.path.as_str() , line.line , line.matched , line.text.as_str()) , ("/var/log/a-b.log" , 12 , true , "error: 3-4-5"));
+	assert_eq!(line.to_string() , "/var/log/a-b.log:12:error: 3-4-5");
+	let line = GrepLine::parse("a.log-3-").unwrap();
+	assert_eq!((line.line , line.matched , line.text.as_str()) , (3 , false , ""));
+	assert!(GrepLine::parse("a.log:x:y").is_err());
+
+	let quota = QuotaUsage::parse("user|/a|b|100|-|2|3").unwrap();
+	assert_eq!(quota , QuotaUsage { kind : String::from("user") , path : String::from("/a|b") , bytes : 100 , max_bytes : None , files : 2 , max_files : Some(3) });
+	assert!(QuotaUsage::parse("user|/a|x|-|2|3").is_err());
+
+	let session = SessionInfo::parse("7|127.0.0.1:4000|alice|cp|/a|b,/c|10|20|3").unwrap();
+	assert_eq!(session.id , 7);
+	assert_eq!(session.user.as_deref() , Some("alice"));
+	assert_eq!(session.paths , vec!["/a|b" , "/c"]);
+	assert_eq!((session.bytes , session.total , session.seconds) , (10 , 20 , 3));
+	let session = SessionInfo::parse("8|-|-|-|-|0|0|0").unwrap();
+	assert!(session.peer.is_none() && session.command.is_none() && session.paths.is_empty());
+	assert!(SessionInfo::parse("8|-|-|-|0|0").is_err());
+
+	assert!(UntarEntry::parse("ok|a/b").unwrap().is_ok());
+	assert!(!UntarEntry::parse("rejected|../x").unwrap().is_ok());
+}
@@ -0,0 +1,409 @@
+use std::{collections::BTreeMap, fs, net::SocketAddr, path::{Path, PathBuf}, sync::RwLock, time::Duration};
+
+use log::LevelFilter;
+use toml::{Spanned, Value};
+
+use yaftp::{access::{Cidr, DEFAULT_BAN_TIME, DEFAULT_FAILURE_WINDOW, DEFAULT_MAX_SESSIONS_PER_IP}, server::Timeouts, utils::parse_size};
+
+/* a key of a config section , the command line flag it stands for and the check of its value */
+struct Key {
+	name : &'static str,
+	flag : &'static str,
+	/* a relative path value is taken from the folder of the config file */
+	path : bool,
+	check : fn(&str) -> Result<() , String>
+}
+
+/* sections of the config file , the modes reading them are listed in the readme */
+const SECTIONS : [(&str , &[Key]) ; 4] = [
+	("server" , &[
+		Key { name : "listen" , flag : "-l" , path : false , check : |p| parse_listen(p).map(drop) },
+		Key { name : "root" , flag : "--root" , path : true , check : check_folder },
+		Key { name : "accounts" , flag : "-u" , path : true , check : check_file },
+		Key { name : "allow" , flag : "-A" , path : false , check : |p| Cidr::parse_list(p).map(drop) },
+		Key { name : "deny" , flag : "-D" , path : false , check : |p| Cidr::parse_list(p).map(drop) },
+		Key { name : "ban" , flag : "-B" , path : false , check : |p| parse_ban(p).map(drop) },
+		Key { name : "sessions" , flag : "-S" , path : false , check : |p| parse_sessions(p).map(drop) },
+		Key { name : "timeouts" , flag : "-T" , path : false , check : |p| parse_timeouts(p).map(drop) },
+		Key { name : "limit" , flag : "--limit" , path : false , check : |p| parse_limit(p).map(drop) },
+		Key { name : "session-limit" , flag : "--session-limit" , path : false , check : |p| parse_limit(p).map(drop) },
+		Key { name : "audit" , flag : "--audit" , path : true , check : check_not_empty },
+		Key { name : "audit-rotate" , flag : "--audit-rotate" , path : false , check : |p| parse_rotate(p).map(drop) },
+		Key { name : "metrics" , flag : "--metrics" , path : false , check : check_addr },
+		Key { name : "admin-socket" , flag : "--admin-socket" , path : true , check : check_not_empty },
+		Key { name : "grace" , flag : "--grace" , path : false , check : |p| parse_grace(p).map(drop) },
+	]),
+	("reverse" , &[
+		Key { name : "master" , flag : "--master" , path : false , check : |p| split_host_port(p).map(drop) },
+		Key { name : "listen" , flag : "-t" , path : false , check : |p| parse_listen(p).map(drop) },
+		Key { name : "secret" , flag : "--secret" , path : false , check : check_not_empty },
+	]),
+	("client" , &[
+		Key { name : "server" , flag : "--server" , path : false , check : |p| split_host_port(p).map(drop) },
+		Key { name : "user" , flag : "-u" , path : false , check : check_not_empty },
+		Key { name : "password" , flag : "--password" , path : false , check : |_| Ok(()) },
+		Key { name : "limit" , flag : "--limit" , path : false , check : |p| parse_limit(p).map(drop) },
+	]),
+	("log" , &[
+		Key { name : "level" , flag : "--log-level" , path : false , check : |p| parse_level(p).map(drop) },
+	]),
+];
+
+/* a problem of the config file , line 0 when it is not about a line */
+#[derive(Debug)]
+pub struct Problem {
+	pub line : usize,
+	pub message : String
+}
+
+/* values of the sections a mode reads , by the flag they stand for */
+pub struct Config {
+	pub path : PathBuf,
+	values : BTreeMap<&'static str , String>
+}
+
+/* folder relative paths of a config file are taken from */
+fn folder_of(path : &Path) -> PathBuf {
+	match path.parent() {
+		Some(p) => p.to_path_buf(),
+		None => PathBuf::new(),
+	}
+}
+
+type Section = BTreeMap<Spanned<String> , Spanned<Value>>;
+
+/* line of a byte offset , from 1 */
+fn line_of(text : &str , offset : usize) -> usize {
+	text[..offset.min(text.len())].matches('\n').count() + 1
+}
+
+/* strings and numbers as on the command line , a list is joined by commas */
+fn flatten(value : &Value) -> Option<String> {
+	match value {
+		Value::String(p) => Some(p.clone()),
+		Value::Integer(p) => Some(p.to_string()),
+		Value::Array(p) => {
+			let items : Option<Vec<String>> = p.iter().map(|p| match p {
+				Value::String(p) => Some(p.clone()),
+				Value::Integer(p) => Some(p.to_string()),
+				_ => None,
+			}).collect();
+			items.map(|p| p.join(","))
+		},
+		_ => None,
+	}
+}
+
+/*
+	parse text , the values of sections are checked and kept when the
+	section is one of wanted . every problem is returned , with its line .
+*/
+fn parse(text : &str , base : &Path , wanted : &[&str]) -> Result<BTreeMap<&'static str , String> , Vec<Problem>> {
+	let file : BTreeMap<Spanned<String> , Spanned<Section>> = match toml::from_str(text) {
+		Ok(p) => p,
+		Err(e) => {
+			let line = match e.span() {
+				Some(p) => line_of(text, p.start),
+				None => 0,
+			};
+			return Err(vec![Problem { line , message : e.message().trim().replace('\n', " , ") }]);
+		},
+	};
+
+	let mut values = BTreeMap::new();
+	let mut problems = vec![];
+
+	for (name , section) in &file {
+		let line = line_of(text, name.span().start);
+
+		if name.get_ref() == "tls" {
+			problems.push(Problem { line , message : String::from("tls is not supported , put yaftp behind a tls tunnel") });
+			continue;
+		}
+
+		let keys = match SECTIONS.iter().find(|p| p.0 == name.get_ref()) {
+			Some(p) => p.1,
+			None => {
+				problems.push(Problem { line , message : format!("unknown section [{}]" , name.get_ref()) });
+				continue;
+			},
+		};
+
+		for (key , value) in section.get_ref() {
+			let line = line_of(text, key.span().start);
+
+			let key = match keys.iter().find(|p| p.name == key.get_ref()) {
+				Some(p) => p,
+				None => {
+					let message = if key.get_ref() == "tls" {
+						String::from("tls is not supported , put yaftp behind a tls tunnel")
+					} else {
+						format!("unknown key {} in [{}]" , key.get_ref() , name.get_ref())
+					};
+					problems.push(Problem { line , message });
+					continue;
+				},
+			};
+
+			let value = match flatten(value.get_ref()) {
+				Some(p) if key.path && !p.is_empty() => base.join(p).to_string_lossy().to_string(),
+				Some(p) => p,
+				None => {
+					problems.push(Problem { line , message : format!("{} must be a string , a number or a list of them" , key.name) });
+					continue;
+				},
+			};
+
+			if let Err(e) = (key.check)(&value) {
+				problems.push(Problem { line , message : format!("{} : {}" , key.name , e) });
+				continue;
+			}
+
+			if wanted.contains(&name.get_ref().as_str()) {
+				values.insert(key.flag, value);
+			}
+		}
+	}
+
+	problems.sort_by_key(|p| p.line);
+
+	if problems.is_empty() {
+		Ok(values)
+	} else {
+		Err(problems)
+	}
+}
+
+impl Config {
+
+	/* read path , only the values of the wanted sections are kept */
+	pub fn load(path : &Path , wanted : &[&str]) -> Result<Config , String> {
+		let text = match fs::read_to_string(path) {
+			Ok(p) => p,
+			Err(e) => return Err(format!("read {} faild : {}" , path.display() , e)),
+		};
+
+		/* signals reload it after the working folder may have changed */
+		let path = match fs::canonicalize(path) {
+			Ok(p) => p,
+			Err(e) => return Err(format!("read {} faild : {}" , path.display() , e)),
+		};
+
+		match parse(&text, &folder_of(&path), wanted) {
+			Ok(values) => Ok(Config { path , values }),
+			Err(problems) => Err(problems.iter().map(|p| format!("{}:{} : {}" , path.display() , p.line , p.message)).collect::<Vec<String>>().join("\n")),
+		}
+	}
+
+	/* every problem of path , empty when it is valid */
+	pub fn check(path : &Path) -> Vec<Problem> {
+		let text = match fs::read_to_string(path) {
+			Ok(p) => p,
+			Err(e) => return vec![Problem { line : 0 , message : format!("read {} faild : {}" , path.display() , e) }],
+		};
+
+		match parse(&text, &folder_of(path), &[]) {
+			Ok(_) => vec![],
+			Err(p) => p,
+		}
+	}
+
+	pub fn get(&self , flag : &str) -> Option<&String> {
+		self.values.get(flag)
+	}
+}
+
+/* the loaded config , looked up after the command line */
+static CONFIG : RwLock<Option<Config>> = RwLock::new(None);
+
+/* the config replaced , if any */
+pub fn set_config(config : Config) -> Option<Config> {
+	CONFIG.write().unwrap().replace(config)
+}
+
+/* config value standing for flag */
+pub fn value(flag : &str) -> Option<String> {
+	match CONFIG.read().unwrap().as_ref() {
+		Some(p) => p.values.get(flag).cloned(),
+		None => None,
+	}
+}
+
+pub fn path() -> Option<PathBuf> {
+	CONFIG.read().unwrap().as_ref().map(|p| p.path.clone())
+}
+
+/* every value , to tell what a reload changed */
+pub fn values() -> BTreeMap<&'static str , String> {
+	match CONFIG.read().unwrap().as_ref() {
+		Some(p) => p.values.clone(),
+		None => BTreeMap::new(),
+	}
+}
+
+/* config key standing for flag , for messages */
+pub fn key_name(flag : &str) -> &str {
+	for (_ , keys) in &SECTIONS {
+		if let Some(p) = keys.iter().find(|p| p.flag == flag) {
+			return p.name;
+		}
+	}
+	flag
+}
+
+fn check_folder(text : &str) -> Result<() , String> {
+	if Path::new(text).is_dir() { Ok(()) } else { Err(format!("{} is not a folder" , text)) }
+}
+
+fn check_file(text : &str) -> Result<() , String> {
+	if Path::new(text).is_file() { Ok(()) } else { Err(format!("{} is not a file" , text)) }
+}
+
+fn check_not_empty(text : &str) -> Result<() , String> {
+	if text.is_empty() { Err(String::from("empty value")) } else { Ok(()) }
+}
+
+fn check_addr(text : &str) -> Result<() , String> {
+	match text.parse::<SocketAddr>() {
+		Ok(_) => Ok(()),
+		Err(_) => Err(format!("invalid address : {}" , text)),
+	}
+}
+
+/* comma separated ip:port , a bare port listens on every ipv4 address */
+pub fn parse_listen(text : &str) -> Result<Vec<String> , String> {
+	let mut addrs = vec![];
+	for p in text.split(',').map(|p| p.trim()).filter(|p| !p.is_empty()) {
+		if p.parse::<u16>().is_ok() {
+			addrs.push(format!("0.0.0.0:{}" , p));
+			continue;
+		}
+		check_addr(p)?;
+		addrs.push(p.to_string());
+	}
+
+	if addrs.is_empty() {
+		return Err(String::from("no listen address"));
+	}
+	Ok(addrs)
+}
+
+/* host:port , the host may be a name */
+pub fn split_host_port(text : &str) -> Result<(String , String) , String> {
+	match text.rsplit_once(':') {
+		Some((h , p)) if !h.is_empty() && p.parse::<u16>().is_ok() => Ok((h.trim_matches(|c| c == '[' || c == ']').to_string() , p.to_string())),
+		_ => Err(format!("invalid address : {} , eg 192.168.0.1:9000" , text)),
+	}
+}
+
+/* failures , or failures/window/ban in seconds */
+pub fn parse_ban(text : &str) -> Result<(usize , Duration , Duration) , String> {
+	let nums : Vec<u64> = match text.split('/').map(|p| p.parse::<u64>()).collect() {
+		Ok(p) => p,
+		Err(_) => return Err(format!("invalid ban option : {}" , text)),
+	};
+	match nums[..] {
+		[n] => Ok((n as usize , DEFAULT_FAILURE_WINDOW , DEFAULT_BAN_TIME)),
+		[n , w , b] => Ok((n as usize , Duration::from_secs(w) , Duration::from_secs(b))),
+		_ => Err(format!("invalid ban option : {}" , text)),
+	}
+}
+
+/* sessions , or sessions/per ip */
+pub fn parse_sessions(text : &str) -> Result<(usize , usize) , String> {
+	let nums : Vec<usize> = match text.split('/').map(|p| p.parse::<usize>()).collect() {
+		Ok(p) => p,
+		Err(_) => return Err(format!("invalid sessions option : {}" , text)),
+	};
+	match nums[..] {
+		[n] => Ok((n , DEFAULT_MAX_SESSIONS_PER_IP)),
+		[n , i] => Ok((n , i)),
+		_ => Err(format!("invalid sessions option : {}" , text)),
+	}
+}
+
+/* handshake/argument/data in seconds */
+pub fn parse_timeouts(text : &str) -> Result<Timeouts , String> {
+	let nums : Vec<u64> = match text.split('/').map(|p| p.parse::<u64>()).collect() {
+		Ok(p) => p,
+		Err(_) => return Err(format!("invalid timeouts option : {}" , text)),
+	};
+	match nums[..] {
+		[h , a , d] => Ok(Timeouts {
+			handshake : Duration::from_secs(h),
+			argument : Duration::from_secs(a),
+			data : Duration::from_secs(d)
+		}),
+		_ => Err(format!("invalid timeouts option : {}" , text)),
+	}
+}
+
+/* rate of both directions , or upload/download , in bytes per second */
+pub fn parse_limit(text : &str) -> Result<[u64 ; 2] , String> {
+	let rates : Vec<u64> = match text.split('/').map(parse_size).collect() {
+		Some(p) => p,
+		None => return Err(format!("invalid limit option : {}" , text)),
+	};
+	match rates[..] {
+		[n] => Ok([n , n]),
+		[u , d] => Ok([u , d]),
+		_ => Err(format!("invalid limit option : {}" , text)),
+	}
+}
+
+/* size , or size/keep , of the audit file */
+pub fn parse_rotate(text : &str) -> Result<(u64 , Option<usize>) , String> {
+	match text.split_once('/') {
+		Some((s , k)) => match (parse_size(s) , k.parse::<usize>()) {
+			(Some(s) , Ok(k)) => Ok((s , Some(k))),
+			_ => Err(format!("invalid audit rotate option : {}" , text)),
+		},
+		None => match parse_size(text) {
+			Some(s) => Ok((s , None)),
+			None => Err(format!("invalid audit rotate option : {}" , text)),
+		},
+	}
+}
+
+pub fn parse_grace(text : &str) -> Result<Duration , String> {
+	match text.parse::<u64>() {
+		Ok(p) => Ok(Duration::from_secs(p)),
+		Err(_) => Err(format!("invalid grace option : {}" , text)),
+	}
+}
+
+pub fn parse_level(text : &str) -> Result<LevelFilter , String> {
+	match text.parse::<LevelFilter>() {
+		Ok(p) => Ok(p),
+		Err(_) => Err(format!("invalid log level : {} , use off , error , warn , info , debug or trace" , text)),
+	}
+}
+
+#[test]
+fn test_config() {
+	let text = "[server]\nlisten = [\"127.0.0.1:8000\" , 8001]\nlimit = \"1m/512k\"\ngrace = 5\n\n[client]\nuser = \"alice\"\n\n[log]\nlevel = \"debug\"\n";
+	let values = parse(text, Path::new("/etc"), &["server" , "log"]).unwrap();
+	assert_eq!(values["-l"] , "127.0.0.1:8000,8001");
+	assert_eq!(parse_listen(&values["-l"]).unwrap() , ["127.0.0.1:8000" , "0.0.0.0:8001"]);
+	assert_eq!(values["--grace"] , "5");
+	assert_eq!(values["--log-level"] , "debug");
+	assert!(!values.contains_key("-u"));
+
+	let values = parse("[server]\naudit = \"audit.log\"\n", Path::new("/etc/yaftp"), &["server"]).unwrap();
+	assert_eq!(values["--audit"] , "/etc/yaftp/audit.log");
+
+	let problems = parse("[server]\nlimit = \"fast\"\nport = 1\n\n[tls]\ncert = \"a\"\n[log]\nlevel = true\n", Path::new(""), &[]).unwrap_err();
+	let lines : Vec<usize> = problems.iter().map(|p| p.line).collect();
+	assert_eq!(lines , [2 , 3 , 5 , 8]);
+
+	let problems = parse("[server]\nlisten = \n", Path::new(""), &[]).unwrap_err();
+	assert_eq!(problems[0].line , 2);
+
+	assert_eq!(parse_limit("2k").unwrap() , [2048 , 2048]);
+	assert_eq!(parse_ban("3").unwrap().0 , 3);
+	assert_eq!(parse_sessions("10/2").unwrap() , (10 , 2));
+	assert!(parse_timeouts("1/2").is_err());
+	assert_eq!(parse_rotate("10m/3").unwrap() , (10 * 1024 * 1024 , Some(3)));
+	assert_eq!(split_host_port("[::1]:9000").unwrap() , (String::from("::1") , String::from("9000")));
+	assert!(split_host_port("9000").is_err());
+}
@@ -0,0 +1,26 @@
+/*
+	yaftp as a library . a service embeds the client through SpawnClient and
+	Client , whose methods give typed results and YaftpError and never write
+	to stdout , and runs a server with server::serve . the yaftp binary and
+	its shell are one consumer of it .
+*/
+pub mod access;
+pub mod account;
+#[cfg(unix)]
+pub mod admin;
+pub mod archive;
+pub mod audit;
+pub mod client;
+pub mod common;
+pub mod metrics;
+pub mod progress;
+pub mod registry;
+pub mod reverse;
+pub mod server;
+pub mod throttle;
+pub mod utils;
+pub mod watch;
+
+mod lock;
+mod quota;
+mod search;
@@ -0,0 +1,209 @@
+use std::{collections::{HashMap, hash_map::RandomState}, hash::{BuildHasher, Hasher}, path::{Path, PathBuf}, sync::{Mutex, OnceLock, atomic::{AtomicU64, Ordering}}, time::{Duration, Instant}};
+
+use async_std::task;
+
+use crate::common::YaftpError;
+
+pub const DEFAULT_LEASE : Duration = Duration::from_secs(60);
+pub const MAX_LEASE : Duration = Duration::from_secs(3600);
+
+const WAIT_INTERVAL : Duration = Duration::from_millis(200);
+
+/*
+	a lock is held by an in-flight writer until it finishes (no expire) or
+	by a client through the lock command until its lease ends .
+*/
+struct LockEntry {
+	token : u64,
+	expires : Option<Instant>
+}
+
+impl LockEntry {
+	fn alive(&self) -> bool {
+		match self.expires {
+			Some(p) => p > Instant::now(),
+			None => true,
+		}
+	}
+}
+
+fn table() -> &'static Mutex<HashMap<PathBuf , LockEntry>> {
+	static LOCKS : OnceLock<Mutex<HashMap<PathBuf , LockEntry>>> = OnceLock::new();
+	LOCKS.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+/*
+	locks are keyed by the real path so a symlink or a `..` name locks the
+	same file , a file not created yet is keyed in its real folder .
+*/
+fn key(path : &Path) -> PathBuf {
+
+	if let Ok(p) = path.canonicalize() {
+		return p;
+	}
+
+	match (path.parent() , path.file_name()) {
+		(Some(dir) , Some(name)) => match dir.canonicalize() {
+			Ok(p) => p.join(name),
+			Err(_) => path.to_path_buf(),
+		},
+		_ => path.to_path_buf(),
+	}
+}
+
+/* drop the leases which ended , the table does not grow with old locks */
+fn prune(locks : &mut HashMap<PathBuf , LockEntry>) {
+	locks.retain(|_, p| p.alive());
+}
+
+/* tokens are hashed with a random key so a client can not guess another one */
+fn new_token() -> u64 {
+	static COUNTER : AtomicU64 = AtomicU64::new(1);
+	static KEY : OnceLock<RandomState> = OnceLock::new();
+
+	let mut hasher = KEY.get_or_init(RandomState::new).build_hasher();
+	hasher.write_u64(COUNTER.fetch_add(1, Ordering::Relaxed));
+	match hasher.finish() {
+		0 => 1,
+		p => p,
+	}
+}
+
+/* release the in-flight writer lock when the write ends */
+pub struct WriteGuard {
+	path : PathBuf,
+	token : u64
+}
+
+impl Drop for WriteGuard {
+	fn drop(&mut self) {
+		let mut locks = table().lock().unwrap();
+		if let Some(p) = locks.get(&self.path) {
+			if p.token == self.token {
+				locks.remove(&self.path);
+			}
+		}
+	}
+}
+
+/*
+	take the writer lock of path . a client holding the lock token writes
+	without a new guard , anyone else gets Locked while the lock is alive .
+*/
+pub fn try_write(path : &Path , token : u64) -> Result<Option<WriteGuard> , YaftpError> {
+
+	let path = key(path);
+	let mut locks = table().lock().unwrap();
+	prune(&mut locks);
+
+	if let Some(p) = locks.get(&path) {
+		if p.alive() {
+			if token != 0 && p.token == token {
+				return Ok(None);
+			}
+			return Err(YaftpError::Locked);
+		}
+	}
+
+	let token = new_token();
+	locks.insert(path.clone(), LockEntry { token , expires : None });
+
+	Ok(Some(WriteGuard { path , token }))
+}
+
+/* same as try_write , with wait it retries until the lock is free */
+pub async fn acquire_write(path : &Path , token : u64 , wait : bool) -> Result<Option<WriteGuard> , YaftpError> {
+	loop {
+		match try_write(path, token) {
+			Err(YaftpError::Locked) if wait => task::sleep(WAIT_INTERVAL).await,
+			other => return other,
+		}
+	}
+}
+
+/*
+	lock path for lease , a token of 0 asks a new lock , the token of the
+	current lock renews its lease . return the token of the lock .
+*/
+pub fn lock(path : &Path , lease : Duration , token : u64) -> Result<u64 , YaftpError> {
+
+	let path = key(path);
+	let mut locks = table().lock().unwrap();
+	prune(&mut locks);
+
+	if let Some(p) = locks.get(&path) {
+		if p.alive() && (token == 0 || p.token != token) {
+			return Err(YaftpError::Locked);
+		}
+	}
+
+	let token = if token == 0 { new_token() } else { token };
+	let lease = std::cmp::min(lease, MAX_LEASE);
+
+	locks.insert(path, LockEntry { token , expires : Some(Instant::now() + lease) });
+
+	Ok(token)
+}
+
+pub fn unlock(path : &Path , token : u64) -> Result<() , YaftpError> {
+
+	let path = key(path);
+	let mut locks = table().lock().unwrap();
+
+	match locks.get(&path) {
+		Some(p) if p.alive() => {
+			if p.token != token {
+				return Err(YaftpError::Locked);
+			}
+		},
+		_ => return Err(YaftpError::NotFound),
+	}
+
+	locks.remove(&path);
+	Ok(())
+}
+
+#[test]
+fn test_lock() {
+	let path = Path::new("/yaftp/test/lock.txt");
+
+	let guard = try_write(path, 0).unwrap();
+	assert!(guard.is_some());
+	assert!(matches!(try_write(path, 0) , Err(YaftpError::Locked)));
+	assert!(matches!(lock(path, DEFAULT_LEASE, 0) , Err(YaftpError::Locked)));
+	drop(guard);
+
+	let token = lock(path, DEFAULT_LEASE, 0).unwrap();
+	assert!(matches!(try_write(path, 0) , Err(YaftpError::Locked)));
+	assert!(try_write(path, token).unwrap().is_none());
+	assert_eq!(lock(path, DEFAULT_LEASE, token).unwrap() , token);
+	assert!(matches!(unlock(path, token + 1) , Err(YaftpError::Locked)));
+	unlock(path, token).unwrap();
+	assert!(matches!(unlock(path, token) , Err(YaftpError::NotFound)));
+
+	let token = lock(path, Duration::from_millis(0), 0).unwrap();
+	assert!(try_write(path, 0).unwrap().is_some());
+	assert!(matches!(unlock(path, token) , Err(YaftpError::NotFound)));
+
+	lock(path, Duration::from_millis(0), 0).unwrap();
+	lock(Path::new("/yaftp/test/other.txt"), Duration::from_millis(0), 0).unwrap();
+	assert!(!table().lock().unwrap().contains_key(path));
+
+	/* another name of the same file shares its lock */
+	let dir = std::env::temp_dir().join(format!("yaftp_lock_{}" , std::process::id()));
+	std::fs::create_dir_all(dir.join("sub")).unwrap();
+	std::fs::write(dir.join("real.txt"), b"").unwrap();
+	crate::utils::make_symlink(Path::new("real.txt"), &dir.join("alias.txt")).unwrap();
+
+	let token = lock(&dir.join("alias.txt"), DEFAULT_LEASE, 0).unwrap();
+	assert!(matches!(try_write(&dir.join("real.txt"), 0) , Err(YaftpError::Locked)));
+	assert!(matches!(try_write(&dir.join("sub/../real.txt"), 0) , Err(YaftpError::Locked)));
+	unlock(&dir.join("real.txt"), token).unwrap();
+
+	let guard = try_write(&dir.join("new.txt"), 0).unwrap();
+	std::fs::write(dir.join("new.txt"), b"").unwrap();
+	assert!(matches!(try_write(&dir.join("new.txt"), 0) , Err(YaftpError::Locked)));
+	drop(guard);
+
+	std::fs::remove_dir_all(&dir).unwrap();
+}
@@ -1,19 +1,418 @@
-use client::SpawnClient;
-use futures::{AsyncReadExt, StreamExt};
+use futures::{AsyncReadExt, future::{self, Either}};
 use async_std::{io, net::{TcpListener, TcpStream}, task};
 use log::LevelFilter;
+use yaftp::client::SpawnClient;
+use yaftp::access::{Access, Cidr, DEFAULT_MAX_FAILURES};
+use yaftp::account::Accounts;
+use yaftp::common::{YaftpError, error_retcode};
+use yaftp::registry::{DEFAULT_SHUTDOWN_GRACE, Registry};
+use yaftp::reverse::{self, Secret};
+use yaftp::server::{self, DEFAULT_TIMEOUTS, SharedAccounts};
+use yaftp::{access, account, audit, metrics, throttle};
+#[cfg(unix)]
+use yaftp::admin;
+use config::Config;
+use std::{io::IsTerminal, path::{Path, PathBuf}, sync::{Arc, OnceLock, RwLock}, time::Duration};
 use simple_logger::SimpleLogger;
 
-mod server;
-mod client;
-mod common;
 mod cmd;
-mod utils;
+mod signal;
+mod config;
+mod oneshot;
+
+/* config values only read at start , a reload warns when they change */
+const RESTART_FLAGS : [&str ; 8] = ["-l" , "-t" , "--master" , "--secret" , "--root" , "--metrics" , "--admin-socket" , "--grace"];
 
 fn usage() {
 	println!("yaftp - Yet Another File Transfer Protocol");
 	println!("https://github.com/b23r0/yaftp");
 	println!("Usage: yaftp [-l yaftp port] [-c ip port] [-t reverse port] [-r [master ip] [master port]]");
+	println!("             [-l ip:port,ip:port] : listen to these addresses instead of every ipv4 address");
+	println!("             [--config file] : read options from a toml file , options given on the command line win");
+	println!("                               , the port and addresses may come from it too . SIGHUP reloads it");
+	println!("             [--check-config file] : check a config file and print its errors with line numbers");
+	println!("             [-u accounts file] with -l or -r : only users of the file can login");
+	println!("             [-u user] with -c or -t : login as user , password from YAFTP_PASSWORD or prompt");
+	println!("             [-t|-r] secret from YAFTP_SECRET or the config : master and slave prove they share it");
+	println!("             [-p] hash a password (YAFTP_PASSWORD or prompt) for the accounts file");
+	println!("             [--root folder] with -l or -r : folder served to clients without accounts");
+	println!("             [-A cidr,cidr] with -l : only accept these addresses , [-D cidr,cidr] : refuse these addresses");
+	println!("             [-B failures/window/ban] with -l : ban an address failing handshake or login too often");
+	println!("                                      in window seconds for ban seconds (default 5/60/600 , 0 disable)");
+	println!("             [-S sessions/per ip] with -l : sessions served at once (default 1024/64 , 0 unlimited)");
+	println!("             [-T handshake/argument/data] with -l or -r : seconds to wait for the client (default 10/30/60 , 0 forever)");
+	println!("             [--limit upload/download] : bytes per second of all transfers , eg 512k/2m (default 0 unlimited)");
+	println!("             [--session-limit upload/download] with -l or -r : bytes per second of each session");
+	println!("             [--audit file] with -l or -r : append a json line per command to file");
+	println!("             [--audit-rotate size/keep] : rotate the audit file past size keeping keep old files (default 100m/5 , 0 never)");
+	println!("             [--metrics ip:port] with -l or -r : serve prometheus metrics at http://ip:port/metrics");
+	println!("             [--admin-socket path] with -l or -r : local socket to list and kill sessions or shut down");
+	println!("             [--grace seconds] with -l or -r : on SIGTERM/SIGINT or shutdown , wait for running sessions");
+	println!("                                          that long before killing them (default 30)");
+	println!("             [--log-level level] : off , error , warn , info (default) , debug or trace");
+	println!("             [-b script] with -c or -t : run the shell commands of script and exit , like a pipe to stdin");
+	println!("       yaftp get host:port:remote path local path , put local path host:port:remote path");
+	println!("             ls|rm|hash host:port:remote path : run one command and exit with its retcode");
+	println!("             [-u user] [--limit upload/download] [--config file] as with -c");
+}
+
+/* value following flag on the command line , looked up from the nth argument */
+fn find_arg(flag : &str , from : usize) -> Option<String> {
+	let mut args = std::env::args().skip(from);
+	while let Some(p) = args.next() {
+		if p == flag {
+			return args.next();
+		}
+	}
+	None
+}
+
+/* value of flag , from the command line or else the config file */
+fn find_option(flag : &str , from : usize) -> Option<String> {
+	match find_arg(flag, from) {
+		Some(p) => Some(p),
+		None => config::value(flag),
+	}
+}
+
+/* working folder yaftp started in , --root changes it */
+static START_DIR : OnceLock<PathBuf> = OnceLock::new();
+
+/* path option , a relative path is taken from the folder yaftp started in */
+fn find_path(flag : &str , from : usize) -> Option<String> {
+	let path = find_option(flag, from)?;
+	match START_DIR.get() {
+		Some(p) => Some(p.join(path).to_string_lossy().to_string()),
+		None => Some(path),
+	}
+}
+
+/* nth argument when it is not an option , options start with - */
+fn positional(n : usize) -> Option<String> {
+	match std::env::args().nth(n) {
+		Some(p) if !p.starts_with('-') => Some(p),
+		_ => None,
+	}
+}
+
+/* read the --config file , keeping the sections the mode uses */
+fn load_config(from : usize , sections : &[&str]) -> Result<() , String> {
+	let path = match find_arg("--config", from){
+		Some(p) => p,
+		None => return Ok(()),
+	};
+
+	let config = Config::load(Path::new(&path), sections)?;
+
+	#[cfg(unix)]
+	if config.get("--password").is_some() || config.get("--secret").is_some() {
+		use std::os::unix::fs::PermissionsExt;
+		if let Ok(p) = std::fs::metadata(&path) {
+			if p.permissions().mode() & 0o077 != 0 {
+				log::warn!("{} holds a password or secret but other users can read it" , path);
+			}
+		}
+	}
+
+	let _ = config::set_config(config);
+	log::info!("load config from {}" , path);
+	Ok(())
+}
+
+fn load_accounts(from : usize) -> Result<Option<Arc<Accounts>> , String> {
+	let path = match find_path("-u", from){
+		Some(p) => p,
+		None => return Ok(None),
+	};
+
+	let accounts = Accounts::load(Path::new(&path))?;
+	log::info!("load {} accounts from {}" , accounts.len() , path);
+	Ok(Some(Arc::new(accounts)))
+}
+
+fn load_access(from : usize) -> Result<Access , String> {
+	let allow = match find_option("-A", from){
+		Some(p) => Cidr::parse_list(&p)?,
+		None => vec![],
+	};
+
+	let deny = match find_option("-D", from){
+		Some(p) => Cidr::parse_list(&p)?,
+		None => vec![],
+	};
+
+	let (max_failures , window , ban_time) = match find_option("-B", from){
+		Some(p) => config::parse_ban(&p)?,
+		None => (DEFAULT_MAX_FAILURES , access::DEFAULT_FAILURE_WINDOW , access::DEFAULT_BAN_TIME),
+	};
+
+	let mut access = Access::new(allow, deny, max_failures, window, ban_time);
+
+	if let Some(p) = find_option("-S", from) {
+		let (sessions , per_ip) = config::parse_sessions(&p)?;
+		access.set_limits(sessions, per_ip);
+	}
+
+	Ok(access)
+}
+
+fn load_timeouts(from : usize) -> Result<() , String> {
+	let timeouts = match find_option("-T", from){
+		Some(p) => config::parse_timeouts(&p)?,
+		None => DEFAULT_TIMEOUTS,
+	};
+
+	server::set_timeouts(timeouts);
+	Ok(())
+}
+
+/* rate option of both directions , or upload/download , in bytes per second */
+fn find_limit(flag : &str , from : usize) -> Result<[u64 ; 2] , String> {
+	match find_option(flag, from){
+		Some(p) => config::parse_limit(&p),
+		None => Ok([0 , 0]),
+	}
+}
+
+fn load_limits(from : usize) -> Result<() , String> {
+	let global = find_limit("--limit", from)?;
+	let session = find_limit("--session-limit", from)?;
+	throttle::set_server_limits(global, session);
+	Ok(())
+}
+
+fn load_audit(from : usize) -> Result<() , String> {
+	let path = match find_path("--audit", from){
+		Some(p) => p,
+		None => {
+			audit::unset_audit();
+			return Ok(());
+		},
+	};
+
+	let (max_size , keep) = match find_option("--audit-rotate", from){
+		Some(p) => match config::parse_rotate(&p)? {
+			(s , Some(k)) => (s , k),
+			(s , None) => (s , audit::DEFAULT_KEEP),
+		},
+		None => (audit::DEFAULT_MAX_SIZE , audit::DEFAULT_KEEP),
+	};
+
+	audit::set_audit(Path::new(&path), max_size, keep)?;
+	log::info!("audit log to {}" , path);
+	Ok(())
+}
+
+fn load_level(from : usize) -> Result<() , String> {
+	let level = match find_option("--log-level", from){
+		Some(p) => config::parse_level(&p)?,
+		None => LevelFilter::Info,
+	};
+
+	::log::set_max_level(level);
+	Ok(())
+}
+
+/* settings a reload applies to running servers */
+fn load_settings(from : usize) -> Result<() , String> {
+	load_level(from)?;
+	load_timeouts(from)?;
+	load_limits(from)?;
+	load_audit(from)
+}
+
+/* serve --root , relative paths of anonymous sessions start there */
+fn change_root(from : usize) -> Result<() , String> {
+	let root = match find_path("--root", from){
+		Some(p) => p,
+		None => return Ok(()),
+	};
+
+	match std::env::set_current_dir(&root) {
+		Ok(_) => {
+			log::info!("serve {}" , root);
+			Ok(())
+		},
+		Err(e) => Err(format!("change to root {} faild : {}" , root , e)),
+	}
+}
+
+async fn start_metrics(from : usize) -> Result<() , String> {
+	let addr = match find_option("--metrics", from){
+		Some(p) => p,
+		None => return Ok(()),
+	};
+
+	let listener = match TcpListener::bind(&addr).await{
+		Ok(p) => p,
+		Err(e) => return Err(format!("metrics listen to {} faild : {}" , addr , e)),
+	};
+
+	log::info!("metrics at : http://{}/metrics" , addr);
+	task::spawn(metrics::serve(listener));
+	Ok(())
+}
+
+#[cfg(unix)]
+async fn start_admin(from : usize , registry : &Arc<Registry>) -> Result<() , String> {
+	let path = match find_path("--admin-socket", from){
+		Some(p) => p,
+		None => return Ok(()),
+	};
+
+	let listener = admin::bind(Path::new(&path)).await?;
+	log::info!("admin socket at : {}" , path);
+	task::spawn(admin::serve(listener, registry.clone()));
+	Ok(())
+}
+
+#[cfg(not(unix))]
+async fn start_admin(from : usize , _registry : &Arc<Registry>) -> Result<() , String> {
+	match find_option("--admin-socket", from){
+		Some(_) => Err(String::from("admin socket is only supported on unix")),
+		None => Ok(()),
+	}
+}
+
+fn load_grace(from : usize) -> Result<Duration , String> {
+	match find_option("--grace", from){
+		Some(p) => config::parse_grace(&p),
+		None => Ok(DEFAULT_SHUTDOWN_GRACE),
+	}
+}
+
+/*
+	read the config file again and apply what running sessions do not
+	notice . nothing changes when the file or a value is wrong .
+*/
+fn reload(from : usize , sections : &[&str] , accounts : &SharedAccounts , access : Option<&Arc<Access>>) {
+	let path = match config::path() {
+		Some(p) => p,
+		None => return,
+	};
+
+	let config = match Config::load(&path, sections) {
+		Ok(p) => p,
+		Err(e) => {
+			log::error!("reload faild , keep the running config : {}" , e);
+			return;
+		},
+	};
+
+	let old = config::values();
+	let previous = config::set_config(config);
+
+	let loaded = match (load_accounts(from) , access.map(|_| load_access(from)).transpose()) {
+		(Ok(accounts) , Ok(access)) => Ok((accounts , access)),
+		(Err(e) , _) | (_ , Err(e)) => Err(e),
+	};
+
+	let (new_accounts , new_access) = match loaded {
+		Ok(p) => p,
+		Err(e) => {
+			if let Some(p) = previous {
+				let _ = config::set_config(p);
+			}
+			log::error!("reload faild , keep the running config : {}" , e);
+			return;
+		},
+	};
+
+	*accounts.write().unwrap() = new_accounts;
+	if let (Some(access) , Some(new_access)) = (access , new_access) {
+		access.reload(new_access);
+	}
+
+	if let Err(e) = load_settings(from) {
+		log::error!("reload {} : {}" , path.display() , e);
+	}
+
+	let new = config::values();
+	for flag in RESTART_FLAGS {
+		if old.get(flag) != new.get(flag) && find_arg(flag, from).is_none() {
+			log::warn!("{} changed in {} , restart to apply it" , config::key_name(flag) , path.display());
+		}
+	}
+
+	log::info!("reload config from {}" , path.display());
+}
+
+/* YAFTP_PASSWORD if set , else the config file , else prompt without echo */
+fn read_password(prompt : &str) -> Result<String , String> {
+	if let Ok(p) = std::env::var("YAFTP_PASSWORD") {
+		return Ok(p);
+	}
+	if let Some(p) = config::value("--password") {
+		return Ok(p);
+	}
+	match rpassword::prompt_password(prompt){
+		Ok(p) => Ok(p),
+		Err(e) => Err(e.to_string()),
+	}
+}
+
+/* secret of the reverse mode connections , none when neither sets it */
+fn load_secret() -> Option<Secret> {
+	match std::env::var("YAFTP_SECRET") {
+		Ok(p) if !p.is_empty() => Some(Secret::new(&p)),
+		_ => config::value("--secret").map(|p| Secret::new(&p)),
+	}
+}
+
+fn login_spawner(spawner : &mut SpawnClient , from : usize) -> Result<() , String> {
+	let user = match find_option("-u", from){
+		Some(p) => p,
+		None => return Ok(()),
+	};
+
+	let password = read_password(&format!("password for {} : " , user))?;
+
+	spawner.set_auth(&user, &password);
+	Ok(())
+}
+
+/* commands of the -b script , or of stdin when it is not a terminal */
+fn load_batch(from : usize) -> Result<Option<cmd::Batch> , String> {
+	if let Some(path) = find_arg("-b", from) {
+		return match std::fs::File::open(&path) {
+			Ok(p) => Ok(Some(cmd::Batch::new(Box::new(std::io::BufReader::new(p))))),
+			Err(e) => Err(format!("open script {} faild : {}" , path , e)),
+		};
+	}
+
+	if std::io::stdin().is_terminal() {
+		return Ok(None);
+	}
+	Ok(Some(cmd::Batch::new(Box::new(std::io::stdin().lock()))))
+}
+
+/* client of a one shot command to the server of its remote operand */
+async fn oneshot_client(command : &str , operands : &[String]) -> Result<(SpawnClient , oneshot::Remote) , YaftpError> {
+	let sections = ["client" , "log"];
+	if let Err(e) = load_config(2, &sections).and_then(|_| load_level(2)) {
+		log::error!("{}" , e);
+		return Err(YaftpError::ArgumentError);
+	}
+
+	let remote = oneshot::remote_of(command, operands)?;
+
+	let mut spawner = SpawnClient::new(&remote.host , &remote.port).await;
+	spawner.set_progress(Arc::new(cmd::Bar::default()));
+
+	match find_limit("--limit", 2){
+		Ok(p) => spawner.set_limit(p),
+		Err(e) => {
+			log::error!("{}" , e);
+			return Err(YaftpError::ArgumentError);
+		}
+	};
+
+	if let Err(e) = login_spawner(&mut spawner, 2) {
+		log::error!("read password error : {}" , e);
+		return Err(YaftpError::AuthFaild);
+	}
+
+	Ok((spawner , remote))
 }
 
 #[async_std::main]
@@ -21,6 +420,10 @@ async fn main() -> io::Result<()>  {
     SimpleLogger::new().with_colors(true).init().unwrap();
 	::log::set_max_level(LevelFilter::Info);
 
+	if let Ok(p) = std::env::current_dir() {
+		let _ = START_DIR.set(p);
+	}
+
 	let arg_count = std::env::args().count();
 
 	if  arg_count == 1{
@@ -32,64 +435,193 @@ async fn main() -> io::Result<()>  {
 
 	match first.as_str() {
 		"-l" => {
-            let port = match std::env::args().nth(2){
+			let sections = ["server" , "log"];
+			let (port , from) = match positional(2){
+				Some(p) => (Some(p) , 3),
+				None => (None , 2),
+			};
+			if let Err(e) = load_config(from, &sections) {
+				log::error!("{}" , e);
+				return Ok(());
+			}
+			let port = match port.or_else(|| config::value("-l")){
 				None => {
 					log::error!("not found listen port . eg : yaftp -l 8000");
 					return Ok(());
 				},
 				Some(p) => p
 			};
-			log::info!("listen to : {}" , "0.0.0.0:".to_string() + &port);
-			
-			let listener = match TcpListener::bind("0.0.0.0:".to_string() + &port).await{
+			let addrs = match config::parse_listen(&port){
+				Ok(p) => p,
 				Err(e) => {
-					log::error!("error : {}", e);
+					log::error!("{}" , e);
 					return Ok(());
-				},
-				Ok(p) => p
+				}
+			};
+			if let Err(e) = load_settings(from) {
+				log::error!("{}" , e);
+				return Ok(());
+			}
+			if let Err(e) = change_root(from) {
+				log::error!("{}" , e);
+				return Ok(());
+			}
+			let accounts : SharedAccounts = match load_accounts(from){
+				Ok(p) => Arc::new(RwLock::new(p)),
+				Err(e) => {
+					log::error!("{}" , e);
+					return Ok(());
+				}
+			};
+			let access = match load_access(from){
+				Ok(p) => Arc::new(p),
+				Err(e) => {
+					log::error!("{}" , e);
+					return Ok(());
+				}
+			};
+			if let Err(e) = start_metrics(from).await {
+				log::error!("{}" , e);
+				return Ok(());
+			}
+			let grace = match load_grace(from){
+				Ok(p) => p,
+				Err(e) => {
+					log::error!("{}" , e);
+					return Ok(());
+				}
+			};
+			let registry = Arc::new(Registry::new());
+			if let Err(e) = start_admin(from, &registry).await {
+				log::error!("{}" , e);
+				return Ok(());
+			}
+
+			let mut listeners = vec![];
+			for addr in &addrs {
+				log::info!("listen to : {}" , addr);
+
+				match TcpListener::bind(addr).await{
+					Err(e) => {
+						log::error!("error : {}", e);
+						return Ok(());
+					},
+					Ok(p) => listeners.push(p)
+				};
+			}
+
+			let reloader = {
+				let accounts = accounts.clone();
+				let access = access.clone();
+				move || reload(from, &sections, &accounts, Some(&access))
 			};
+			task::spawn(signal::watch(registry.clone(), config::path().map(|_| Box::new(reloader) as Box<dyn Fn() + Send>)));
 
-			let mut incoming = listener.incoming();
+			let loops : Vec<_> = listeners.into_iter().map(|p| task::spawn(server::serve(p, accounts.clone(), access.clone(), registry.clone()))).collect();
 
-			while let Some(stream) = incoming.next().await {
-				let stream = stream?;
-				task::spawn(async {
-					server::yaftp_server_handle(stream).await;
-				});
+			/* the loops end at shutdown , closing their listeners while the running sessions end */
+			for p in loops {
+				p.await;
 			}
+			registry.drain(grace).await;
+			log::info!("server stopped");
 		},
 		"-c" => {
-			let ip = match std::env::args().nth(2){
-				None => {
-					log::error!("not found listen port . eg : yaftp -c 127.0.0.1 8000");
-					return Ok(());
+			let sections = ["client" , "log"];
+			let (server , from) = match (positional(2) , positional(3)){
+				(Some(ip) , Some(port)) => (Some((ip , port)) , 4),
+				_ => (None , 2),
+			};
+			if let Err(e) = load_config(from, &sections) {
+				log::error!("{}" , e);
+				return Ok(());
+			}
+			if let Err(e) = load_level(from) {
+				log::error!("{}" , e);
+				return Ok(());
+			}
+			let server = match server{
+				Some(p) => p,
+				None => match config::value("--server").map(|p| config::split_host_port(&p)){
+					Some(Ok(p)) => p,
+					_ => {
+						log::error!("not found listen port . eg : yaftp -c 127.0.0.1 8000");
+						return Ok(());
+					},
 				},
-				Some(p) => p
 			};
-			let port = match std::env::args().nth(3){
-				None => {
-					log::error!("not found listen port . eg : yaftp -c 127.0.0.1 8000");
+			let (ip , port) = server;
+			let batch = match load_batch(from){
+				Ok(p) => p,
+				Err(e) => {
+					log::error!("{}" , e);
+					std::process::exit(1);
+				}
+			};
+
+			let mut spawner = SpawnClient::new(&ip , &port).await;
+
+			match find_limit("--limit", from){
+				Ok(p) => spawner.set_limit(p),
+				Err(e) => {
+					log::error!("{}" , e);
 					return Ok(());
-				},
-				Some(p) => p
+				}
 			};
 
-			let spawner = SpawnClient::new(&ip , &port).await;
+			if let Err(e) = login_spawner(&mut spawner, from) {
+				log::error!("read password error : {}" , e);
+				return Ok(());
+			}
 
-			cmd::handle_cmd(spawner).await;
+			if !cmd::handle_cmd(spawner, batch).await {
+				std::process::exit(1);
+			}
 		},
 		"-t" => {
-			let master_port = match std::env::args().nth(2){
+			let sections = ["client" , "reverse" , "log"];
+			let (master_port , from) = match positional(2){
+				Some(p) => (Some(p) , 3),
+				None => (None , 2),
+			};
+			if let Err(e) = load_config(from, &sections) {
+				log::error!("{}" , e);
+				return Ok(());
+			}
+			if let Err(e) = load_level(from) {
+				log::error!("{}" , e);
+				return Ok(());
+			}
+			let master_port = match master_port.or_else(|| config::value("-t")){
 				None => {
 					log::error!("not found listen port . eg : yaftp -t 9000");
 					return Ok(());
 				},
 				Some(p) => p
 			};
+			let addr = match config::parse_listen(&master_port){
+				Ok(p) if p.len() == 1 => p[0].clone(),
+				Ok(_) => {
+					log::error!("listen to one address in reverse mode");
+					return Ok(());
+				},
+				Err(e) => {
+					log::error!("{}" , e);
+					return Ok(());
+				}
+			};
+
+			let batch = match load_batch(from){
+				Ok(p) => p,
+				Err(e) => {
+					log::error!("{}" , e);
+					std::process::exit(1);
+				}
+			};
+
+			log::info!("listen to : {} waiting for slave" , addr);
 
-			log::info!("listen to : {} waiting for slave" , "0.0.0.0:".to_string() + &master_port);
-			
-			let slave_listener = match TcpListener::bind("0.0.0.0:".to_string() + &master_port).await{
+			let slave_listener = match TcpListener::bind(&addr).await{
 				Err(e) => {
 					log::error!("error : {}", e);
 					return Ok(());
@@ -97,36 +629,110 @@ async fn main() -> io::Result<()>  {
 				Ok(p) => p
 			};
 
-			let stream  = match slave_listener.accept().await{
-				Ok(p) => {
-					log::info!("accept from : {}:{}" , p.1.ip() , p.1.port() );
-					p.0
-				},
+			let secret = load_secret();
+
+			let stream = loop {
+				let (mut stream , peer) = match slave_listener.accept().await{
+					Ok(p) => p,
+					Err(e) => {
+						log::error!("accept error : {}" , e);
+						return Ok(());
+					},
+				};
+
+				if let Some(secret) = &secret {
+					if let Err(e) = reverse::prove_master(&mut stream, secret).await {
+						log::warn!("refuse {}:{} : {}" , peer.ip() , peer.port() , e);
+						continue;
+					}
+				}
+
+				log::info!("accept from : {}:{}" , peer.ip() , peer.port() );
+				break stream;
+			};
+
+			let mut spawner = SpawnClient::new_t(stream, slave_listener).await;
+			if let Some(secret) = secret {
+				spawner.set_secret(secret);
+			}
+
+			match find_limit("--limit", from){
+				Ok(p) => spawner.set_limit(p),
 				Err(e) => {
-					log::error!("accept error : {}" , e);
+					log::error!("{}" , e);
 					return Ok(());
-				},
+				}
 			};
 
-			let spawner = SpawnClient::new_t(stream, slave_listener).await;
+			if let Err(e) = login_spawner(&mut spawner, from) {
+				log::error!("read password error : {}" , e);
+				return Ok(());
+			}
 
-			cmd::handle_cmd(spawner).await;
+			if !cmd::handle_cmd(spawner, batch).await {
+				std::process::exit(1);
+			}
 		},
 		"-r" => {
-			let addr = match std::env::args().nth(2){
-				None => {
-					log::error!("not found ip . eg : yaftp -r 192.168.0.1 9000");
-					return Ok(());
+			let sections = ["server" , "reverse" , "log"];
+			let (master , from) = match (positional(2) , positional(3)){
+				(Some(ip) , Some(port)) => (Some((ip , port)) , 4),
+				_ => (None , 2),
+			};
+			if let Err(e) = load_config(from, &sections) {
+				log::error!("{}" , e);
+				return Ok(());
+			}
+			let (addr , port) = match master{
+				Some(p) => p,
+				None => match config::value("--master").map(|p| config::split_host_port(&p)){
+					Some(Ok(p)) => p,
+					_ => {
+						log::error!("not found ip . eg : yaftp -r 192.168.0.1 9000");
+						return Ok(());
+					},
 				},
-				Some(p) => p
 			};
-			let port = match std::env::args().nth(3){
-				None => {
-					log::error!("not found port . eg : yaftp -r 192.168.0.1 9000");
+			if let Err(e) = load_settings(from) {
+				log::error!("{}" , e);
+				return Ok(());
+			}
+			if let Err(e) = change_root(from) {
+				log::error!("{}" , e);
+				return Ok(());
+			}
+			let accounts : SharedAccounts = match load_accounts(from){
+				Ok(p) => Arc::new(RwLock::new(p)),
+				Err(e) => {
+					log::error!("{}" , e);
 					return Ok(());
-				},
-				Some(p) => p
+				}
+			};
+			if let Err(e) = start_metrics(from).await {
+				log::error!("{}" , e);
+				return Ok(());
+			}
+			let grace = match load_grace(from){
+				Ok(p) => p,
+				Err(e) => {
+					log::error!("{}" , e);
+					return Ok(());
+				}
 			};
+			let registry = Arc::new(Registry::new());
+			if let Err(e) = start_admin(from, &registry).await {
+				log::error!("{}" , e);
+				return Ok(());
+			}
+
+			let reloader = {
+				let accounts = accounts.clone();
+				move || reload(from, &sections, &accounts, None)
+			};
+			task::spawn(signal::watch(registry.clone(), config::path().map(|_| Box::new(reloader) as Box<dyn Fn() + Send>)));
+
+			let secret = load_secret().map(Arc::new);
+
 			let fulladdr = format!("{}:{}" , addr , port);
 			let mut master_stream = match TcpStream::connect(fulladdr.clone()).await{
 				Err(e) => {
@@ -135,17 +741,28 @@ async fn main() -> io::Result<()>  {
 				},
 				Ok(p) => p
 			};
+			if let Some(secret) = &secret {
+				if let Err(e) = reverse::prove_slave(&mut master_stream, secret).await {
+					log::error!("master {} : {}" , fulladdr , e);
+					return Ok(());
+				}
+			}
 			log::info!("connect to {} success" ,fulladdr );
 			loop {
 				let mut buf = [0u8 ; 1];
-				match master_stream.read_exact(&mut buf).await{
+				let read = match future::select(Box::pin(master_stream.read_exact(&mut buf)), Box::pin(registry.stopped())).await{
+					Either::Left((p , _)) => p,
+					Either::Right(_) => break,
+				};
+
+				match read{
 					Err(e) => {
 						log::error!("error : {}", e);
 						return Ok(());
 					},
 					Ok(p) => p
 				};
-	
+
 				if buf[0] == 0x55 {
 					let stream = match TcpStream::connect(fulladdr.clone()).await{
 						Err(e) => {
@@ -155,11 +772,77 @@ async fn main() -> io::Result<()>  {
 						Ok(p) => p
 					};
 
-					task::spawn(async {
-						server::yaftp_server_handle(stream).await;
+					metrics::connect_back();
+
+					let accounts = accounts.read().unwrap().clone();
+					let secret = secret.clone();
+					let master = fulladdr.clone();
+					registry.spawn(stream.peer_addr().ok(), move |registry , id| async move {
+						let mut stream = stream;
+						if let Some(secret) = secret {
+							if let Err(e) = reverse::prove_slave(&mut stream, &secret).await {
+								log::warn!("master {} : {}" , master , e);
+								return;
+							}
+						}
+						server::yaftp_server_handle(stream , accounts , None , registry , id).await;
 					});
 				}
 			}
+
+			registry.drain(grace).await;
+			log::info!("server stopped");
+		},
+		"-p" => {
+			let password = match read_password("password : "){
+				Ok(p) => p,
+				Err(e) => {
+					log::error!("read password error : {}" , e);
+					return Ok(());
+				}
+			};
+
+			match account::hash_password(&password){
+				Ok(p) => println!("{}" , p),
+				Err(e) => log::error!("hash password error : {}" , e),
+			};
+		},
+		command if oneshot::COMMANDS.contains(&command) => {
+			let args : Vec<String> = std::env::args().skip(2).collect();
+			let operands = oneshot::operands(&args);
+
+			let ret = match oneshot_client(command, &operands).await {
+				Ok((spawner , remote)) => oneshot::run(&spawner, command, &remote, &operands).await,
+				Err(e) => Err(oneshot::Failure::told(e)),
+			};
+
+			if let Err(failure) = ret {
+				/* the client is quiet , tell the error unless the command told it */
+				if !failure.told {
+					println_err!("{} faild : {}" , command , failure.error);
+				}
+				std::process::exit(error_retcode(failure.error) as i32);
+			}
+		},
+		"--check-config" => {
+			let path = match std::env::args().nth(2){
+				None => {
+					log::error!("not found config file . eg : yaftp --check-config yaftp.toml");
+					std::process::exit(1);
+				},
+				Some(p) => p
+			};
+
+			let problems = Config::check(Path::new(&path));
+			if problems.is_empty() {
+				println!("{} is valid" , path);
+				return Ok(());
+			}
+
+			for p in &problems {
+				println!("{}:{} : {}" , path , p.line , p.message);
+			}
+			std::process::exit(1);
 		},
 		_ => {
 			usage();
@@ -0,0 +1,213 @@
+use std::{collections::BTreeMap, fmt::Write, sync::{Mutex, atomic::{AtomicU64, Ordering}}, time::Duration};
+
+use async_std::{net::{TcpListener, TcpStream}, task};
+use futures::{AsyncReadExt, AsyncWriteExt};
+
+use crate::throttle::{DOWNLOAD, UPLOAD};
+
+/* upper bounds in seconds of the transfer duration buckets */
+const DURATION_BUCKETS : [f64 ; 9] = [0.1 , 0.5 , 1.0 , 5.0 , 10.0 , 30.0 , 60.0 , 300.0 , 1800.0];
+
+/* a scrape request head larger than this or slower than the timeout is dropped */
+const MAX_REQUEST_SIZE : usize = 8192;
+const REQUEST_TIMEOUT : Duration = Duration::from_secs(5);
+
+static SESSIONS_ACTIVE : AtomicU64 = AtomicU64::new(0);
+static SESSIONS_TOTAL : AtomicU64 = AtomicU64::new(0);
+static HANDSHAKE_FAILURES : AtomicU64 = AtomicU64::new(0);
+static CONNECT_BACKS : AtomicU64 = AtomicU64::new(0);
+
+/* data bytes received from and sent to clients , indexed by direction */
+static BYTES : [AtomicU64 ; 2] = [AtomicU64::new(0) , AtomicU64::new(0)];
+
+/* commands by name and retcode */
+static COMMANDS : Mutex<BTreeMap<(&'static str , u8) , u64>> = Mutex::new(BTreeMap::new());
+
+/* transfer durations by command name */
+static DURATIONS : Mutex<BTreeMap<&'static str , Histogram>> = Mutex::new(BTreeMap::new());
+
+#[derive(Default)]
+struct Histogram {
+	buckets : [u64 ; DURATION_BUCKETS.len()],
+	sum : f64,
+	count : u64
+}
+
+/* a session being served , counted active until dropped */
+pub struct Active;
+
+impl Active {
+	pub fn new() -> Active {
+		SESSIONS_ACTIVE.fetch_add(1, Ordering::Relaxed);
+		SESSIONS_TOTAL.fetch_add(1, Ordering::Relaxed);
+		Active
+	}
+}
+
+impl Default for Active {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+impl Drop for Active {
+	fn drop(&mut self) {
+		SESSIONS_ACTIVE.fetch_sub(1, Ordering::Relaxed);
+	}
+}
+
+pub fn handshake_failed() {
+	HANDSHAKE_FAILURES.fetch_add(1, Ordering::Relaxed);
+}
+
+pub fn connect_back() {
+	CONNECT_BACKS.fetch_add(1, Ordering::Relaxed);
+}
+
+/* n data bytes moved in direction , seen from the client */
+pub fn transferred(direction : usize , n : u64) {
+	BYTES[direction].fetch_add(n, Ordering::Relaxed);
+}
+
+pub fn command(name : &'static str , retcode : u8) {
+	*COMMANDS.lock().unwrap().entry((name , retcode)).or_insert(0) += 1;
+}
+
+pub fn transfer_duration(name : &'static str , duration : Duration) {
+	let seconds = duration.as_secs_f64();
+	let mut durations = DURATIONS.lock().unwrap();
+	let histogram = durations.entry(name).or_default();
+
+	for (i , bound) in DURATION_BUCKETS.iter().enumerate() {
+		if seconds <= *bound {
+			histogram.buckets[i] += 1;
+		}
+	}
+	histogram.sum += seconds;
+	histogram.count += 1;
+}
+
+fn header(out : &mut String , name : &str , kind : &str , help : &str) {
+	let _ = writeln!(out , "# HELP {} {}" , name , help);
+	let _ = writeln!(out , "# TYPE {} {}" , name , kind);
+}
+
+/* every metric in the prometheus text format */
+pub fn render() -> String {
+	let mut out = String::new();
+
+	header(&mut out, "yaftp_sessions_active", "gauge", "Sessions being served.");
+	let _ = writeln!(out , "yaftp_sessions_active {}" , SESSIONS_ACTIVE.load(Ordering::Relaxed));
+
+	header(&mut out, "yaftp_sessions_total", "counter", "Sessions served since start.");
+	let _ = writeln!(out , "yaftp_sessions_total {}" , SESSIONS_TOTAL.load(Ordering::Relaxed));
+
+	header(&mut out, "yaftp_handshake_failures_total", "counter", "Connections closed for a bad handshake.");
+	let _ = writeln!(out , "yaftp_handshake_failures_total {}" , HANDSHAKE_FAILURES.load(Ordering::Relaxed));
+
+	header(&mut out, "yaftp_connect_backs_total", "counter", "Connections made back to the master in reverse mode.");
+	let _ = writeln!(out , "yaftp_connect_backs_total {}" , CONNECT_BACKS.load(Ordering::Relaxed));
+
+	header(&mut out, "yaftp_bytes_received_total", "counter", "Data bytes received from clients.");
+	let _ = writeln!(out , "yaftp_bytes_received_total {}" , BYTES[UPLOAD].load(Ordering::Relaxed));
+
+	header(&mut out, "yaftp_bytes_sent_total", "counter", "Data bytes sent to clients.");
+	let _ = writeln!(out , "yaftp_bytes_sent_total {}" , BYTES[DOWNLOAD].load(Ordering::Relaxed));
+
+	header(&mut out, "yaftp_commands_total", "counter", "Commands served by command and retcode.");
+	for ((name , retcode) , count) in COMMANDS.lock().unwrap().iter() {
+		let _ = writeln!(out , "yaftp_commands_total{{command=\"{}\",retcode=\"{}\"}} {}" , name , retcode , count);
+	}
+
+	header(&mut out, "yaftp_transfer_duration_seconds", "histogram", "Duration of transfer commands.");
+	for (name , histogram) in DURATIONS.lock().unwrap().iter() {
+		for (i , bound) in DURATION_BUCKETS.iter().enumerate() {
+			let _ = writeln!(out , "yaftp_transfer_duration_seconds_bucket{{command=\"{}\",le=\"{}\"}} {}" , name , bound , histogram.buckets[i]);
+		}
+		let _ = writeln!(out , "yaftp_transfer_duration_seconds_bucket{{command=\"{}\",le=\"+Inf\"}} {}" , name , histogram.count);
+		let _ = writeln!(out , "yaftp_transfer_duration_seconds_sum{{command=\"{}\"}} {}" , name , histogram.sum);
+		let _ = writeln!(out , "yaftp_transfer_duration_seconds_count{{command=\"{}\"}} {}" , name , histogram.count);
+	}
+
+	out
+}
+
+/* read the request head , none if the peer closed or sent too much */
+async fn read_request(stream : &mut TcpStream) -> Option<String> {
+	let mut head = vec![];
+	let mut buf = [0u8 ; 1024];
+
+	while !head.windows(4).any(|p| p == b"\r\n\r\n") {
+		let n = match stream.read(&mut buf).await {
+			Ok(0) | Err(_) => return None,
+			Ok(p) => p,
+		};
+		head.extend_from_slice(&buf[..n]);
+		if head.len() > MAX_REQUEST_SIZE {
+			return None;
+		}
+	}
+
+	Some(String::from_utf8_lossy(&head).to_string())
+}
+
+async fn serve_scrape(mut stream : TcpStream) {
+	let head = match async_std::future::timeout(REQUEST_TIMEOUT, read_request(&mut stream)).await {
+		Ok(Some(p)) => p,
+		_ => return,
+	};
+
+	let mut line = head.lines().next().unwrap_or("").split(' ');
+
+	let (status , body) = match (line.next() , line.next()) {
+		(Some("GET") , Some("/metrics")) => ("200 OK" , render()),
+		(Some("GET") , _) => ("404 Not Found" , String::from("not found\n")),
+		_ => ("405 Method Not Allowed" , String::from("method not allowed\n")),
+	};
+
+	let response = format!(
+		"HTTP/1.1 {}\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+		status,
+		body.len(),
+		body
+	);
+
+	if let Err(e) = stream.write_all(response.as_bytes()).await {
+		log::warn!("metrics reply error : {}" , e);
+	}
+}
+
+/* answer GET /metrics on listener until the server exits */
+pub async fn serve(listener : TcpListener) {
+	loop {
+		match listener.accept().await {
+			Ok((stream , _)) => {
+				task::spawn(serve_scrape(stream));
+			},
+			Err(e) => {
+				log::error!("metrics accept error : {}" , e);
+				task::sleep(Duration::from_secs(1)).await;
+			},
+		};
+	}
+}
+
+#[test]
+fn test_metrics() {
+	let active = Active::new();
+	transferred(UPLOAD, 10);
+	command("put", 0);
+	command("put", 0);
+	transfer_duration("put", Duration::from_millis(700));
+
+	let text = render();
+	assert!(text.contains("yaftp_sessions_active 1\n"));
+	assert!(text.contains("yaftp_bytes_received_total 10\n"));
+	assert!(text.contains("yaftp_commands_total{command=\"put\",retcode=\"0\"} 2\n"));
+	assert!(text.contains("yaftp_transfer_duration_seconds_bucket{command=\"put\",le=\"0.5\"} 0\n"));
+	assert!(text.contains("yaftp_transfer_duration_seconds_bucket{command=\"put\",le=\"1\"} 1\n"));
+	assert!(text.contains("yaftp_transfer_duration_seconds_count{command=\"put\"} 1\n"));
+
+	drop(active);
+	assert!(render().contains("yaftp_sessions_active 0\n"));
+}
@@ -0,0 +1,357 @@
+use std::path::Path;
+
+use async_std::fs;
+
+use yaftp::client::{Client, SpawnClient};
+use crate::cmd::{format_time, pre_handle_path};
+use yaftp::common::{FileInfo, YaftpError};
+use crate::println_err;
+use crate::println_info;
+use yaftp::utils::calc_md5;
+
+/* commands run once from the command line , the process exits with the retcode */
+pub const COMMANDS : [&str ; 5] = ["get" , "put" , "ls" , "rm" , "hash"];
+
+/* options of a one shot command which take a value , the rest are operands */
+const VALUE_OPTIONS : [&str ; 4] = ["-u" , "--limit" , "--config" , "--log-level"];
+
+/* a failed one shot command , told when its error is printed already */
+pub struct Failure {
+	pub error : YaftpError,
+	pub told : bool
+}
+
+impl Failure {
+	pub fn told(error : YaftpError) -> Failure {
+		Failure { error , told : true }
+	}
+}
+
+/* errors of the client are not printed by it */
+impl From<YaftpError> for Failure {
+	fn from(error : YaftpError) -> Failure {
+		Failure { error , told : false }
+	}
+}
+
+/* a remote path as host:port:path , [ipv6]:port:path for an ipv6 host */
+#[derive(Debug, PartialEq)]
+pub struct Remote {
+	pub host : String,
+	pub port : String,
+	pub path : String
+}
+
+impl Remote {
+
+	/* none when text is a local path */
+	pub fn parse(text : &str) -> Option<Remote> {
+		let (host , rest) = match text.strip_prefix('[') {
+			Some(p) => {
+				let (host , rest) = p.split_once(']')?;
+				(host , rest.strip_prefix(':')?)
+			},
+			None => text.split_once(':')?,
+		};
+
+		let (port , path) = rest.split_once(':')?;
+
+		if host.is_empty() || port.parse::<u16>().is_err() {
+			return None;
+		}
+
+		Some(Remote { host : host.to_string() , port : port.to_string() , path : path.to_string() })
+	}
+}
+
+/* arguments which are not options nor option values */
+pub fn operands(args : &[String]) -> Vec<String> {
+	let mut ret = vec![];
+	let mut args = args.iter();
+	while let Some(p) = args.next() {
+		if VALUE_OPTIONS.contains(&p.as_str()) {
+			args.next();
+			continue;
+		}
+		ret.push(p.clone());
+	}
+	ret
+}
+
+/* the remote operand of command , first but for put */
+pub fn remote_of(command : &str , operands : &[String]) -> Result<Remote , YaftpError> {
+	let (count , index) = match command {
+		"get" => (2 , 0),
+		"put" => (2 , 1),
+		_ => (1 , 0),
+	};
+
+	if operands.len() != count {
+		println_err!("{}" , usage(command));
+		return Err(YaftpError::ArgumentCountError);
+	}
+
+	match Remote::parse(&operands[index]) {
+		Some(p) => Ok(p),
+		None => {
+			println_err!("'{}' is not a remote path . {}" , operands[index] , usage(command));
+			Err(YaftpError::ArgumentError)
+		},
+	}
+}
+
+fn usage(command : &str) -> &'static str {
+	match command {
+		"get" => "eg : yaftp get 127.0.0.1:8000:/var/file1 ./file1",
+		"put" => "eg : yaftp put ./file1 127.0.0.1:8000:/var/file1",
+		"ls" => "eg : yaftp ls 127.0.0.1:8000:/var",
+		"rm" => "eg : yaftp rm 127.0.0.1:8000:/var/file1",
+		_ => "eg : yaftp hash 127.0.0.1:8000:/var/file1",
+	}
+}
+
+async fn connect(spawn : &SpawnClient) -> Result<Client , Failure> {
+	match spawn.spawn().await {
+		Ok(p) => Ok(p),
+		Err(e) => {
+			println_err!("connect error : {}", e);
+			Err(Failure::told(YaftpError::UnknownNetwordError))
+		},
+	}
+}
+
+/*
+	the path sent to the server , which resolves a relative one from the working
+	directory it gives . the server runs one command per connection , asking it
+	for that directory would cost one more .
+*/
+fn server_path(path : &str) -> String {
+	if path.is_empty() {
+		return String::from(".");
+	}
+	path.to_string()
+}
+
+/* name inside the remote folder , a relative folder is joined by '/' which windows takes too */
+fn join(folder : &String , name : &str) -> String {
+	if folder.starts_with('/') || (folder.len() > 1 && folder.as_bytes()[1] == b':') {
+		return pre_handle_path(&name.to_string(), folder);
+	}
+	format!("{}/{}" , folder.trim_end_matches('/') , name)
+}
+
+/* last part of a local or remote path */
+fn file_name(path : &str) -> String {
+	match path.rfind(['/' , '\\']) {
+		Some(p) => path[p + 1..].to_string(),
+		None => path.to_string(),
+	}
+}
+
+/* info of a remote path , none when it does not exist */
+async fn remote_info(spawn : &SpawnClient , path : &String) -> Result<Option<FileInfo> , Failure> {
+	let mut client = connect(spawn).await?;
+	match client.info(path).await {
+		Ok(p) => Ok(Some(p)),
+		Err(YaftpError::NotFound) => Ok(None),
+		Err(e) => Err(e.into()),
+	}
+}
+
+/* md5 of the first size bytes of a local file */
+async fn local_hash(path : &str , size : u64) -> Result<String , Failure> {
+	let mut f = match fs::File::open(path).await {
+		Ok(p) => p,
+		Err(e) => {
+			println_err!("open local file faild : {}" , e);
+			return Err(Failure::told(YaftpError::ReadFileError));
+		},
+	};
+
+	let md5_str = calc_md5(&mut f , size).await;
+	if md5_str.is_empty() {
+		println_err!("calc local file hash faild !");
+		return Err(Failure::told(YaftpError::CalcMd5Error));
+	}
+	Ok(md5_str)
+}
+
+/*
+	where a transfer starts : the size of a partial copy whose data is the
+	start of the file , 0 to overwrite any other copy , none when the copy is
+	already complete .
+*/
+async fn resume_pos(spawn : &SpawnClient , localpath : &str , remotepath : &String , copy_size : u64 , full_size : u64) -> Result<Option<u64> , Failure> {
+	if copy_size == 0 || copy_size > full_size {
+		return Ok(Some(0));
+	}
+
+	let mut client = connect(spawn).await?;
+	let remote_hash = client.hash(remotepath, copy_size).await?;
+	let local_hash = local_hash(localpath, copy_size).await?;
+
+	if remote_hash != local_hash {
+		println_info!("the copy differs , overwrite it");
+		return Ok(Some(0));
+	}
+
+	if copy_size == full_size {
+		println_info!("the copy is up to date");
+		return Ok(None);
+	}
+
+	println_info!("resume broken transfer at {}" , copy_size);
+	Ok(Some(copy_size))
+}
+
+async fn get(spawn : &SpawnClient , remote : &Remote , local : &str) -> Result<() , Failure> {
+	let path = server_path(&remote.path);
+
+	let info = match remote_info(spawn, &path).await? {
+		Some(p) => p,
+		None => {
+			println_err!("'{}' not found" , path);
+			return Err(Failure::told(YaftpError::NotFound));
+		},
+	};
+
+	if !info.is_file() {
+		println_err!("'{}' not a file" , info.path);
+		return Err(Failure::told(YaftpError::ArgumentError));
+	}
+
+	/* into a local folder the file keeps its name */
+	let local = if Path::new(local).is_dir() {
+		Path::new(local).join(file_name(&info.path)).to_string_lossy().to_string()
+	} else {
+		local.to_string()
+	};
+
+	let start_pos = match fs::metadata(&local).await {
+		Ok(p) => match resume_pos(spawn, &local, &info.path, p.len(), info.size).await? {
+			Some(p) => p,
+			None => return Ok(()),
+		},
+		Err(_) => 0,
+	};
+
+	let mut client = connect(spawn).await?;
+	client.get(&local, &info.path, start_pos).await?;
+	println_info!("file transfer success!");
+	Ok(())
+}
+
+async fn put(spawn : &SpawnClient , local : &str , remote : &Remote) -> Result<() , Failure> {
+	let size = match fs::metadata(local).await {
+		Ok(p) if p.is_file() => p.len(),
+		Ok(_) => {
+			println_err!("'{}' not a file" , local);
+			return Err(Failure::told(YaftpError::ReadFileError));
+		},
+		Err(e) => {
+			println_err!("read local file '{}' faild : {}" , local , e);
+			return Err(Failure::told(YaftpError::ReadFileError));
+		},
+	};
+
+	let mut path = server_path(&remote.path);
+
+	let mut existing = remote_info(spawn, &path).await?;
+
+	/* into a remote folder the file keeps its name */
+	if let Some(info) = &existing {
+		if info.is_folder() {
+			path = join(&info.path, &file_name(local));
+			existing = remote_info(spawn, &path).await?;
+		}
+	}
+
+	let start_pos = match existing {
+		Some(info) => {
+			if !info.is_file() {
+				println_err!("'{}' not a file" , info.path);
+				return Err(Failure::told(YaftpError::ArgumentError));
+			}
+			match resume_pos(spawn, local, &info.path, info.size, size).await? {
+				Some(p) => p,
+				None => return Ok(()),
+			}
+		},
+		None => 0,
+	};
+
+	let mut client = connect(spawn).await?;
+	client.put(local, &path, start_pos, 0, 0).await?;
+	println_info!("file transfer success!");
+	Ok(())
+}
+
+/* one row per entry , name type size modified and accessed split by tabs */
+async fn ls(spawn : &SpawnClient , remote : &Remote) -> Result<() , Failure> {
+	let path = server_path(&remote.path);
+	let mut client = connect(spawn).await?;
+	for entry in client.ls(&path).await? {
+		println!("{}\t{}\t{}\t{}\t{}" , entry.name , entry.kind , entry.size , format_time(entry.modified) , format_time(entry.accessed));
+	}
+	Ok(())
+}
+
+async fn rm(spawn : &SpawnClient , remote : &Remote) -> Result<() , Failure> {
+	let path = server_path(&remote.path);
+	let mut client = connect(spawn).await?;
+	client.rm(&path, true).await?;
+	println_info!("remove '{}' success" , path);
+	Ok(())
+}
+
+/* md5 and path , like md5sum */
+async fn hash(spawn : &SpawnClient , remote : &Remote) -> Result<() , Failure> {
+	let path = server_path(&remote.path);
+
+	let info = match remote_info(spawn, &path).await? {
+		Some(p) => p,
+		None => {
+			println_err!("'{}' not found" , path);
+			return Err(Failure::told(YaftpError::NotFound));
+		},
+	};
+
+	if !info.is_file() {
+		println_err!("'{}' not a file" , info.path);
+		return Err(Failure::told(YaftpError::ArgumentError));
+	}
+
+	let mut client = connect(spawn).await?;
+	println!("{}  {}" , client.hash(&info.path, info.size).await? , info.path);
+	Ok(())
+}
+
+/* run command on the server of remote , operands as given on the command line */
+pub async fn run(spawn : &SpawnClient , command : &str , remote : &Remote , operands : &[String]) -> Result<() , Failure> {
+	match command {
+		"get" => get(spawn, remote, &operands[1]).await,
+		"put" => put(spawn, &operands[0], remote).await,
+		"ls" => ls(spawn, remote).await,
+		"rm" => rm(spawn, remote).await,
+		"hash" => hash(spawn, remote).await,
+		_ => Err(YaftpError::NoSupportCommand.into()),
+	}
+}
+
+#[test]
+fn test_oneshot() {
+	assert_eq!(Remote::parse("10.0.0.1:8000:/var/a:b") , Some(Remote { host : String::from("10.0.0.1") , port : String::from("8000") , path : String::from("/var/a:b") }));
+	assert_eq!(Remote::parse("[::1]:8000:").unwrap().host , "::1");
+	assert_eq!(Remote::parse("host:8000:a").unwrap().path , "a");
+	assert!(Remote::parse("./file").is_none());
+	assert!(Remote::parse("C:\\file").is_none());
+	assert!(Remote::parse("host:http:/a").is_none());
+
+	let args : Vec<String> = ["-u" , "alice" , "a" , "--limit" , "1m" , "b"].iter().map(|p| p.to_string()).collect();
+	assert_eq!(operands(&args) , ["a" , "b"]);
+	assert_eq!(file_name("/var/a.txt") , "a.txt");
+	assert_eq!(file_name("C:\\a.txt") , "a.txt");
+	assert_eq!(server_path("") , ".");
+	assert_eq!(join(&String::from("releases/") , "a.txt") , "releases/a.txt");
+	assert_eq!(join(&String::from("/") , "a.txt") , "/a.txt");
+}
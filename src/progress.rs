@@ -0,0 +1,97 @@
+use std::{sync::{Arc, atomic::{AtomicBool, Ordering}}, time::Instant};
+
+/* how far a transfer is , rate is bytes per second since it started */
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Transfer {
+	pub done : u64,
+	pub total : u64,
+	pub rate : u64
+}
+
+/*
+	told how a transfer of the client goes , get and put know the total ,
+	archives are streamed and start with a total of 0 . the shell draws a
+	progress bar from it , a service may log or show it its own way .
+*/
+pub trait Progress : Send + Sync {
+
+	/* a transfer of total bytes starts , 0 when the size is unknown */
+	fn start(&self , total : u64);
+
+	/* after every chunk moved */
+	fn update(&self , transfer : &Transfer);
+
+	/* the transfer ended , ok is false when it failed or was cancelled */
+	fn finish(&self , ok : bool);
+}
+
+/* progress of a client nobody watches */
+pub struct Silent;
+
+impl Progress for Silent {
+	fn start(&self , _total : u64) {}
+	fn update(&self , _transfer : &Transfer) {}
+	fn finish(&self , _ok : bool) {}
+}
+
+/*
+	stops the transfers of every client holding a clone , checked between
+	chunks . a cancelled put or get leaves a partial copy to resume .
+*/
+#[derive(Clone, Default)]
+pub struct Cancel {
+	cancelled : Arc<AtomicBool>
+}
+
+impl Cancel {
+	pub fn new() -> Cancel {
+		Cancel::default()
+	}
+
+	pub fn cancel(&self) {
+		self.cancelled.store(true, Ordering::SeqCst);
+	}
+
+	pub fn is_cancelled(&self) -> bool {
+		self.cancelled.load(Ordering::SeqCst)
+	}
+
+	/* let the next transfers run again */
+	pub fn reset(&self) {
+		self.cancelled.store(false, Ordering::SeqCst);
+	}
+}
+
+/* counts the rate of a transfer from its start */
+pub(crate) struct Meter {
+	started : Instant,
+	total : u64
+}
+
+impl Meter {
+	pub(crate) fn new(total : u64) -> Meter {
+		Meter { started : Instant::now() , total }
+	}
+
+	pub(crate) fn at(&self , done : u64) -> Transfer {
+		let millis = self.started.elapsed().as_millis() as u64;
+		let rate = done.saturating_mul(1000).checked_div(millis).unwrap_or(0);
+		Transfer { done , total : self.total , rate }
+	}
+}
+
+#[test]
+fn test_progress() {
+	let cancel = Cancel::new();
+	let other = cancel.clone();
+	assert!(!other.is_cancelled());
+	cancel.cancel();
+	assert!(other.is_cancelled());
+	other.reset();
+	assert!(!cancel.is_cancelled());
+
+	let meter = Meter { started : Instant::now() - std::time::Duration::from_secs(2) , total : 100 };
+	let transfer = meter.at(50);
+	assert_eq!(transfer.total , 100);
+	assert!(transfer.rate > 0 && transfer.rate <= 25);
+}
@@ -0,0 +1,221 @@
+use std::{path::{Path, PathBuf}, sync::{Arc, Mutex, MutexGuard}, time::{Duration, Instant}};
+
+use async_std::task;
+
+use crate::{common::YaftpError, search::Walker};
+
+pub const QUOTA_USER : &str = "user";
+pub const QUOTA_ROOT : &str = "root";
+
+/* usage counted by the server drifts from the disk , scan the folder again after */
+const RESCAN_INTERVAL : Duration = Duration::from_secs(60);
+
+struct Usage {
+	bytes : u64,
+	files : u64,
+	scanned : Option<Instant>,
+	stale : bool,
+	writers : u32
+}
+
+/*
+	byte and file limits of a folder tree . usage is scanned from the disk
+	once then counted by the writers , a new scan waits until no writer is
+	running so a half written file is not counted twice .
+*/
+pub struct Quota {
+	pub kind : &'static str,
+	pub path : PathBuf,
+	pub max_bytes : Option<u64>,
+	pub max_files : Option<u64>,
+	usage : Mutex<Usage>
+}
+
+impl Quota {
+
+	pub fn new(kind : &'static str , path : PathBuf , max_bytes : Option<u64> , max_files : Option<u64>) -> Quota {
+		Quota {
+			kind,
+			path,
+			max_bytes,
+			max_files,
+			usage : Mutex::new(Usage { bytes : 0 , files : 0 , scanned : None , stale : false , writers : 0 })
+		}
+	}
+
+	fn expired(usage : &Usage) -> bool {
+		match usage.scanned {
+			Some(p) => usage.writers == 0 && (usage.stale || p.elapsed() > RESCAN_INTERVAL),
+			None => true,
+		}
+	}
+
+	/*
+		scan the folder again when due . the walk is blocking and runs
+		without the lock , so counting writes is never held up by it .
+	*/
+	fn refresh(&self) {
+		if !Quota::expired(&self.usage.lock().unwrap()) {
+			return;
+		}
+
+		let (bytes , files) = scan(&self.path);
+
+		/* another scan or a writer may have come meanwhile */
+		let mut usage = self.usage.lock().unwrap();
+		if Quota::expired(&usage) {
+			usage.bytes = bytes;
+			usage.files = files;
+			usage.scanned = Some(Instant::now());
+			usage.stale = false;
+		}
+	}
+
+	fn refreshed(&self) -> MutexGuard<'_ , Usage> {
+		self.refresh();
+		self.usage.lock().unwrap()
+	}
+
+	/* bytes and files used under the folder */
+	pub fn usage(&self) -> (u64 , u64) {
+		let usage = self.refreshed();
+		(usage.bytes , usage.files)
+	}
+
+	/* usage from an async task , a due scan runs in a blocking thread */
+	pub async fn usage_async(self : &Arc<Quota>) -> (u64 , u64) {
+		let quota = self.clone();
+		task::spawn_blocking(move || quota.usage()).await
+	}
+
+	fn allow(&self , usage : &Usage , bytes : u64 , files : u64) -> bool {
+		if let Some(max) = self.max_bytes {
+			if usage.bytes.saturating_add(bytes) > max {
+				return false;
+			}
+		}
+		if let Some(max) = self.max_files {
+			if usage.files.saturating_add(files) > max {
+				return false;
+			}
+		}
+		true
+	}
+
+	/* files were removed or moved out , count the folder again */
+	pub fn invalidate(&self) {
+		self.usage.lock().unwrap().stale = true;
+	}
+}
+
+fn scan(path : &Path) -> (u64 , u64) {
+	let mut bytes = 0u64;
+	let mut files = 0u64;
+	for entry in Walker::new(path.to_path_buf(), 0) {
+		if entry.meta.is_dir() {
+			continue;
+		}
+		bytes += entry.meta.len();
+		files += 1;
+	}
+	(bytes , files)
+}
+
+/*
+	the quotas a write under one path is counted in , the writer keeps it
+	alive until the write ends .
+*/
+pub struct Charge {
+	quotas : Vec<Arc<Quota>>
+}
+
+impl Charge {
+
+	pub fn new(quotas : Vec<Arc<Quota>>) -> Charge {
+		for quota in &quotas {
+			quota.usage.lock().unwrap().writers += 1;
+		}
+		Charge { quotas }
+	}
+
+	/* scan the due quotas in a blocking thread , so check and reserve do not walk a folder on an async task */
+	pub async fn refresh(&self) {
+		let quotas = self.quotas.clone();
+		task::spawn_blocking(move || {
+			for quota in quotas {
+				quota.refresh();
+			}
+		}).await
+	}
+
+	/* fail if bytes and files more would exceed a quota , nothing is counted */
+	pub fn check(&self , bytes : u64 , files : u64) -> Result<() , YaftpError> {
+		for quota in &self.quotas {
+			let usage = quota.refreshed();
+			if !quota.allow(&usage, bytes, files) {
+				log::warn!("quota of {} exceeded" , quota.path.display());
+				return Err(YaftpError::QuotaExceeded);
+			}
+		}
+		Ok(())
+	}
+
+	/* count bytes and files written , fail and count nothing if a quota would be exceeded */
+	pub fn reserve(&self , bytes : u64 , files : u64) -> Result<() , YaftpError> {
+		if bytes == 0 && files == 0 {
+			return Ok(());
+		}
+		self.check(bytes, files)?;
+		for quota in &self.quotas {
+			let mut usage = quota.usage.lock().unwrap();
+			usage.bytes = usage.bytes.saturating_add(bytes);
+			usage.files = usage.files.saturating_add(files);
+		}
+		Ok(())
+	}
+
+	/* count bytes and files freed by the write */
+	pub fn release(&self , bytes : u64 , files : u64) {
+		for quota in &self.quotas {
+			let mut usage = quota.usage.lock().unwrap();
+			usage.bytes = usage.bytes.saturating_sub(bytes);
+			usage.files = usage.files.saturating_sub(files);
+		}
+	}
+}
+
+impl Drop for Charge {
+	fn drop(&mut self) {
+		for quota in &self.quotas {
+			let mut usage = quota.usage.lock().unwrap();
+			usage.writers = usage.writers.saturating_sub(1);
+		}
+	}
+}
+
+#[test]
+fn test_quota() {
+	let root = std::env::temp_dir().join("yaftp_test_quota");
+	let _ = std::fs::remove_dir_all(&root);
+	std::fs::create_dir_all(root.join("sub")).unwrap();
+	std::fs::write(root.join("sub/a.txt"), "yaftp").unwrap();
+
+	let quota = Arc::new(Quota::new(QUOTA_ROOT, root.clone(), Some(10), Some(2)));
+	assert_eq!(quota.usage() , (5 , 1));
+
+	let charge = Charge::new(vec![quota.clone()]);
+	assert!(charge.reserve(3, 1).is_ok());
+	assert!(matches!(charge.reserve(3, 0) , Err(YaftpError::QuotaExceeded)));
+	assert!(matches!(charge.check(0, 1) , Err(YaftpError::QuotaExceeded)));
+	charge.release(3, 1);
+	assert_eq!(quota.usage() , (5 , 1));
+
+	/* no scan while a writer is running , even when invalidated */
+	std::fs::write(root.join("b.txt"), "yaftp").unwrap();
+	quota.invalidate();
+	assert_eq!(quota.usage() , (5 , 1));
+	drop(charge);
+	assert_eq!(quota.usage() , (10 , 2));
+
+	std::fs::remove_dir_all(&root).unwrap();
+}
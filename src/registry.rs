@@ -0,0 +1,254 @@
+use std::{collections::BTreeMap, future::Future, net::SocketAddr, sync::{Arc, Mutex, atomic::{AtomicU64, Ordering}}, time::{Duration, Instant}};
+
+use async_std::{channel::{self, Receiver, Sender}, task};
+use futures::future::{AbortHandle, Abortable};
+
+use crate::audit::Trail;
+
+/* sessions still running this long after a shutdown are killed */
+pub const DEFAULT_SHUTDOWN_GRACE : Duration = Duration::from_secs(30);
+
+const DRAIN_POLL_INTERVAL : Duration = Duration::from_millis(100);
+
+/* killed sessions are given this long to close their files */
+const KILL_WAIT : Duration = Duration::from_secs(5);
+
+struct Entry {
+	peer : Option<SocketAddr>,
+	started : Instant,
+	user : Option<String>,
+	command : &'static str,
+	trail : Option<Arc<Mutex<Trail>>>,
+	abort : AbortHandle
+}
+
+/* what a session is doing , taken when listed */
+pub struct Status {
+	pub id : u64,
+	pub peer : Option<SocketAddr>,
+	pub user : Option<String>,
+	pub command : &'static str,
+	pub paths : Vec<String>,
+	pub bytes : u64,
+	pub total : u64,
+	pub elapsed : Duration
+}
+
+impl Status {
+
+	/* id|peer|user|command|paths|bytes|total|seconds , - when unknown */
+	pub fn row(&self) -> String {
+		let peer = match self.peer {
+			Some(p) => p.to_string(),
+			None => String::from("-"),
+		};
+		let user = match &self.user {
+			Some(p) => p.clone(),
+			None => String::from("-"),
+		};
+		let paths = if self.paths.is_empty() { String::from("-") } else { self.paths.join(",") };
+		let command = if self.command.is_empty() { "-" } else { self.command };
+
+		[self.id.to_string() , peer , user , command.to_string() , paths , self.bytes.to_string() , self.total.to_string() , self.elapsed.as_secs().to_string()].join("|")
+	}
+}
+
+/*
+	every session the server runs , so they can be listed and killed . a
+	shutdown stops the listeners and lets the running sessions end .
+*/
+pub struct Registry {
+	next_id : AtomicU64,
+	sessions : Mutex<BTreeMap<u64 , Entry>>,
+	stop : Mutex<Option<Sender<()>>>,
+	stopped : Receiver<()>
+}
+
+/* removes a finished or killed session from the registry */
+struct Registered {
+	registry : Arc<Registry>,
+	id : u64
+}
+
+impl Drop for Registered {
+	fn drop(&mut self) {
+		self.registry.sessions.lock().unwrap().remove(&self.id);
+	}
+}
+
+impl Default for Registry {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+impl Registry {
+
+	pub fn new() -> Registry {
+		let (stop , stopped) = channel::bounded(1);
+		Registry {
+			next_id : AtomicU64::new(1),
+			sessions : Mutex::new(BTreeMap::new()),
+			stop : Mutex::new(Some(stop)),
+			stopped
+		}
+	}
+
+	/* run the session built by handle from its id , it is listed until it ends or is killed */
+	pub fn spawn<F>(self : &Arc<Registry> , peer : Option<SocketAddr> , handle : impl FnOnce(Arc<Registry> , u64) -> F)
+		where F : Future<Output = ()> + Send + 'static {
+
+		let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+		let (abort , registration) = AbortHandle::new_pair();
+
+		self.sessions.lock().unwrap().insert(id, Entry {
+			peer,
+			started : Instant::now(),
+			user : None,
+			command : "",
+			trail : None,
+			abort
+		});
+
+		let registered = Registered { registry : self.clone() , id };
+		let session = Abortable::new(handle(self.clone(), id), registration);
+
+		task::spawn(async move {
+			let _ = session.await;
+			drop(registered);
+		});
+	}
+
+	pub fn login(&self , id : u64 , user : &str) {
+		if let Some(p) = self.sessions.lock().unwrap().get_mut(&id) {
+			p.user = Some(user.to_string());
+		}
+	}
+
+	/* the session runs command , trail follows its paths and bytes */
+	pub fn start(&self , id : u64 , command : &'static str , trail : Arc<Mutex<Trail>>) {
+		if let Some(p) = self.sessions.lock().unwrap().get_mut(&id) {
+			p.command = command;
+			p.trail = Some(trail);
+		}
+	}
+
+	pub fn list(&self) -> Vec<Status> {
+		let sessions = self.sessions.lock().unwrap();
+		sessions.iter().map(|(id , p)| {
+			let (paths , bytes , total) = match &p.trail {
+				Some(trail) => {
+					let trail = trail.lock().unwrap();
+					(trail.paths.iter().map(|p| p.to_string_lossy().to_string()).collect() , trail.bytes , trail.total)
+				},
+				None => (vec![] , 0 , 0),
+			};
+			Status {
+				id : *id,
+				peer : p.peer,
+				user : p.user.clone(),
+				command : p.command,
+				paths,
+				bytes,
+				total,
+				elapsed : p.started.elapsed()
+			}
+		}).collect()
+	}
+
+	/* stop a session at its next wait , false if there is no such session */
+	pub fn kill(&self , id : u64) -> bool {
+		match self.sessions.lock().unwrap().get(&id) {
+			Some(p) => {
+				log::warn!("kill session {}" , id);
+				p.abort.abort();
+				true
+			},
+			None => false,
+		}
+	}
+
+	pub fn shutdown(&self) {
+		/* log before the listeners wake up , the process may exit right after */
+		let stop = self.stop.lock().unwrap().take();
+		if stop.is_some() {
+			log::warn!("shutdown requested");
+		}
+		drop(stop);
+	}
+
+	/* wait until a shutdown is requested */
+	pub async fn stopped(&self) {
+		let _ = self.stopped.recv().await;
+	}
+
+	/* wait up to limit for every session to end , the number still running */
+	async fn wait_empty(&self , limit : Duration) -> usize {
+		let start = Instant::now();
+		loop {
+			let count = self.sessions.lock().unwrap().len();
+			if count == 0 || start.elapsed() >= limit {
+				return count;
+			}
+			task::sleep(DRAIN_POLL_INTERVAL).await;
+		}
+	}
+
+	/*
+		wait for the running sessions to end , kill what is left after grace .
+		a killed upload closes its file with the data received so far , the
+		client resumes it later .
+	*/
+	pub async fn drain(&self , grace : Duration) {
+		let count = self.sessions.lock().unwrap().len();
+		if count != 0 {
+			log::info!("waiting {} seconds for {} sessions to end" , grace.as_secs() , count);
+		}
+
+		let count = self.wait_empty(grace).await;
+		if count == 0 {
+			return;
+		}
+
+		log::warn!("kill {} sessions still running" , count);
+		for session in self.sessions.lock().unwrap().values() {
+			session.abort.abort();
+		}
+
+		let count = self.wait_empty(KILL_WAIT).await;
+		if count != 0 {
+			log::error!("{} sessions did not end" , count);
+		}
+	}
+}
+
+#[test]
+fn test_registry() {
+	let registry = Arc::new(Registry::new());
+	let (done , wait) = channel::bounded::<()>(1);
+
+	registry.spawn(None, move |registry , id| async move {
+		let trail = Arc::new(Mutex::new(Trail::default()));
+		trail.lock().unwrap().bytes = 5;
+		registry.login(id, "alice");
+		registry.start(id, "get", trail);
+		let _ = done.send(()).await;
+		task::sleep(Duration::from_secs(60)).await;
+	});
+
+	task::block_on(async {
+		let _ = wait.recv().await;
+
+		let list = registry.list();
+		assert_eq!(list.len() , 1);
+		assert_eq!(list[0].row() , format!("{}|-|alice|get|-|5|0|0" , list[0].id));
+
+		assert!(!registry.kill(list[0].id + 1));
+		assert!(registry.kill(list[0].id));
+		registry.drain(Duration::from_secs(5)).await;
+		assert!(registry.list().is_empty());
+
+		registry.shutdown();
+		registry.stopped().await;
+	});
+}
@@ -0,0 +1,117 @@
+use std::time::Duration;
+
+use async_std::{io::{self, prelude::*}, net::TcpStream};
+use blake2::{Blake2b512, Blake2bMac512, digest::{Digest, KeyInit, Mac}};
+use password_hash::rand_core::{OsRng, RngCore};
+
+const NONCE_SIZE : usize = 16;
+const TAG_SIZE : usize = 64;
+
+/* the secret is proved within this time , or the connection is dropped */
+pub const PROOF_TIMEOUT : Duration = Duration::from_secs(10);
+
+/*
+	a secret shared by the master and the slave of reverse mode . every
+	connection between them starts with both sides proving they know it ,
+	so nobody else can pose as the slave to the master or as the master
+	to the slave . the secret itself never goes over the wire .
+*/
+pub struct Secret {
+	key : [u8 ; 64]
+}
+
+impl Secret {
+	pub fn new(text : &str) -> Secret {
+		Secret { key : Blake2b512::digest(text.as_bytes()).into() }
+	}
+
+	/* proof of the side label over the nonces of the master and the slave */
+	fn tag(&self , label : &[u8] , master : &[u8] , slave : &[u8]) -> Blake2bMac512 {
+		let mut mac = <Blake2bMac512 as KeyInit>::new_from_slice(&self.key).unwrap();
+		mac.update(label);
+		mac.update(master);
+		mac.update(slave);
+		mac
+	}
+}
+
+fn wrong_secret() -> io::Error {
+	io::Error::new(io::ErrorKind::PermissionDenied, "wrong reverse secret")
+}
+
+/* send our nonce , read the nonce of the other side */
+async fn exchange(stream : &mut TcpStream) -> io::Result<([u8 ; NONCE_SIZE] , [u8 ; NONCE_SIZE])> {
+	let mut ours = [0u8 ; NONCE_SIZE];
+	OsRng.fill_bytes(&mut ours);
+	stream.write_all(&ours).await?;
+
+	let mut theirs = [0u8 ; NONCE_SIZE];
+	stream.read_exact(&mut theirs).await?;
+	Ok((ours , theirs))
+}
+
+/* the master checks the slave proof first , then proves it to the slave */
+pub async fn prove_master(stream : &mut TcpStream , secret : &Secret) -> io::Result<()> {
+	io::timeout(PROOF_TIMEOUT, async {
+		let (master , slave) = exchange(stream).await?;
+
+		let mut proof = [0u8 ; TAG_SIZE];
+		stream.read_exact(&mut proof).await?;
+		secret.tag(b"slave", &master, &slave).verify_slice(&proof).map_err(|_| wrong_secret())?;
+
+		stream.write_all(&secret.tag(b"master", &master, &slave).finalize().into_bytes()).await
+	}).await
+}
+
+/* the slave proves it knows the secret , then checks the master proof */
+pub async fn prove_slave(stream : &mut TcpStream , secret : &Secret) -> io::Result<()> {
+	io::timeout(PROOF_TIMEOUT, async {
+		let (slave , master) = exchange(stream).await?;
+
+		stream.write_all(&secret.tag(b"slave", &master, &slave).finalize().into_bytes()).await?;
+
+		let mut proof = [0u8 ; TAG_SIZE];
+		stream.read_exact(&mut proof).await?;
+		secret.tag(b"master", &master, &slave).verify_slice(&proof).map_err(|_| wrong_secret())
+	}).await
+}
+
+#[test]
+fn test_prove() {
+	use async_std::{net::TcpListener, task};
+
+	/* result of the master and the slave proving their secrets to each other */
+	fn prove(master : &'static str , slave : &'static str) -> (io::Result<()> , io::Result<()>) {
+		task::block_on(async {
+			let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+			let addr = listener.local_addr().unwrap();
+
+			let other = task::spawn(async move {
+				let mut stream = TcpStream::connect(addr).await.unwrap();
+				prove_slave(&mut stream, &Secret::new(slave)).await
+			});
+
+			let (mut stream , _) = listener.accept().await.unwrap();
+			let ret = prove_master(&mut stream, &Secret::new(master)).await;
+			drop(stream);
+			(ret , other.await)
+		})
+	}
+
+	let (master , slave) = prove("s3cret", "s3cret");
+	assert!(master.is_ok());
+	assert!(slave.is_ok());
+
+	/* a wrong slave is refused before the master proves anything */
+	let (master , slave) = prove("s3cret", "guess");
+	assert_eq!(master.unwrap_err().kind() , io::ErrorKind::PermissionDenied);
+	assert!(slave.is_err());
+
+	/* a proof is bound to its side , the master cannot echo the slave proof */
+	let secret = Secret::new("s3cret");
+	let (a , b) = ([1u8 ; NONCE_SIZE] , [2u8 ; NONCE_SIZE]);
+	let slave = secret.tag(b"slave", &a, &b).finalize().into_bytes();
+	assert!(secret.tag(b"master", &a, &b).verify_slice(&slave).is_err());
+	assert!(secret.tag(b"slave", &b, &a).verify_slice(&slave).is_err());
+	assert!(secret.tag(b"slave", &a, &b).verify_slice(&slave).is_ok());
+}
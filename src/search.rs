@@ -0,0 +1,328 @@
+use std::{collections::VecDeque, fs::{self, Metadata}, io::{self, BufRead, BufReader}, path::{Path, PathBuf}};
+
+use chrono::{DateTime, offset::Utc};
+use glob::Pattern;
+use regex::{Regex, RegexBuilder};
+
+use crate::common::{FindFilter, FIND_TYPE_ANY, FIND_TYPE_FILE, FIND_TYPE_FOLDER, FIND_TYPE_SYMLINK, YaftpError};
+
+pub const MAX_GREP_LINE : usize = 4000;
+
+pub enum NameMatcher {
+	Any,
+	Glob(Pattern),
+	Regex(Regex)
+}
+
+impl NameMatcher {
+
+	pub fn new(pattern : &str , regex : bool) -> Result<NameMatcher , YaftpError> {
+
+		if pattern.is_empty() {
+			return Ok(NameMatcher::Any);
+		}
+
+		if regex {
+			return match Regex::new(pattern){
+				Ok(p) => Ok(NameMatcher::Regex(p)),
+				Err(_) => Err(YaftpError::ArgumentError),
+			};
+		}
+
+		match Pattern::new(pattern){
+			Ok(p) => Ok(NameMatcher::Glob(p)),
+			Err(_) => Err(YaftpError::ArgumentError),
+		}
+	}
+
+	pub fn is_match(&self , name : &str) -> bool {
+		match self {
+			NameMatcher::Any => true,
+			NameMatcher::Glob(p) => p.matches(name),
+			NameMatcher::Regex(p) => p.is_match(name),
+		}
+	}
+}
+
+pub struct WalkEntry {
+	pub path : PathBuf,
+	pub meta : Metadata
+}
+
+/*
+	depth first walker over a folder , it never follows symlinks so a link
+	loop can not trap it . entries which can not be read are skipped .
+*/
+pub struct Walker {
+	stack : Vec<(PathBuf , u64)>,
+	max_depth : u64
+}
+
+impl Walker {
+
+	pub fn new(root : PathBuf , max_depth : u64) -> Walker {
+		let mut walker = Walker { stack : vec![] , max_depth };
+		walker.push_children(&root, 0);
+		walker
+	}
+
+	fn push_children(&mut self , folder : &PathBuf , depth : u64) {
+
+		if self.max_depth != 0 && depth >= self.max_depth {
+			return;
+		}
+
+		let paths = match fs::read_dir(folder){
+			Ok(p) => p,
+			Err(_) => return,
+		};
+
+		let mut children : Vec<PathBuf> = paths.filter_map(|p| p.ok()).map(|p| p.path()).collect();
+		children.sort();
+
+		for child in children.into_iter().rev() {
+			self.stack.push((child , depth + 1));
+		}
+	}
+}
+
+impl Iterator for Walker {
+	type Item = WalkEntry;
+
+	fn next(&mut self) -> Option<WalkEntry> {
+		while let Some((path , depth)) = self.stack.pop() {
+
+			let meta = match fs::symlink_metadata(&path){
+				Ok(p) => p,
+				Err(_) => continue,
+			};
+
+			if meta.is_dir() {
+				self.push_children(&path, depth);
+			}
+
+			return Some(WalkEntry { path , meta });
+		}
+		None
+	}
+}
+
+pub fn type_name(meta : &Metadata) -> String {
+	let t = meta.file_type();
+	if t.is_dir() {
+		String::from("folder")
+	} else if t.is_file() {
+		String::from("file")
+	} else if t.is_symlink() {
+		String::from("symlink")
+	} else {
+		String::from("other")
+	}
+}
+
+pub fn mtime(meta : &Metadata) -> u64 {
+	match meta.modified(){
+		Ok(p) => {
+			let mt : DateTime<Utc> = p.into();
+			mt.timestamp().try_into().unwrap_or(0)
+		},
+		Err(_) => 0,
+	}
+}
+
+pub fn find_match(entry : &WalkEntry , filter : &FindFilter , matcher : &NameMatcher) -> bool {
+
+	let t = entry.meta.file_type();
+
+	let typ_match = match filter.typ {
+		FIND_TYPE_ANY => true,
+		FIND_TYPE_FILE => t.is_file(),
+		FIND_TYPE_FOLDER => t.is_dir(),
+		FIND_TYPE_SYMLINK => t.is_symlink(),
+		_ => false,
+	};
+
+	if !typ_match {
+		return false;
+	}
+
+	let size = entry.meta.len();
+	if size < filter.min_size || size > filter.max_size {
+		return false;
+	}
+
+	let mt = mtime(&entry.meta);
+	if mt < filter.newer || mt > filter.older {
+		return false;
+	}
+
+	let name = match entry.path.file_name(){
+		Some(p) => p.to_string_lossy().to_string(),
+		None => return false,
+	};
+
+	matcher.is_match(&name)
+}
+
+pub fn line_matcher(pattern : &str , regex : bool , ignore_case : bool) -> Result<Regex , YaftpError> {
+
+	let pattern = if regex {
+		pattern.to_string()
+	} else {
+		regex::escape(pattern)
+	};
+
+	match RegexBuilder::new(&pattern).case_insensitive(ignore_case).build(){
+		Ok(p) => Ok(p),
+		Err(_) => Err(YaftpError::ArgumentError),
+	}
+}
+
+fn grep_row(path : &str , num : u64 , sep : char , line : &[u8]) -> String {
+
+	let mut line = String::from_utf8_lossy(line).trim_end_matches(['\r', '\n']).to_string();
+
+	if line.len() > MAX_GREP_LINE {
+		let mut end = MAX_GREP_LINE;
+		while !line.is_char_boundary(end) {
+			end -= 1;
+		}
+		line.truncate(end);
+	}
+
+	format!("{}{}{}{}{}", path , sep , num , sep , line)
+}
+
+/*
+	search a file line by line , emit rows as `name:line:text` for matched
+	lines and `name-line-text` for context lines as they are found , return
+	the count of matched lines . a file with a NUL byte in its first block
+	is treated as binary and emits no rows . when emit returns false the
+	search stops with BrokenPipe .
+*/
+pub fn grep_file(path : &Path , name : &str , matcher : &Regex , context : u64 , max_matches : u64 , emit : &mut impl FnMut(String) -> bool) -> io::Result<u64> {
+
+	let f = fs::File::open(path)?;
+	let mut reader = BufReader::new(f);
+
+	if reader.fill_buf()?.contains(&0u8) {
+		return Ok(0);
+	}
+
+	let mut send = |row : String| -> io::Result<()> {
+		match emit(row) {
+			true => Ok(()),
+			false => Err(io::Error::new(io::ErrorKind::BrokenPipe, "grep receiver closed")),
+		}
+	};
+
+	let mut before : VecDeque<(u64 , Vec<u8>)> = VecDeque::new();
+	let mut after_left = 0u64;
+	let mut count = 0u64;
+	let mut num = 0u64;
+	let mut line : Vec<u8> = vec![];
+
+	loop {
+		line.clear();
+		if reader.read_until(b'\n', &mut line)? == 0 {
+			break;
+		}
+		num += 1;
+
+		if matcher.is_match(&String::from_utf8_lossy(&line)) {
+
+			if max_matches != 0 && count >= max_matches {
+				break;
+			}
+
+			for (n , l) in before.drain(..) {
+				send(grep_row(name, n, '-', &l))?;
+			}
+			send(grep_row(name, num, ':', &line))?;
+			after_left = context;
+			count += 1;
+
+		} else if after_left > 0 {
+			send(grep_row(name, num, '-', &line))?;
+			after_left -= 1;
+		} else if context > 0 {
+			if before.len() as u64 >= context {
+				before.pop_front();
+			}
+			before.push_back((num , line.clone()));
+		}
+	}
+
+	Ok(count)
+}
+
+/*
+	grep a file , or every file under a folder , until max_matches lines
+	matched in all . files of a folder which can not be read are skipped .
+	name gives the path shown in a row .
+*/
+pub fn grep_path(path : &Path , name : impl Fn(&Path) -> String , matcher : &Regex , context : u64 , max_matches : u64 , emit : &mut impl FnMut(String) -> bool) -> io::Result<()> {
+
+	if !fs::metadata(path)?.is_dir() {
+		grep_file(path, &name(path), matcher, context, max_matches, emit)?;
+		return Ok(());
+	}
+
+	let mut left = max_matches;
+	for entry in Walker::new(path.to_path_buf(), 0) {
+
+		if max_matches != 0 && left == 0 {
+			break;
+		}
+
+		if !entry.meta.is_file() {
+			continue;
+		}
+
+		match grep_file(&entry.path, &name(&entry.path), matcher, context, left, emit) {
+			Ok(count) => left = left.saturating_sub(count),
+			Err(e) if e.kind() == io::ErrorKind::BrokenPipe => return Err(e),
+			Err(_) => continue,
+		};
+	}
+
+	Ok(())
+}
+
+#[test]
+fn test_grep_file() {
+	let path = std::env::temp_dir().join("yaftp_test_grep.txt");
+	fs::write(&path, "a\nerror 1\nb\nc\nd\nERROR 2\ne\n").unwrap();
+	let name = path.to_string_lossy().to_string();
+
+	let grep = |matcher : &Regex , context : u64 , max_matches : u64| {
+		let mut rows : Vec<String> = vec![];
+		let count = grep_file(&path, &name, matcher, context, max_matches, &mut |row| { rows.push(row); true }).unwrap();
+		(rows , count)
+	};
+
+	let matcher = line_matcher("error", false, true).unwrap();
+	let (rows , count) = grep(&matcher, 1, 0);
+	assert_eq!(count , 2);
+	assert_eq!(rows[0] , format!("{}-1-a", name));
+	assert_eq!(rows[1] , format!("{}:2:error 1", name));
+	assert_eq!(rows[2] , format!("{}-3-b", name));
+	assert_eq!(rows[3] , format!("{}-5-d", name));
+	assert_eq!(rows[4] , format!("{}:6:ERROR 2", name));
+
+	let (rows , count) = grep(&line_matcher("error", false, false).unwrap(), 0, 1);
+	assert_eq!(count , 1);
+	assert_eq!(rows.len() , 1);
+
+	/* a receiver gone stops the search at the first row */
+	let mut seen = 0;
+	let ret = grep_file(&path, &name, &matcher, 0, 0, &mut |_| { seen += 1; false });
+	assert_eq!(ret.unwrap_err().kind() , io::ErrorKind::BrokenPipe);
+	assert_eq!(seen , 1);
+
+	fs::write(&path, b"error\x00binary").unwrap();
+	let (rows , _) = grep(&matcher, 0, 0);
+	assert!(rows.is_empty());
+
+	fs::remove_file(&path).unwrap();
+}
@@ -1,11 +1,75 @@
-use futures::{AsyncReadExt, AsyncWriteExt};
-use async_std::{fs::File, io::{self, prelude::SeekExt}, net::{TcpStream}};
+use futures::{AsyncReadExt, AsyncWriteExt, future::Either};
+use async_std::{channel, fs::File, io::{self, prelude::SeekExt}, net::{TcpListener, TcpStream}, task};
 use chrono::DateTime;
 use chrono::offset::Utc;
-use path_absolutize::*;
 
-use crate::{common::{YaftpError, error_retcode}, utils::{calc_md5, check_support_methods}};
-use std::{fs, io::{SeekFrom}, net::Shutdown, path::{Path}};
+use crate::{access::Access, account::{Accounts, PERM_ADMIN, PERM_DELETE, PERM_LIST, PERM_MKDIR, PERM_READ, PERM_RENAME, PERM_WRITE, Session}, archive::{CHUNK_SIZE, COMPRESS_ZSTD, ENTRY_QUOTA, ChannelReader, ChannelWriter, build_archive, extract_archive}, audit::{self, Entry, Trail}, common::{FindFilter, LINK_NOFOLLOW, PUT_FLAG_APPEND, PUT_FLAG_WAIT, STREAM_NARG, YaftpError, error_retcode}, lock::{DEFAULT_LEASE, acquire_write, lock, try_write, unlock}, metrics, registry::Registry, search::{NameMatcher, Walker, find_match, grep_path, line_matcher, type_name}, throttle::{DOWNLOAD, Throttle, UPLOAD}, utils::{calc_md5, check_support_methods, file_id, make_symlink, remove_link, tail_offset}, watch::{WatchEvent, start_watcher}};
+use std::{fs, future::Future, io::SeekFrom, net::{Shutdown, SocketAddr}, path::{Path}, sync::{Arc, RwLock, atomic::{AtomicUsize, Ordering}}, time::{Duration, Instant}};
+
+const TAIL_POLL_INTERVAL : Duration = Duration::from_millis(500);
+
+/* a busy reply is given up after , and at most so many are sent at once */
+const BUSY_REPLY_TIMEOUT : Duration = Duration::from_secs(5);
+const MAX_BUSY_REPLIES : usize = 64;
+
+static BUSY_REPLIES : AtomicUsize = AtomicUsize::new(0);
+
+/* wait after a failed accept , doubled until the max while it keeps failing */
+const ACCEPT_BACKOFF_MIN : Duration = Duration::from_millis(10);
+const ACCEPT_BACKOFF_MAX : Duration = Duration::from_secs(1);
+
+/* accounts of new sessions , a reload replaces them */
+pub type SharedAccounts = Arc<RwLock<Option<Arc<Accounts>>>>;
+
+/*
+	how long a session waits for its peer : the handshake and the command ,
+	each command argument , and every read or write of a data stream once
+	it started (upload , download , replies). zero waits forever .
+*/
+#[derive(Clone, Copy)]
+pub struct Timeouts {
+	pub handshake : Duration,
+	pub argument : Duration,
+	pub data : Duration
+}
+
+pub const DEFAULT_TIMEOUTS : Timeouts = Timeouts {
+	handshake : Duration::from_secs(10),
+	argument : Duration::from_secs(30),
+	data : Duration::from_secs(60)
+};
+
+static TIMEOUTS : RwLock<Timeouts> = RwLock::new(DEFAULT_TIMEOUTS);
+
+pub fn set_timeouts(timeouts : Timeouts) {
+	*TIMEOUTS.write().unwrap() = timeouts;
+}
+
+fn timeouts() -> Timeouts {
+	*TIMEOUTS.read().unwrap()
+}
+
+/* wait for a network io at most limit , the session is closed by its caller on error */
+async fn within<T>(limit : Duration , io : impl Future<Output = std::io::Result<T>>) -> Result<T , YaftpError> {
+
+	let ret = if limit.is_zero() {
+		io.await
+	} else {
+		io::timeout(limit, io).await
+	};
+
+	match ret {
+		Ok(p) => Ok(p),
+		Err(e) if e.kind() == std::io::ErrorKind::TimedOut => {
+			log::warn!("peer idle for {} seconds , close session" , limit.as_secs());
+			Err(YaftpError::TimedOut)
+		},
+		Err(e) => {
+			log::error!("error : {}" , e);
+			Err(YaftpError::UnknownNetwordError)
+		},
+	}
+}
 
 async fn send_reply(stream :&mut  TcpStream , retcode : u8 , narg : u32) -> Result<Vec<u8>, YaftpError> {
 	/*
@@ -20,13 +84,7 @@ async fn send_reply(stream :&mut  TcpStream , retcode : u8 , narg : u32) -> Resu
 
 	args.push(retcode);
 	args.append(&mut b_narg.to_vec());
-	match stream.write_all( &mut args).await{
-		Ok(_) => {},
-		Err(e) => {
-			log::error!("error : {}" , e);
-			return Err(YaftpError::UnknownNetwordError);
-		},
-	};
+	within(timeouts().data, stream.write_all(&args)).await?;
 
 	Ok(args)
 }
@@ -45,17 +103,15 @@ async fn send_argument(stream :&mut  TcpStream , data :&mut Vec<u8>) -> Result<V
 	argument.append(&mut b_size.to_vec());
 	argument.append(data);
 
-	match stream.write_all(&mut argument).await{
-		Ok(_) => {},
-		Err(e) => {
-			log::error!("error : {}" , e);
-			return Err(YaftpError::UnknownNetwordError);
-		},
-	};
+	within(timeouts().data, stream.write_all(&argument)).await?;
 	Ok(argument)
 }
 
 async fn read_argument(stream :&mut  TcpStream , max_size : u64) -> Result<Vec<u8>, YaftpError> {
+	read_argument_within(stream, max_size, timeouts().argument).await
+}
+
+async fn read_argument_within(stream :&mut  TcpStream , max_size : u64 , limit : Duration) -> Result<Vec<u8>, YaftpError> {
 	/*
 	+-----------------+---------------------+
 	| NEXT_ARG_SIZE   |      ARG            |
@@ -64,13 +120,7 @@ async fn read_argument(stream :&mut  TcpStream , max_size : u64) -> Result<Vec<u
 	+-----------------+---------------------+
 	*/
 	let mut argument_size = [0u8;8];
-	match stream.read_exact(&mut argument_size).await{
-		Ok(_) => {},
-		Err(e) => {
-			log::error!("error : {}" , e);
-			return Err(YaftpError::UnknownNetwordError);
-		},
-	};
+	within(limit, stream.read_exact(&mut argument_size)).await?;
 
 	let size = u64::from_be_bytes(argument_size);
 
@@ -81,1373 +131,1931 @@ async fn read_argument(stream :&mut  TcpStream , max_size : u64) -> Result<Vec<u
 
 	let mut arg = vec![0u8;size as usize].into_boxed_slice();
 
-	match stream.read_exact(&mut arg).await{
-		Ok(_) => {},
-		Err(e) => {
-			log::error!("error : {}" , e);
-			return Err(YaftpError::UnknownNetwordError);
-		},
-	};
+	within(limit, stream.read_exact(&mut arg)).await?;
 
 	Ok(arg.to_vec())
 }
 
-async fn c_ls(stream :&mut  TcpStream, narg : u32) -> u8 {
+async fn read_u64_argument(stream :&mut  TcpStream) -> Result<u64, YaftpError> {
 
-	let mut ret = error_retcode(YaftpError::OK);
+	let arg = read_argument(stream, 8).await?;
 
-	if narg != 1 {
-		log::error!("command [{}] arguments count unvalid : {}" , "ls", narg);
-		ret = error_retcode(YaftpError::ArgumentCountError);
+	match arg.try_into(){
+		Ok(p) => Ok(u64::from_be_bytes(p)),
+		Err(_) => Err(YaftpError::ArgumentSizeError),
+	}
+}
 
-		match send_reply(stream, ret , 0).await {
-			Ok(_) => {},
-			Err(e) => {
-				ret = error_retcode(e);
-			},
-		};
+async fn read_u8_argument(stream :&mut  TcpStream) -> Result<u8, YaftpError> {
+
+	let arg = read_argument(stream, 1).await?;
 
-		return ret;
+	if arg.len() != 1 {
+		return Err(YaftpError::ArgumentSizeError);
 	}
 
-	loop {
-		let path = match read_argument(stream, 1024).await{
-			Ok(p) => p,
-			Err(e) => {
-				ret = error_retcode(e);
-				break;
-			}
-		};
+	Ok(arg[0])
+}
 
-		let path = match String::from_utf8(path.to_vec()){
-			Ok(p) => p,
-			Err(_) => {
-				ret = error_retcode(YaftpError::UTF8FormatError);
-				break;
-			},
-		};
+async fn read_string_argument(stream :&mut  TcpStream) -> Result<String, YaftpError> {
+	let arg = read_argument(stream, 1024).await?;
 
-		let paths = match fs::read_dir(path){
-			Ok(p) => p,
-			Err(_) => {
-				ret = error_retcode(YaftpError::ReadFolderFaild);
-				break;
-			},
-		};
+	match String::from_utf8(arg) {
+		Ok(p) => Ok(p),
+		Err(_) => Err(YaftpError::UTF8FormatError),
+	}
+}
 
-		let mut rows1 : Vec<String> = Vec::new();
-		let mut rows2 : Vec<String> = Vec::new();
-		let mut rows3 : Vec<String> = Vec::new();
-		let mut rows4 : Vec<String> = Vec::new();
-		let mut rows5 : Vec<String> = Vec::new();
-
-		for path in paths {
-			let path = path.unwrap();
-			let filename = String::from(path.file_name().to_str().unwrap());
-
-			let mt = path.metadata().unwrap().modified().unwrap();
-			let mt: DateTime<Utc> = mt.into();
-
-			let at = path.metadata().unwrap().accessed().unwrap();
-			let at: DateTime<Utc> = at.into();
-
-			rows1.push(filename);
-
-			let t = path.file_type().unwrap();
-			if t.is_dir() {
-				rows2.push(String::from("folder"));
-			} else if t.is_file() {
-				rows2.push(String::from("file"));
-			} else if t.is_symlink() {
-				rows2.push(String::from("symlink"));
-			} else {
-				rows2.push(String::from("other"));
-			}
-			rows3.push(path.metadata().unwrap().len().to_string());
+/* retcode of a path which can not be read */
+fn io_error(e : std::io::Error) -> YaftpError {
+	match e.kind() {
+		std::io::ErrorKind::PermissionDenied => YaftpError::NoPermission,
+		std::io::ErrorKind::NotFound => YaftpError::NotFound,
+		_ => {
+			log::error!("error : {}" , e);
+			YaftpError::UnknownError
+		},
+	}
+}
 
-			rows4.push(mt.format("%Y-%m-%d %H:%M:%S").to_string());
-			rows5.push(at.format("%Y-%m-%d %H:%M:%S").to_string());
-		}
+/* reply the error of a command refused before its reply , return its retcode */
+async fn fail(stream :&mut  TcpStream , e : YaftpError) -> u8 {
+	let ret = error_retcode(e);
+	let _ = send_reply(stream, ret , 0).await;
+	ret
+}
 
-		if ret == error_retcode(YaftpError::OK) {
+/* reply OK with rows as its arguments , an error after the reply is only returned as a retcode */
+async fn send_rows(stream :&mut  TcpStream , rows : Vec<Vec<u8>>) -> Result<u8 , YaftpError> {
 
-			match send_reply(stream, 0 , rows1.len() as u32).await {
-				Ok(_) => {},
-				Err(_) => {
-					break;
-				},
-			};
+	send_reply(stream, 0 , rows.len() as u32).await?;
 
-			let mut i = 0 ;
-
-			while i < rows1.len(){
-				let full = [rows1[i].clone() , rows2[i].clone(), rows3[i].clone() , rows4[i].clone() , rows5[i].clone()].join("|");
-				match send_argument(stream, &mut full.as_bytes().to_vec()).await {
-					Ok(_) => {},
-					Err(e) => {
-						log::error!("yaftp send argument error");
-						ret = error_retcode(e);
-						break;
-					},
-				};
-				i += 1;
-			}
+	for mut row in rows {
+		if let Err(e) = send_argument(stream, &mut row).await {
+			log::error!("yaftp send argument error");
+			return Ok(error_retcode(e));
 		}
-
-		break;
 	}
 
-	if ret != error_retcode(YaftpError::OK){
+	Ok(error_retcode(YaftpError::OK))
+}
 
-		match send_reply(stream, ret , 0).await {
-			Ok(_) => {},
-			Err(_) => {
-			},
-		};
+async fn c_ls(stream :&mut  TcpStream, narg : u32, session : &Session) -> u8 {
+	match ls(stream, narg, session).await {
+		Ok(p) => p,
+		Err(e) => fail(stream, e).await,
 	}
-
-	ret
-
 }
 
-async fn c_cwd(stream :&mut  TcpStream, narg : u32) -> u8 {
-
-	let mut ret = error_retcode(YaftpError::OK);
+async fn ls(stream :&mut  TcpStream, narg : u32, session : &Session) -> Result<u8 , YaftpError> {
 
-	if narg != 0 {
-		log::error!("command [{}] arguments count unvalid : {}" , "cwd", narg);
-		ret = error_retcode(YaftpError::ArgumentCountError);
-		match send_reply(stream, ret , 0).await {
-			Ok(_) => {},
-			Err(e) => {
-				ret = error_retcode(e);
-			},
-		};
-		return ret;
+	if narg != 1 {
+		log::error!("command [{}] arguments count unvalid : {}" , "ls", narg);
+		return Err(YaftpError::ArgumentCountError);
 	}
 
-	loop {
-		let paths = match  std::env::current_dir(){
-			Ok(p) => p,
-			Err(_) => {
-				ret = error_retcode(YaftpError::ReadCwdFaild);
-				break;
-			},
-		};
+	session.check(PERM_LIST)?;
 
-		let path = match paths.to_str(){
-			Some(p) => p,
-			None => {
-				ret = error_retcode(YaftpError::ReadCwdFaild);
-				break;
-			},
-		};
+	let path = read_string_argument(stream).await?;
+	let path = session.resolve(&path)?;
 
-		if ret == error_retcode(YaftpError::OK) {
+	let paths = fs::read_dir(path).map_err(|_| YaftpError::ReadFolderFaild)?;
 
-			match send_reply(stream, 0 , 1).await {
-				Ok(_) => {},
-				Err(e) => {
-					ret = error_retcode(e);
-					break;
-				},
-			};
+	/*
+	+--------+--------+--------+------------+------------+
+	|  NAME  |  TYPE  |  SIZE  |  MODIFIED  |  ACCESSED  |
+	+--------+--------+--------+------------+------------+
+	*/
+	let mut rows : Vec<Vec<u8>> = Vec::new();
 
-			match send_argument(stream, &mut path.as_bytes().to_vec()).await {
-				Ok(_) => {},
-				Err(e) => {
-					ret = error_retcode(e);
-					break;
-				},
-			};
-		}
+	for path in paths {
+		let path = path.unwrap();
+		let filename = String::from(path.file_name().to_str().unwrap());
 
-		break;
-	}
+		let mt = path.metadata().unwrap().modified().unwrap();
+		let mt: DateTime<Utc> = mt.into();
 
-	if ret != error_retcode(YaftpError::OK){
+		let at = path.metadata().unwrap().accessed().unwrap();
+		let at: DateTime<Utc> = at.into();
 
-		match send_reply(stream, ret , 0).await {
-			Ok(_) => {},
-			Err(e) => {
-				ret = error_retcode(e);
-			},
+		let t = path.file_type().unwrap();
+		let typ = if t.is_dir() {
+			"folder"
+		} else if t.is_file() {
+			"file"
+		} else if t.is_symlink() {
+			"symlink"
+		} else {
+			"other"
 		};
-	}
 
-	ret
+		let row = [filename , typ.to_string() , path.metadata().unwrap().len().to_string() , mt.format("%Y-%m-%d %H:%M:%S").to_string() , at.format("%Y-%m-%d %H:%M:%S").to_string()].join("|");
+		rows.push(row.into_bytes());
+	}
 
+	send_rows(stream, rows).await
 }
 
-async fn c_info(stream :&mut  TcpStream, narg : u32) {
+async fn c_cwd(stream :&mut  TcpStream, narg : u32, session : &Session) -> u8 {
+	match cwd(stream, narg, session).await {
+		Ok(p) => p,
+		Err(e) => fail(stream, e).await,
+	}
+}
 
-	let mut ret = 0u8;
+async fn cwd(stream :&mut  TcpStream, narg : u32, session : &Session) -> Result<u8 , YaftpError> {
 
-	if narg != 1 {
-		log::error!("command [{}] arguments count unvalid : {}" , "ls", narg);
-		ret = error_retcode(YaftpError::ArgumentCountError);
-		match send_reply(stream, ret , 0).await {
-			Ok(_) => {},
-			Err(_) => {},
-		};
-		return;
+	if narg != 0 {
+		log::error!("command [{}] arguments count unvalid : {}" , "cwd", narg);
+		return Err(YaftpError::ArgumentCountError);
 	}
 
-	loop {
-		let path = match read_argument(stream, 1024).await{
-			Ok(p) => p,
-			Err(e) => {
-				ret = error_retcode(e);
-				break;
-			}
-		};
-
-		let path = match String::from_utf8(path.to_vec()){
-			Ok(p) => p,
-			Err(_) => {
-				ret = error_retcode(YaftpError::UTF8FormatError);
-				break;
-			},
-		};
+	let path = session.cwd()?;
 
-		let path = Path::new(path.as_str());
-		let path =  match path.absolutize(){
-			Ok(p) => p,
-			Err(e) => {
-				if e.kind() == std::io::ErrorKind::PermissionDenied {
-					ret = error_retcode(YaftpError::NoPermission);
-				} else if e.kind() == std::io::ErrorKind::NotFound {
-					ret = error_retcode(YaftpError::NotFound);
-				} else {
-					log::error!("error : {}" , e);
-					ret = error_retcode(YaftpError::UnknownError);
-				}
-				break;
-			},
-		};
+	send_rows(stream, vec![path.into_bytes()]).await
+}
 
-		let path = path.to_str().unwrap().to_string();
+async fn c_info(stream :&mut  TcpStream, narg : u32, session : &Session) -> u8 {
+	match info(stream, narg, session).await {
+		Ok(p) => p,
+		Err(e) => fail(stream, e).await,
+	}
+}
 
-		let info = match fs::metadata(path.clone()){
-			Ok(p) => p,
-			Err(e) => {
-				if e.kind() == std::io::ErrorKind::PermissionDenied {
-					ret = error_retcode(YaftpError::NoPermission);
-				} else if e.kind() == std::io::ErrorKind::NotFound {
-					ret = error_retcode(YaftpError::NotFound);
-				} else {
-					log::error!("error : {}" , e);
-					ret = error_retcode(YaftpError::UnknownError);
-				}
-				break;
-			},
-		};
+async fn info(stream :&mut  TcpStream, narg : u32, session : &Session) -> Result<u8 , YaftpError> {
 
-		let t : u8;
+	if narg != 1 && narg != 2 {
+		log::error!("command [{}] arguments count unvalid : {}" , "info", narg);
+		return Err(YaftpError::ArgumentCountError);
+	}
 
-		if info.is_dir() {
-			t = 0;
-		} else if info.is_file() {
-			t = 1;
-		} else {
-			t = 0xff;
-		}
+	session.check(PERM_LIST)?;
 
-		let size : u64 = info.len();
+	let path = read_argument(stream, 1024).await?;
 
-		let mt =  info.modified().unwrap();
-		let mt: DateTime<Utc> = mt.into();
-		let mt : u64 = mt.timestamp().try_into().unwrap();
+	let mut flags = 0u8;
 
-		let at =  info.accessed().unwrap();
-		let at: DateTime<Utc> = at.into();
-		let at : u64 = at.timestamp().try_into().unwrap();
+	if narg == 2 {
+		flags = read_u8_argument(stream).await?;
+	}
 
-		if ret == error_retcode(YaftpError::OK) {
+	let path = String::from_utf8(path).map_err(|_| YaftpError::UTF8FormatError)?;
 
-			match send_reply(stream, 0 , 5).await {
-				Ok(_) => {},
-				Err(e) => {
-					ret = error_retcode(e);
-					break;
-				},
-			};
+	/* without follow , a symlink pointing out of the root is still described */
+	let path = if flags & LINK_NOFOLLOW != 0 {
+		session.resolve_nofollow(&path)?
+	} else {
+		session.resolve(&path)?
+	};
 
-			match send_argument(stream, &mut [t].to_vec()).await {
-				Ok(_) => {},
-				Err(e) => {
-					log::error!("yaftp send argument error");
-					ret = error_retcode(e);
-					break;
-				},
-			};
+	let path = path.to_str().unwrap().to_string();
 
-			let mut size = size.to_be_bytes().to_vec();
+	let info = if flags & LINK_NOFOLLOW != 0 {
+		fs::symlink_metadata(path.clone())
+	} else {
+		fs::metadata(path.clone())
+	};
 
-			match send_argument(stream, &mut size).await {
-				Ok(_) => {},
-				Err(e) => {
-					log::error!("yaftp send argument error");
-					ret = error_retcode(e);
-					break;
-				},
-			};
+	let info = info.map_err(io_error)?;
 
-			let mut mt = mt.to_be_bytes().to_vec();
+	let t : u8 = if info.is_dir() {
+		0
+	} else if info.is_file() {
+		1
+	} else if info.file_type().is_symlink() {
+		2
+	} else {
+		0xff
+	};
 
-			match send_argument(stream, &mut mt).await {
-				Ok(_) => {},
-				Err(e) => {
-					log::error!("yaftp send argument error");
-					ret = error_retcode(e);
-					break;
-				},
-			};
+	let mt =  info.modified().unwrap();
+	let mt: DateTime<Utc> = mt.into();
+	let mt : u64 = mt.timestamp().try_into().unwrap();
 
-			let mut at = at.to_be_bytes().to_vec();
+	let at =  info.accessed().unwrap();
+	let at: DateTime<Utc> = at.into();
+	let at : u64 = at.timestamp().try_into().unwrap();
 
-			match send_argument(stream, &mut at).await {
-				Ok(_) => {},
-				Err(e) => {
-					log::error!("yaftp send argument error");
-					ret = error_retcode(e);
-					break;
-				},
-			};
+	/* the path itself may be a link even if its target was described */
+	let target = match fs::read_link(&path){
+		Ok(p) if p.is_absolute() => session.display(&p),
+		Ok(p) => p.to_string_lossy().to_string(),
+		Err(_) => String::new(),
+	};
 
-			match send_argument(stream, &mut path.as_bytes().to_vec()).await {
-				Ok(_) => {},
-				Err(e) => {
-					log::error!("yaftp send argument error");
-					ret = error_retcode(e);
-					break;
-				},
-			};
-		}
+	send_rows(stream, vec![
+		vec![t],
+		info.len().to_be_bytes().to_vec(),
+		mt.to_be_bytes().to_vec(),
+		at.to_be_bytes().to_vec(),
+		session.display(Path::new(&path)).into_bytes(),
+		target.into_bytes()
+	]).await
+}
 
-		break;
+async fn c_cp(stream :&mut  TcpStream, narg : u32, session : &Session) -> u8 {
+	match cp(stream, narg, session).await {
+		Ok(p) => p,
+		Err(e) => fail(stream, e).await,
 	}
+}
 
-	if ret != error_retcode(YaftpError::OK){
+async fn cp(stream :&mut  TcpStream, narg : u32, session : &Session) -> Result<u8 , YaftpError> {
 
-		match send_reply(stream, ret , 0).await {
-			Ok(_) => {},
-			Err(_) => {
-			},
-		};
+	if narg != 2 && narg != 3 {
+		log::error!("command [{}] arguments count unvalid : {}" , "cp", narg);
+		return Err(YaftpError::ArgumentCountError);
 	}
 
-}
+	session.check(PERM_READ | PERM_WRITE)?;
 
-async fn c_cp(stream :&mut  TcpStream, narg : u32){
+	let srcpath = read_string_argument(stream).await?;
+	let targetpath = read_string_argument(stream).await?;
+	let targetpath = session.resolve(&targetpath)?;
 
-	let mut ret = 0u8;
+	let mut flags = 0u8;
 
-	if narg != 2 {
-		log::error!("command [{}] arguments count unvalid : {}" , "cp", narg);
-		ret = error_retcode(YaftpError::ArgumentCountError);
-		match send_reply(stream, ret , 0).await {
-			Ok(_) => {},
-			Err(_) => {},
-		};
-		return;
+	if narg == 3 {
+		flags = read_u8_argument(stream).await?;
 	}
-	loop {
-		let path = match read_argument(stream, 1024).await{
-			Ok(p) => p,
-			Err(e) => {
-				ret = error_retcode(e);
-				break;
-			}
-		};
-
-		let srcpath = match String::from_utf8(path.to_vec()){
-			Ok(p) => p,
-			Err(_) => {
-				ret = error_retcode(YaftpError::UTF8FormatError);
-				break;
-			},
-		};
 
+	/* no follow , a source link pointing out of the root can still be copied */
+	let srcpath = if flags & LINK_NOFOLLOW != 0 {
+		session.resolve_nofollow(&srcpath)?
+	} else {
+		session.resolve(&srcpath)?
+	};
 
-		let srcpath = Path::new(srcpath.as_str());
-		let srcpath =  match srcpath.absolutize(){
-			Ok(p) => p,
-			Err(e) => {
-				if e.kind() == std::io::ErrorKind::PermissionDenied {
-					ret = error_retcode(YaftpError::NoPermission);
-				} else if e.kind() == std::io::ErrorKind::NotFound {
-					ret = error_retcode(YaftpError::NotFound);
-				} else {
-					log::error!("error : {}" , e);
-					ret = error_retcode(YaftpError::UnknownError);
-				}
-				break;
-			},
-		};
+	let _guard = try_write(&targetpath, 0)?;
 
-		let path = match read_argument(stream, 1024).await{
-			Ok(p) => p,
-			Err(e) => {
-				ret = error_retcode(e);
-				break;
-			}
-		};
+	/* no follow , a symlink source is copied as a new link with the same target */
+	let link = match fs::symlink_metadata(&srcpath){
+		Ok(p) => flags & LINK_NOFOLLOW != 0 && p.file_type().is_symlink(),
+		Err(_) => false,
+	};
 
-		let targetpath = match String::from_utf8(path.to_vec()){
-			Ok(p) => p,
-			Err(_) => {
-				ret = error_retcode(YaftpError::UTF8FormatError);
-				break;
-			},
-		};
+	/* the copy is counted in the quotas of the target , less what it replaces */
+	let size = match fs::metadata(&srcpath){
+		Ok(p) if !link => p.len(),
+		_ => 0,
+	};
 
-		let targetpath = Path::new(targetpath.as_str());
-		let targetpath =  match targetpath.absolutize(){
-			Ok(p) => p,
-			Err(e) => {
-				if e.kind() == std::io::ErrorKind::PermissionDenied {
-					ret = error_retcode(YaftpError::NoPermission);
-				} else if e.kind() == std::io::ErrorKind::NotFound {
-					ret = error_retcode(YaftpError::NotFound);
-				} else {
-					log::error!("error : {}" , e);
-					ret = error_retcode(YaftpError::UnknownError);
-				}
-				break;
-			},
-		};
+	let (old_len , exists) = match fs::symlink_metadata(&targetpath){
+		Ok(p) => (p.len() , true),
+		Err(_) => (0 , false),
+	};
 
-		match fs::copy(srcpath, targetpath){
-			Ok(p) => p,
-			Err(e) => {
-				if e.kind() == std::io::ErrorKind::PermissionDenied {
-					ret = error_retcode(YaftpError::NoPermission);
-				} else if e.kind() == std::io::ErrorKind::NotFound {
-					ret = error_retcode(YaftpError::NotFound);
-				} else {
-					log::error!("error : {}" , e);
-					ret = error_retcode(YaftpError::UnknownError);
-				}
-				break;
-			},
-		};
+	let charge = session.charge(&targetpath);
+	charge.refresh().await;
 
-		if ret == error_retcode(YaftpError::OK){
-			match send_reply(stream, 0 , 0).await {
-				Ok(_) => {},
-				Err(_) => {
-				},
-			};
-		}
+	charge.reserve(size.saturating_sub(old_len), !exists as u64)?;
 
-		break;
-	}
+	charge.release(old_len.saturating_sub(size), 0);
 
-	if ret != error_retcode(YaftpError::OK){
+	let result = if link {
+		fs::read_link(&srcpath).and_then(|p| make_symlink(&p, &targetpath).map(|_| 0))
+	} else {
+		fs::copy(srcpath, &targetpath)
+	};
 
-		match send_reply(stream, ret , 0).await {
-			Ok(_) => {},
-			Err(_) => {
-			},
-		};
-	}
+	match result {
+		Ok(p) => session.transferred(p),
+		Err(e) => {
+			session.changed(&targetpath);
+			return Err(io_error(e));
+		},
+	};
 
+	let _ = send_reply(stream, 0 , 0).await;
 
+	Ok(error_retcode(YaftpError::OK))
 }
 
-async fn c_mkd(stream :&mut  TcpStream, narg : u32){
+async fn c_mkd(stream :&mut  TcpStream, narg : u32, session : &Session) -> u8 {
+	match mkd(stream, narg, session).await {
+		Ok(p) => p,
+		Err(e) => fail(stream, e).await,
+	}
+}
 
-	let mut ret = 0u8;
+async fn mkd(stream :&mut  TcpStream, narg : u32, session : &Session) -> Result<u8 , YaftpError> {
 
 	if narg != 1 {
 		log::error!("command [{}] arguments count unvalid : {}" , "mkd", narg);
-		ret = error_retcode(YaftpError::ArgumentCountError);
-		match send_reply(stream, ret , 0).await {
-			Ok(_) => {},
-			Err(_) => {},
-		};
-		return;
+		return Err(YaftpError::ArgumentCountError);
 	}
-	loop {
-		let path = match read_argument(stream, 1024).await{
-			Ok(p) => p,
-			Err(e) => {
-				ret = error_retcode(e);
-				break;
-			}
-		};
-
-		let path = match String::from_utf8(path.to_vec()){
-			Ok(p) => p,
-			Err(_) => {
-				ret = error_retcode(YaftpError::ArgumentError);
-				break;
-			},
-		};
 
+	session.check(PERM_MKDIR)?;
 
-		let path = Path::new(path.as_str());
-		let path =  match path.absolutize(){
-			Ok(p) => p,
-			Err(e) => {
-				if e.kind() == std::io::ErrorKind::PermissionDenied {
-					ret = error_retcode(YaftpError::NoPermission);
-				} else if e.kind() == std::io::ErrorKind::NotFound {
-					ret = error_retcode(YaftpError::NotFound);
-				} else {
-					log::error!("error : {}" , e);
-					ret = error_retcode(YaftpError::UnknownError);
-				}
-				break;
-			},
-		};
+	let path = read_argument(stream, 1024).await?;
+	let path = String::from_utf8(path).map_err(|_| YaftpError::ArgumentError)?;
+	let path = session.resolve(&path)?;
 
+	fs::create_dir_all(path).map_err(|e| match e.kind() {
+		std::io::ErrorKind::AlreadyExists => YaftpError::NotFound,
+		_ => io_error(e),
+	})?;
 
-		match fs::create_dir_all(path){
-			Ok(p) => p,
-			Err(e) => {
-				if e.kind() == std::io::ErrorKind::PermissionDenied {
-					ret = error_retcode(YaftpError::NoPermission);
-				} else if e.kind() == std::io::ErrorKind::AlreadyExists {
-					ret = error_retcode(YaftpError::NotFound);
-				} else {
-					log::error!("error : {}" , e);
-					ret = error_retcode(YaftpError::UnknownError);
-				}
-				break;
-			},
-		};
+	let _ = send_reply(stream, 0 , 0).await;
 
-		if ret == error_retcode(YaftpError::OK){
-			match send_reply(stream, 0 , 0).await {
-				Ok(_) => {},
-				Err(_) => {
-				},
-			};
-		}
+	Ok(error_retcode(YaftpError::OK))
+}
 
-		break;
+async fn c_mv(stream :&mut  TcpStream, narg : u32, session : &Session) -> u8 {
+	match mv(stream, narg, session).await {
+		Ok(p) => p,
+		Err(e) => fail(stream, e).await,
 	}
+}
 
-	if ret != error_retcode(YaftpError::OK){
+async fn mv(stream :&mut  TcpStream, narg : u32, session : &Session) -> Result<u8 , YaftpError> {
 
-		match send_reply(stream, ret , 0).await {
-			Ok(_) => {},
-			Err(_) => {
-			},
-		};
+	if narg != 2 {
+		log::error!("command [{}] arguments count unvalid : {}" , "mv", narg);
+		return Err(YaftpError::ArgumentCountError);
 	}
 
+	session.check(PERM_RENAME)?;
 
-}
+	let srcpath = read_string_argument(stream).await?;
+	let srcpath = session.resolve(&srcpath)?;
 
-async fn c_mv(stream :&mut  TcpStream, narg : u32){
+	if session.is_root(&srcpath) {
+		return Err(YaftpError::NoPermission);
+	}
 
-	let mut ret = 0u8;
+	let targetpath = read_string_argument(stream).await?;
+	let targetpath = session.resolve(&targetpath)?;
 
-	if narg != 2 {
-		log::error!("command [{}] arguments count unvalid : {}" , "mv", narg);
-		ret = error_retcode(YaftpError::ArgumentCountError);
-		match send_reply(stream, ret , 0).await {
-			Ok(_) => {},
-			Err(_) => {},
-		};
-		return;
-	}
-	loop {
-		let path = match read_argument(stream, 1024).await{
-			Ok(p) => p,
-			Err(e) => {
-				ret = error_retcode(e);
-				break;
-			}
-		};
+	/* the source is removed once copied , so both sides are written */
+	let _src_guard = try_write(&srcpath, 0)?;
+	let _guard = try_write(&targetpath, 0)?;
 
-		let srcpath = match String::from_utf8(path.to_vec()){
-			Ok(p) => p,
-			Err(_) => {
-				ret = error_retcode(YaftpError::UTF8FormatError);
-				break;
-			},
-		};
+	/* the moved file is counted in the target quotas it enters , the source ones count the disk again */
+	let size = match fs::metadata(&srcpath){
+		Ok(p) => p.len(),
+		Err(_) => 0,
+	};
 
+	let charge = session.charge_move(&srcpath, &targetpath);
+	charge.refresh().await;
 
-		let srcpath = Path::new(srcpath.as_str());
-		let srcpath =  match srcpath.absolutize(){
-			Ok(p) => p,
-			Err(e) => {
-				if e.kind() == std::io::ErrorKind::PermissionDenied {
-					ret = error_retcode(YaftpError::NoPermission);
-				} else if e.kind() == std::io::ErrorKind::NotFound {
-					ret = error_retcode(YaftpError::NotFound);
-				} else {
-					log::error!("error : {}" , e);
-					ret = error_retcode(YaftpError::UnknownError);
-				}
-				break;
-			},
-		};
+	charge.reserve(size, 1)?;
 
-		let path = match read_argument(stream, 1024).await{
-			Ok(p) => p,
-			Err(e) => {
-				ret = error_retcode(e);
-				break;
-			}
-		};
+	session.changed(&srcpath);
+	session.changed(&targetpath);
 
-		let targetpath = match String::from_utf8(path.to_vec()){
-			Ok(p) => p,
-			Err(_) => {
-				ret = error_retcode(YaftpError::UTF8FormatError);
-				break;
-			},
-		};
+	fs::copy(srcpath.clone(), targetpath).map_err(io_error)?;
+	fs::remove_file(srcpath).map_err(io_error)?;
 
-		let targetpath = Path::new(targetpath.as_str());
-		let targetpath =  match targetpath.absolutize(){
-			Ok(p) => p,
-			Err(e) => {
-				if e.kind() == std::io::ErrorKind::PermissionDenied {
-					ret = error_retcode(YaftpError::NoPermission);
-				} else if e.kind() == std::io::ErrorKind::NotFound {
-					ret = error_retcode(YaftpError::NotFound);
-				} else {
-					log::error!("error : {}" , e);
-					ret = error_retcode(YaftpError::UnknownError);
-				}
-				break;
-			},
-		};
+	let _ = send_reply(stream, 0 , 0).await;
 
-		match fs::copy(srcpath.clone(), targetpath){
-			Ok(p) => p,
-			Err(e) => {
-				if e.kind() == std::io::ErrorKind::PermissionDenied {
-					ret = error_retcode(YaftpError::NoPermission);
-				} else if e.kind() == std::io::ErrorKind::NotFound {
-					ret = error_retcode(YaftpError::NotFound);
-				} else {
-					log::error!("error : {}" , e);
-					ret = error_retcode(YaftpError::UnknownError);
-				}
-				break;
-			},
-		};
+	Ok(error_retcode(YaftpError::OK))
+}
 
-		match fs::remove_file(srcpath){
-			Ok(p) => p,
-			Err(e) => {
-				if e.kind() == std::io::ErrorKind::PermissionDenied {
-					ret = error_retcode(YaftpError::NoPermission);
-				} else if e.kind() == std::io::ErrorKind::NotFound {
-					ret = error_retcode(YaftpError::NotFound);
-				} else {
-					log::error!("error : {}" , e);
-					ret = error_retcode(YaftpError::UnknownError);
-				}
-				break;
-			},
-		};
+async fn c_rm(stream :&mut  TcpStream, narg : u32, session : &Session) -> u8 {
+	match rm(stream, narg, session).await {
+		Ok(p) => p,
+		Err(e) => fail(stream, e).await,
+	}
+}
 
-		if ret == error_retcode(YaftpError::OK){
-			match send_reply(stream, 0 , 0).await {
-				Ok(_) => {},
-				Err(_) => {
-				},
-			};
-		}
+async fn rm(stream :&mut  TcpStream, narg : u32, session : &Session) -> Result<u8 , YaftpError> {
 
-		break;
+	if narg != 1 && narg != 2 {
+		log::error!("command [{}] arguments count unvalid : {}" , "rm", narg);
+		return Err(YaftpError::ArgumentCountError);
 	}
 
-	if ret != error_retcode(YaftpError::OK){
+	session.check(PERM_DELETE)?;
 
-		match send_reply(stream, ret , 0).await {
-			Ok(_) => {},
-			Err(_) => {
-			},
-		};
+	let path = read_argument(stream, 1024).await?;
+
+	let mut flags = 0u8;
+
+	if narg == 2 {
+		flags = read_u8_argument(stream).await?;
 	}
 
+	let path = String::from_utf8(path).map_err(|_| YaftpError::UTF8FormatError)?;
 
-}
+	let path = if flags & LINK_NOFOLLOW != 0 {
+		session.resolve_nofollow(&path)?
+	} else {
+		session.resolve(&path)?
+	};
+
+	/* a user can empty its root but never remove it */
+	if session.is_root(&path) {
+		return Err(YaftpError::NoPermission);
+	}
 
-async fn c_rm(stream :&mut  TcpStream, narg : u32){
+	let info = if flags & LINK_NOFOLLOW != 0 {
+		fs::symlink_metadata(path.clone())
+	} else {
+		fs::metadata(path.clone())
+	};
 
-	let mut ret = 0u8;
+	let info = info.map_err(io_error)?;
 
-	if narg != 1 {
-		log::error!("command [{}] arguments count unvalid : {}" , "rm", narg);
-		ret = error_retcode(YaftpError::ArgumentCountError);
-		match send_reply(stream, ret , 0).await {
-			Ok(_) => {},
-			Err(_) => {},
-		};
-		return;
+	session.changed(&path);
+
+	if info.file_type().is_symlink(){
+		remove_link(&path).map_err(io_error)?;
+	} else if info.is_dir(){
+		fs::remove_dir_all(path).map_err(io_error)?;
+	} else if info.is_file(){
+		fs::remove_file(path).map_err(io_error)?;
+	} else {
+		return Err(YaftpError::UnknownError);
 	}
-	loop {
-		let path = match read_argument(stream, 1024).await{
-			Ok(p) => p,
-			Err(e) => {
-				ret = error_retcode(e);
-				break;
-			}
-		};
 
-		let path = match String::from_utf8(path.to_vec()){
-			Ok(p) => p,
-			Err(_) => {
-				ret = error_retcode(YaftpError::UTF8FormatError);
-				break;
-			},
-		};
+	let _ = send_reply(stream, 0 , 0).await;
 
+	Ok(error_retcode(YaftpError::OK))
+}
 
-		let path = Path::new(path.as_str());
-		let path =  match path.absolutize(){
-			Ok(p) => p,
-			Err(e) => {
-				if e.kind() == std::io::ErrorKind::PermissionDenied {
-					ret = error_retcode(YaftpError::NoPermission);
-				} else if e.kind() == std::io::ErrorKind::NotFound {
-					ret = error_retcode(YaftpError::NotFound);
-				} else {
-					log::error!("error : {}" , e);
-					ret = error_retcode(YaftpError::UnknownError);
-				}
-				break;
-			},
-		};
+async fn c_put(stream :&mut  TcpStream, narg : u32, session : &Session) -> u8 {
+	match put(stream, narg, session).await {
+		Ok(p) => p,
+		Err(e) => fail(stream, e).await,
+	}
+}
 
-		let info = match fs::metadata(path.clone()){
-			Ok(p) => p,
-			Err(e) => {
-				if e.kind() == std::io::ErrorKind::PermissionDenied {
-					ret = error_retcode(YaftpError::NoPermission);
-				} else if e.kind() == std::io::ErrorKind::NotFound {
-					ret = error_retcode(YaftpError::NotFound);
-				} else {
-					log::error!("error : {}" , e);
-					ret = error_retcode(YaftpError::UnknownError);
-				}
-				break;
-			},
-		};
+/* the reply comes after the whole file , an upload error is told in it */
+async fn put(stream :&mut  TcpStream, narg : u32, session : &Session) -> Result<u8 , YaftpError> {
 
-		if info.is_dir(){
-			match fs::remove_dir_all(path){
-				Ok(p) => p,
-				Err(e) => {
-					if e.kind() == std::io::ErrorKind::PermissionDenied {
-						ret = error_retcode(YaftpError::NoPermission);
-					} else if e.kind() == std::io::ErrorKind::NotFound {
-						ret = error_retcode(YaftpError::NotFound);
-					} else {
-						log::error!("error : {}" , e);
-						ret = error_retcode(YaftpError::UnknownError);
-					}
-					break;
-				},
-			};
-		} else if info.is_file(){
-			match fs::remove_file(path){
-				Ok(p) => p,
-				Err(e) => {
-					if e.kind() == std::io::ErrorKind::PermissionDenied {
-						ret = error_retcode(YaftpError::NoPermission);
-					} else if e.kind() == std::io::ErrorKind::NotFound {
-						ret = error_retcode(YaftpError::NotFound);
-					} else {
-						log::error!("error : {}" , e);
-						ret = error_retcode(YaftpError::UnknownError);
-					}
-					break;
-				},
-			};
-		} else{
-			ret = error_retcode(YaftpError::UnknownError);
-			break;
-		}
+	if !(3..=5).contains(&narg) {
+		log::error!("command [{}] arguments count unvalid : {}" , "put", narg);
+		return Err(YaftpError::ArgumentCountError);
+	}
 
+	session.check(PERM_WRITE)?;
 
+	let path = read_argument(stream, 1024).await?;
+	let start_pos = read_argument(stream, 8).await?;
+	let start_pos = u64::from_be_bytes(start_pos.try_into().unwrap());
 
-		if ret == error_retcode(YaftpError::OK){
-			match send_reply(stream, 0 , 0).await {
-				Ok(_) => {},
-				Err(_) => {
-				},
-			};
-		}
+	let mut flags = 0u8;
 
-		break;
+	if narg >= 4 {
+		flags = read_u8_argument(stream).await?;
 	}
 
-	if ret != error_retcode(YaftpError::OK){
+	let mut token = 0u64;
 
-		match send_reply(stream, ret , 0).await {
-			Ok(_) => {},
-			Err(_) => {
-			},
-		};
+	if narg == 5 {
+		token = read_u64_argument(stream).await?;
 	}
 
+	let append = flags & PUT_FLAG_APPEND != 0;
 
-}
+	if append && start_pos != 0 {
+		return Err(YaftpError::StartPosError);
+	}
 
-async fn c_put(stream :&mut  TcpStream, narg : u32){
+	let path = String::from_utf8(path).map_err(|_| YaftpError::UTF8FormatError)?;
+	let path = session.resolve(&path)?;
+	let path = path.to_str().unwrap().to_string();
 
-	let mut ret = 0u8;
+	/* held until the upload ends */
+	let _guard = acquire_write(Path::new(&path), token, flags & PUT_FLAG_WAIT != 0).await?;
 
-	if narg != 3 {
-		log::error!("command [{}] arguments count unvalid : {}" , "put", narg);
-		ret = error_retcode(YaftpError::ArgumentCountError);
-		match send_reply(stream, ret , 0).await {
-			Ok(_) => {},
-			Err(_) => {},
-		};
-		return;
-	}
-	loop {
-		let path = match read_argument(stream, 1024).await{
-			Ok(p) => p,
-			Err(e) => {
-				ret = error_retcode(e);
-				break;
-			}
-		};
+	/*
+	+-----------------+---------------------+
+	| NEXT_ARG_SIZE   |      ARG            |
+	+-----------------+---------------------+
+	|     8(u64)      |	     Variable       |
+	+-----------------+---------------------+
+	*/
+	let mut argument_size = [0u8;8];
+	within(timeouts().argument, stream.read_exact(&mut argument_size)).await?;
 
-		let start_pos = match read_argument(stream, 8).await{
-			Ok(p) => p,
-			Err(e) => {
-				ret = error_retcode(e);
-				break;
-			}
-		};
+	let size = u64::from_be_bytes(argument_size);
+	session.expect(size);
 
-		let start_pos = u64::from_be_bytes(start_pos.try_into().unwrap());
+	/* bytes already on disk which the upload overwrites are not counted again */
+	let (old_len , exists) = match fs::metadata(&path){
+		Ok(p) => (p.len() , true),
+		Err(_) => (0 , false),
+	};
 
-		let path = match String::from_utf8(path.to_vec()){
-			Ok(p) => p,
-			Err(_) => {
-				ret = error_retcode(YaftpError::UTF8FormatError);
-				break;
-			},
-		};
+	let mut offset = if append { old_len } else { start_pos };
 
+	let charge = session.charge(Path::new(&path));
+	charge.refresh().await;
 
-		let path = Path::new(path.as_str());
-		let path =  match path.absolutize(){
-			Ok(p) => p,
-			Err(e) => {
-				if e.kind() == std::io::ErrorKind::PermissionDenied {
-					ret = error_retcode(YaftpError::NoPermission);
-				} else if e.kind() == std::io::ErrorKind::NotFound {
-					ret = error_retcode(YaftpError::NotFound);
-				} else {
-					log::error!("error : {}" , e);
-					ret = error_retcode(YaftpError::UnknownError);
-				}
-				break;
-			}
-		};
+	/* refuse an upload which can not fit before the file is touched */
+	charge.check((offset + size).saturating_sub(old_len), !exists as u64)?;
 
-		let path = path.to_str().unwrap().to_string();
+	let f = if append {
+		async_std::fs::OpenOptions::new().append(true).create(true).open(path).await
+	} else if start_pos == 0 {
+		async_std::fs::File::create(path).await
+	} else {
+		async_std::fs::OpenOptions::new().write(true).read(true).open(path).await
+	};
 
-		let mut f : File;
+	let mut f : File = f.map_err(io_error)?;
 
-		if start_pos == 0{
-			f = match async_std::fs::File::create(path).await{
-				Ok(p) => p,
-				Err(e) => {
-					if e.kind() == std::io::ErrorKind::PermissionDenied {
-						ret = error_retcode(YaftpError::NoPermission);
-					} else if e.kind() == std::io::ErrorKind::NotFound {
-						ret = error_retcode(YaftpError::NotFound);
-					} else {
-						log::error!("error : {}" , e);
-						ret = error_retcode(YaftpError::UnknownError);
-					}
-					break;
-				}
-			};
-		} else {
-			f = match async_std::fs::OpenOptions::new().write(true).read(true).open(path).await{
-				Ok(p) => p,
-				Err(e) => {
-					if e.kind() == std::io::ErrorKind::PermissionDenied {
-						ret = error_retcode(YaftpError::NoPermission);
-					} else if e.kind() == std::io::ErrorKind::NotFound {
-						ret = error_retcode(YaftpError::NotFound);
-					} else {
-						log::error!("error : {}" , e);
-						ret = error_retcode(YaftpError::UnknownError);
-					}
-					break;
-				}
-			};
-		}
+	charge.reserve(0, !exists as u64)?;
 
+	/* a new upload replaced the old bytes */
+	let mut high = old_len;
 
+	if !append && start_pos == 0 {
+		charge.release(old_len, 0);
+		high = 0;
+	}
 
+	/* append mode writes at the end of the file , no seek needed */
+	if !append {
 		let mut pos = SeekFrom::Start(start_pos);
 
 		if start_pos == f.metadata().await.unwrap().len() {
 			pos = SeekFrom::End(0);
 		}
 
-		match f.seek(pos).await{
-			Ok(_) => {},
-			Err(_) => {
-				ret = error_retcode(YaftpError::StartPosError);
+		f.seek(pos).await.map_err(|_| YaftpError::StartPosError)?;
+	}
+
+	let throttle = Throttle::server(UPLOAD);
+	let mut buf = [0;2048];
+	let mut sum = 0u64;
+	let mut received = Ok(());
+	loop{
+		/* a stalled or closed upload keeps what was written , the client resume it */
+		let a = match within(timeouts().data, stream.read(&mut buf)).await{
+			Ok(0) => {
+				received = Err(YaftpError::UnknownNetwordError);
 				break;
 			},
-		};
-
-		/*
-		+-----------------+---------------------+
-		| NEXT_ARG_SIZE   |      ARG            |
-		+-----------------+---------------------+
-		|     8(u64)      |	     Variable       |
-		+-----------------+---------------------+
-		*/
-		let mut argument_size = [0u8;8];
-		match stream.read_exact(&mut argument_size).await{
-			Ok(_) => {},
-			Err(_) => {
-				ret = error_retcode(YaftpError::UnknownNetwordError);
+			Ok(p) => p,
+			Err(e) => {
+				received = Err(e);
 				break;
 			},
 		};
 
-		let size = u64::from_be_bytes(argument_size);
-
-		let mut buf = [0;2048];
-		let mut sum = 0u64;
-		loop{
-			let a = match stream.read(&mut buf).await{
-				Ok(p) => p,
-				Err(_) => {
-					ret = error_retcode(YaftpError::UnknownNetwordError);
-					break;
-				},
-			};
+		throttle.take(a).await;
 
-			match f.write_all(&buf[..a]).await{
-				Ok(p) => p,
-				Err(_) => {
-					ret = error_retcode(YaftpError::WriteFileError);
-					break;
-				},
-			};
+		if let Err(e) = charge.reserve((offset + a as u64).saturating_sub(high), 0) {
+			received = Err(e);
+			break;
+		}
 
-			sum += a as u64;
+		offset += a as u64;
+		high = std::cmp::max(high, offset);
 
-			if sum >= size {
-				break
-			}
+		if f.write_all(&buf[..a]).await.is_err() {
+			received = Err(YaftpError::WriteFileError);
+			break;
 		}
 
-		f.close().await.unwrap();
+		session.transferred(a as u64);
+		metrics::transferred(UPLOAD, a as u64);
+		sum += a as u64;
 
-		if ret == error_retcode(YaftpError::OK){
-			match send_reply(stream, 0 , 0).await {
-				Ok(_) => {},
-				Err(_) => {
-				},
-			};
+		if sum >= size {
+			break
 		}
-
-		break;
 	}
 
-	if ret != error_retcode(YaftpError::OK){
+	f.close().await.unwrap();
 
-		match send_reply(stream, ret , 0).await {
-			Ok(_) => {},
-			Err(_) => {
-			},
-		};
-	}
+	received?;
 
+	let _ = send_reply(stream, 0 , 0).await;
 
+	Ok(error_retcode(YaftpError::OK))
 }
 
-async fn c_get(stream :&mut  TcpStream, narg : u32){
+async fn c_get(stream :&mut  TcpStream, narg : u32, session : &Session) -> u8 {
+	match get(stream, narg, session).await {
+		Ok(p) => p,
+		Err(e) => fail(stream, e).await,
+	}
+}
 
-	let mut ret = 0u8;
+/* the file follows the reply , an error while sending it ends the connection */
+async fn get(stream :&mut  TcpStream, narg : u32, session : &Session) -> Result<u8 , YaftpError> {
 
 	if narg != 2 {
 		log::error!("command [{}] arguments count unvalid : {}" , "get", narg);
-		ret = error_retcode(YaftpError::ArgumentCountError);
-		match send_reply(stream, ret , 0).await {
-			Ok(_) => {},
-			Err(_) => {},
-		};
-		return;
+		return Err(YaftpError::ArgumentCountError);
 	}
-	loop {
-		let path = match read_argument(stream, 1024).await{
-			Ok(p) => p,
-			Err(e) => {
-				ret = error_retcode(e);
-				break;
-			}
-		};
-
-		let start_pos = match read_argument(stream, 8).await{
-			Ok(p) => p,
-			Err(e) => {
-				ret = error_retcode(e);
-				break;
-			}
-		};
-
-		let start_pos = u64::from_be_bytes(start_pos.try_into().unwrap());
 
-		let path = match String::from_utf8(path.to_vec()){
-			Ok(p) => p,
-			Err(_) => {
-				ret = error_retcode(YaftpError::UTF8FormatError);
-				break;
-			},
-		};
+	session.check(PERM_READ)?;
 
+	let path = read_argument(stream, 1024).await?;
+	let start_pos = read_argument(stream, 8).await?;
+	let start_pos = u64::from_be_bytes(start_pos.try_into().unwrap());
 
-		let path = Path::new(path.as_str());
-		let path =  match path.absolutize(){
-			Ok(p) => p,
-			Err(e) => {
-				if e.kind() == std::io::ErrorKind::PermissionDenied {
-					ret = error_retcode(YaftpError::NoPermission);
-				} else if e.kind() == std::io::ErrorKind::NotFound {
-					ret = error_retcode(YaftpError::NotFound);
-				} else {
-					log::error!("error : {}" , e);
-					ret = error_retcode(YaftpError::UnknownError);
-				}
-				break;
-			}
-		};
+	let path = String::from_utf8(path).map_err(|_| YaftpError::UTF8FormatError)?;
+	let path = session.resolve(&path)?;
+	let path = path.to_str().unwrap().to_string();
 
-		let path = path.to_str().unwrap().to_string();
+	let mut f = async_std::fs::File::open(path).await.map_err(io_error)?;
 
-		let mut f = match async_std::fs::File::open(path).await{
-			Ok(p) => p,
-			Err(e) => {
-				if e.kind() == std::io::ErrorKind::PermissionDenied {
-					ret = error_retcode(YaftpError::NoPermission);
-				} else if e.kind() == std::io::ErrorKind::NotFound {
-					ret = error_retcode(YaftpError::NotFound);
-				} else {
-					log::error!("error : {}" , e);
-					ret = error_retcode(YaftpError::UnknownError);
-				}
-				break;
-			}
-		};
+	f.seek(SeekFrom::Start(start_pos)).await.map_err(|_| YaftpError::StartPosError)?;
 
-		match f.seek(SeekFrom::Start(start_pos)).await{
-			Ok(_) => {},
-			Err(_) => {
-				ret = error_retcode(YaftpError::StartPosError);
-				break;
-			},
-		};
+	let _ = send_reply(stream, 0 , 1).await;
 
-		match send_reply(stream, 0 , 1).await {
-			Ok(_) => {},
-			Err(_) => {
-			},
-		};
+	/*
+	+-----------------+---------------------+
+	| NEXT_ARG_SIZE   |       ARG           |
+	+-----------------+---------------------+
+	|     8(u64)      |    Variable         |
+	+-----------------+---------------------+
+	*/
 
-		/*
-		+-----------------+---------------------+
-		| NEXT_ARG_SIZE   |       ARG           |
-		+-----------------+---------------------+
-		|     8(u64)      |    Variable         |
-		+-----------------+---------------------+
-		*/
+	let size = f.metadata().await.unwrap().len() - start_pos;
+	session.expect(size);
 
-		let size = f.metadata().await.unwrap().len() - start_pos;
+	let mut ret = error_retcode(YaftpError::OK);
 
-		match stream.write_all(&size.to_be_bytes().to_vec()).await{
-			Ok(_) => {},
-			Err(_) => {
-				ret = error_retcode(YaftpError::UnknownNetwordError);
-				break;
-			}
-		};
+	if let Err(e) = within(timeouts().data, stream.write_all(&size.to_be_bytes())).await {
+		return Ok(error_retcode(e));
+	}
 
-		match io::copy(&mut f , stream).await{
-			Ok(_) => {},
+	/* a client which stops reading is given up after the data timeout */
+	let throttle = Throttle::server(DOWNLOAD);
+	let mut buf = vec![0u8;CHUNK_SIZE];
+	loop {
+		let n = match f.read(&mut buf).await{
+			Ok(p) => p,
 			Err(e) => {
-				if e.kind() == std::io::ErrorKind::PermissionDenied {
-					ret = error_retcode(YaftpError::NoPermission);
-				} else if e.kind() == std::io::ErrorKind::NotFound {
-					ret = error_retcode(YaftpError::NotFound);
-				} else {
-					log::error!("error : {}" , e);
-					ret = error_retcode(YaftpError::UnknownError);
-				}
+				ret = error_retcode(io_error(e));
 				break;
 			}
 		};
 
-		f.close().await.unwrap();
+		if n == 0 {
+			break;
+		}
 
-		break;
-	}
+		throttle.take(n).await;
 
-	if ret != error_retcode(YaftpError::OK){
+		if let Err(e) = within(timeouts().data, stream.write_all(&buf[..n])).await {
+			ret = error_retcode(e);
+			break;
+		}
 
-		match send_reply(stream, ret , 0).await {
-			Ok(_) => {},
-			Err(_) => {
-			},
-		};
+		session.transferred(n as u64);
+		metrics::transferred(DOWNLOAD, n as u64);
 	}
 
+	f.close().await.unwrap();
 
+	Ok(ret)
 }
 
-async fn c_hash(stream :&mut  TcpStream, narg : u32){
+async fn c_hash(stream :&mut  TcpStream, narg : u32, session : &Session) -> u8 {
+	match hash(stream, narg, session).await {
+		Ok(p) => p,
+		Err(e) => fail(stream, e).await,
+	}
+}
 
-	let mut ret = 0u8;
+async fn hash(stream :&mut  TcpStream, narg : u32, session : &Session) -> Result<u8 , YaftpError> {
 
 	if narg != 2 {
 		log::error!("command [{}] arguments count unvalid : {}" , "hash", narg);
-		ret = error_retcode(YaftpError::ArgumentCountError);
-		match send_reply(stream, ret , 0).await {
-			Ok(_) => {},
-			Err(_) => {},
-		};
-		return;
+		return Err(YaftpError::ArgumentCountError);
 	}
-	loop {
-		let path = match read_argument(stream, 1024).await{
-			Ok(p) => p,
-			Err(e) => {
-				ret = error_retcode(e);
-				break;
-			}
-		};
 
-		let end_pos = match read_argument(stream, 8).await{
-			Ok(p) => p,
-			Err(e) => {
-				ret = error_retcode(e);
-				break;
-			}
-		};
+	session.check(PERM_READ)?;
 
-		let end_pos = u64::from_be_bytes(end_pos.try_into().unwrap());
+	let path = read_argument(stream, 1024).await?;
+	let end_pos = read_argument(stream, 8).await?;
+	let end_pos = u64::from_be_bytes(end_pos.try_into().unwrap());
 
-		let path = match String::from_utf8(path.to_vec()){
-			Ok(p) => p,
-			Err(_) => {
-				ret = error_retcode(YaftpError::UTF8FormatError);
-				break;
-			},
-		};
+	let path = String::from_utf8(path).map_err(|_| YaftpError::UTF8FormatError)?;
+	let path = session.resolve(&path)?;
 
-		let path = Path::new(path.as_str());
-		let path =  match path.absolutize(){
-			Ok(p) => p,
-			Err(e) => {
-				if e.kind() == std::io::ErrorKind::PermissionDenied {
-					ret = error_retcode(YaftpError::NoPermission);
-				} else if e.kind() == std::io::ErrorKind::NotFound {
-					ret = error_retcode(YaftpError::NotFound);
-				} else {
-					log::error!("error : {}" , e);
-					ret = error_retcode(YaftpError::UnknownError);
-				}
-				break;
-			},
-		};
+	let mut f = async_std::fs::File::open(path.to_str().unwrap().to_string()).await.map_err(io_error)?;
 
-		let mut f = match async_std::fs::File::open(path.to_str().unwrap().to_string()).await{
-			Ok(p) => p,
-			Err(e) => {
-				if e.kind() == std::io::ErrorKind::PermissionDenied {
-					ret = error_retcode(YaftpError::NoPermission);
-				} else if e.kind() == std::io::ErrorKind::NotFound {
-					ret = error_retcode(YaftpError::NotFound);
-				} else {
-					log::error!("error : {}" , e);
-					ret = error_retcode(YaftpError::UnknownError);
-				}
-				break;
-			},
-		};
+	if end_pos > f.metadata().await.unwrap().len(){
+		return Err(YaftpError::EndPosError);
+	}
 
-		if end_pos > f.metadata().await.unwrap().len(){
-			ret = error_retcode(YaftpError::EndPosError);
-			break;
-		}
+	let md5_str = calc_md5(&mut f , end_pos).await;
 
-		let md5_str = calc_md5(&mut f , end_pos).await;
+	f.close().await.unwrap();
 
-		f.close().await.unwrap();
+	if md5_str.is_empty() {
+		return Err(YaftpError::CalcMd5Error);
+	}
 
-		if md5_str.is_empty() {
-			ret = error_retcode(YaftpError::CalcMd5Error);
+	send_rows(stream, vec![md5_str.into_bytes()]).await
+}
+
+async fn c_find(stream :&mut  TcpStream, narg : u32, session : &Session) -> u8 {
+	match find(stream, narg, session).await {
+		Ok(p) => p,
+		Err(e) => fail(stream, e).await,
+	}
+}
+
+/* entries are streamed as the walk finds them , an error after the reply ends the stream */
+async fn find(stream :&mut  TcpStream, narg : u32, session : &Session) -> Result<u8 , YaftpError> {
+
+	if narg != 10 {
+		log::error!("command [{}] arguments count unvalid : {}" , "find", narg);
+		return Err(YaftpError::ArgumentCountError);
+	}
+
+	session.check(PERM_LIST)?;
+
+	let path = read_string_argument(stream).await?;
+	let name = read_string_argument(stream).await?;
+	let regex = read_u8_argument(stream).await?;
+	let typ = read_u8_argument(stream).await?;
+
+	/* min_size , max_size , newer , older , max_depth , max_results */
+	let mut nums = [0u64 ; 6];
+	for num in nums.iter_mut() {
+		*num = read_u64_argument(stream).await?;
+	}
+
+	let filter = FindFilter{
+		name ,
+		regex : regex != 0,
+		typ ,
+		min_size : nums[0],
+		max_size : nums[1],
+		newer : nums[2],
+		older : nums[3],
+		max_depth : nums[4],
+		max_results : nums[5]
+	};
+
+	let matcher = NameMatcher::new(&filter.name, filter.regex)?;
+	let path = session.resolve(&path)?.to_path_buf();
+
+	fs::read_dir(&path).map_err(|e| match e.kind() {
+		std::io::ErrorKind::PermissionDenied => YaftpError::NoPermission,
+		std::io::ErrorKind::NotFound => YaftpError::NotFound,
+		_ => YaftpError::ReadFolderFaild,
+	})?;
+
+	send_reply(stream, 0 , STREAM_NARG).await?;
+
+	/*
+	+----------+----------+------------+----------+
+	|  TYPE    |  SIZE    |  MODIFIED  |  PATH    |
+	+----------+----------+------------+----------+
+	*/
+	let (sender , receiver) = channel::bounded::<String>(64);
+	let shown = session.clone();
+
+	let walker = task::spawn_blocking(move || {
+		let mut count = 0u64;
+		for entry in Walker::new(path, filter.max_depth) {
+
+			if filter.max_results != 0 && count >= filter.max_results {
+				break;
+			}
+
+			if !find_match(&entry, &filter, &matcher) {
+				continue;
+			}
+
+			let mt : DateTime<Utc> = match entry.meta.modified(){
+				Ok(p) => p.into(),
+				Err(_) => continue,
+			};
+
+			let row = [type_name(&entry.meta) , entry.meta.len().to_string() , mt.format("%Y-%m-%d %H:%M:%S").to_string() , shown.display(&entry.path)].join("|");
+
+			/* the receiver is gone when the client stopped reading */
+			if sender.send_blocking(row).is_err() {
+				break;
+			}
+			count += 1;
+		}
+	});
+
+	let mut ret = error_retcode(YaftpError::OK);
+	while let Ok(row) = receiver.recv().await {
+		if let Err(e) = send_argument(stream, &mut row.into_bytes()).await {
+			ret = error_retcode(e);
 			break;
 		}
+	}
 
-		if ret == error_retcode(YaftpError::OK){
-			match send_reply(stream, 0 , 1).await {
-				Ok(_) => {},
-				Err(_) => {
-				},
-			};
+	drop(receiver);
+	walker.await;
+
+	if ret != error_retcode(YaftpError::OK) {
+		log::error!("yaftp send argument error");
+		return Ok(ret);
+	}
+
+	let _ = send_argument(stream, &mut vec![]).await;
+
+	Ok(ret)
+}
+
+async fn c_grep(stream :&mut  TcpStream, narg : u32, session : &Session) -> u8 {
+	match grep(stream, narg, session).await {
+		Ok(p) => p,
+		Err(e) => fail(stream, e).await,
+	}
+}
+
+/* rows are streamed as the search finds them , an error after the reply ends the stream */
+async fn grep(stream :&mut  TcpStream, narg : u32, session : &Session) -> Result<u8 , YaftpError> {
+
+	if narg != 6 {
+		log::error!("command [{}] arguments count unvalid : {}" , "grep", narg);
+		return Err(YaftpError::ArgumentCountError);
+	}
+
+	session.check(PERM_READ)?;
+
+	let path = read_string_argument(stream).await?;
+	let pattern = read_string_argument(stream).await?;
+	let regex = read_u8_argument(stream).await?;
+	let ignore_case = read_u8_argument(stream).await?;
+	let context = read_u64_argument(stream).await?;
+	let max_matches = read_u64_argument(stream).await?;
+
+	if pattern.is_empty() {
+		return Err(YaftpError::ArgumentError);
+	}
+
+	let matcher = line_matcher(&pattern, regex != 0, ignore_case != 0)?;
+	let path = session.resolve(&path)?.to_path_buf();
+
+	let info = fs::metadata(&path).map_err(io_error)?;
+
+	if info.is_file() {
+		/* open errors of a single file are told in the reply */
+		fs::File::open(&path).map_err(|e| match e.kind() {
+			std::io::ErrorKind::PermissionDenied => YaftpError::NoPermission,
+			_ => YaftpError::ReadFileError,
+		})?;
+	} else if !info.is_dir() {
+		return Err(YaftpError::ReadFileError);
+	}
+
+	send_reply(stream, 0 , STREAM_NARG).await?;
+
+	let (sender , receiver) = channel::bounded::<String>(64);
+	let shown = session.clone();
+
+	let searcher = task::spawn_blocking(move || {
+		grep_path(&path, |p| shown.display(p), &matcher, context, max_matches, &mut |row| sender.send_blocking(row).is_ok())
+	});
+
+	let mut ret = error_retcode(YaftpError::OK);
+	while let Ok(row) = receiver.recv().await {
+		if let Err(e) = send_argument(stream, &mut row.into_bytes()).await {
+			ret = error_retcode(e);
+			break;
+		}
+	}
+
+	drop(receiver);
+	let _ = searcher.await;
+
+	if ret != error_retcode(YaftpError::OK) {
+		log::error!("yaftp send argument error");
+		return Ok(ret);
+	}
+
+	let _ = send_argument(stream, &mut vec![]).await;
+
+	Ok(ret)
+}
+
+async fn c_tar(stream :&mut  TcpStream, narg : u32, session : &Session) -> u8 {
+	match tar(stream, narg, session).await {
+		Ok(p) => p,
+		Err(e) => fail(stream, e).await,
+	}
+}
+
+/* the archive is streamed as it is built , a reply after it tells if it is whole */
+async fn tar(stream :&mut  TcpStream, narg : u32, session : &Session) -> Result<u8 , YaftpError> {
+
+	if narg != 2 {
+		log::error!("command [{}] arguments count unvalid : {}" , "tar", narg);
+		return Err(YaftpError::ArgumentCountError);
+	}
+
+	session.check(PERM_READ)?;
+
+	let path = read_string_argument(stream).await?;
+	let compress = read_u8_argument(stream).await?;
+
+	if compress > COMPRESS_ZSTD {
+		return Err(YaftpError::ArgumentError);
+	}
+
+	let path = session.resolve(&path)?.to_path_buf();
+
+	fs::symlink_metadata(&path).map_err(io_error)?;
+
+	send_reply(stream, 0 , STREAM_NARG).await?;
+
+	let (sender , receiver) = channel::bounded::<Vec<u8>>(16);
+
+	let builder = task::spawn_blocking(move || {
+		build_archive(&path, compress, ChannelWriter::new(sender))
+	});
+
+	let mut ret = error_retcode(YaftpError::OK);
+	let throttle = Throttle::server(DOWNLOAD);
+	while let Ok(mut chunk) = receiver.recv().await {
+		throttle.take(chunk.len()).await;
+		if let Err(e) = send_argument(stream, &mut chunk).await {
+			ret = error_retcode(e);
+			break;
 		}
+		session.transferred(chunk.len() as u64);
+		metrics::transferred(DOWNLOAD, chunk.len() as u64);
+	}
+
+	drop(receiver);
+
+	if ret != error_retcode(YaftpError::OK) {
+		log::error!("yaftp send archive error");
+		let _ = builder.await;
+		return Ok(ret);
+	}
+
+	/*
+		the stream is closed by an empty argument , then a reply tell
+		client if the whole archive was built .
+	*/
+	if let Err(e) = builder.await {
+		log::error!("build archive error : {}" , e);
+		if e.kind() == std::io::ErrorKind::PermissionDenied {
+			ret = error_retcode(YaftpError::NoPermission);
+		} else {
+			ret = error_retcode(YaftpError::ReadFileError);
+		}
+	}
+
+	if let Err(e) = send_argument(stream, &mut vec![]).await {
+		return Ok(error_retcode(e));
+	}
+
+	let _ = send_reply(stream, ret , 0).await;
+
+	Ok(ret)
+}
+
+async fn c_untar(stream :&mut  TcpStream, narg : u32, session : &Session) -> u8 {
+	match untar(stream, narg, session).await {
+		Ok(p) => p,
+		Err(e) => fail(stream, e).await,
+	}
+}
+
+/* the reply comes after the whole archive , with a row per entry */
+async fn untar(stream :&mut  TcpStream, narg : u32, session : &Session) -> Result<u8 , YaftpError> {
+
+	if narg != 3 {
+		log::error!("command [{}] arguments count unvalid : {}" , "untar", narg);
+		return Err(YaftpError::ArgumentCountError);
+	}
+
+	session.check(PERM_WRITE)?;
+
+	let path = read_string_argument(stream).await?;
+	let compress = read_u8_argument(stream).await?;
+
+	if compress > COMPRESS_ZSTD {
+		return Err(YaftpError::ArgumentError);
+	}
+
+	let path = session.resolve(&path)?.to_path_buf();
+
+	/* one extraction at a time into a folder */
+	let _guard = try_write(&path, 0)?;
 
-		match send_argument(stream, &mut md5_str.as_bytes().to_vec()).await {
-			Ok(_) => {},
+	fs::create_dir_all(&path).map_err(|e| match e.kind() {
+		std::io::ErrorKind::PermissionDenied => YaftpError::NoPermission,
+		_ => {
+			log::error!("error : {}" , e);
+			YaftpError::WriteFileError
+		},
+	})?;
+
+	let (sender , receiver) = channel::bounded::<Vec<u8>>(16);
+
+	let charge = session.charge(&path);
+	let target = path.clone();
+
+	let extractor = task::spawn_blocking(move || {
+		extract_archive(ChannelReader::new(receiver), compress, &target, &charge, false)
+	});
+
+	/*
+		data is a stream of arguments closed by an empty argument , keep
+		reading it even if extractor stopped so the reply stay in sync .
+	*/
+	let throttle = Throttle::server(UPLOAD);
+	let mut alive = true;
+	let mut read = Ok(());
+	loop {
+		let chunk = match read_argument_within(stream, CHUNK_SIZE as u64, timeouts().data).await{
+			Ok(p) => p,
 			Err(e) => {
-				log::error!("yaftp send argument error");
-				ret = error_retcode(e);
+				read = Err(e);
 				break;
-			},
+			}
 		};
 
-		break;
+		if chunk.is_empty() {
+			break;
+		}
+
+		throttle.take(chunk.len()).await;
+		session.transferred(chunk.len() as u64);
+		metrics::transferred(UPLOAD, chunk.len() as u64);
+
+		if alive && sender.send(chunk).await.is_err() {
+			alive = false;
+		}
 	}
 
-	if ret != error_retcode(YaftpError::OK){
+	drop(sender);
 
-		match send_reply(stream, ret , 0).await {
-			Ok(_) => {},
-			Err(_) => {
-			},
+	let entries = match extractor.await {
+		Ok(p) => p,
+		Err(e) => {
+			log::error!("extract archive error : {}" , e);
+			read?;
+			return Err(YaftpError::WriteFileError);
+		},
+	};
+
+	/* replaced files were counted twice , count the disk again */
+	session.changed(&path);
+
+	read?;
+
+	if entries.iter().any(|p| p.status == ENTRY_QUOTA) {
+		return Err(YaftpError::QuotaExceeded);
+	}
+
+	send_reply(stream, 0 , entries.len() as u32).await?;
+
+	for entry in entries {
+		let row = [entry.status.to_string() , entry.path].join("|");
+		if send_argument(stream, &mut row.into_bytes()).await.is_err() {
+			log::error!("yaftp send argument error");
+			break;
+		}
+	}
+
+	Ok(error_retcode(YaftpError::OK))
+}
+
+async fn c_truncate(stream :&mut  TcpStream, narg : u32, session : &Session) -> u8 {
+	match truncate(stream, narg, session).await {
+		Ok(p) => p,
+		Err(e) => fail(stream, e).await,
+	}
+}
+
+async fn truncate(stream :&mut  TcpStream, narg : u32, session : &Session) -> Result<u8 , YaftpError> {
+
+	if narg != 2 && narg != 3 {
+		log::error!("command [{}] arguments count unvalid : {}" , "truncate", narg);
+		return Err(YaftpError::ArgumentCountError);
+	}
+
+	session.check(PERM_WRITE)?;
+
+	let path = read_argument(stream, 1024).await?;
+	let length = read_u64_argument(stream).await?;
+
+	let mut token = 0u64;
+
+	if narg == 3 {
+		token = read_u64_argument(stream).await?;
+	}
+
+	let path = String::from_utf8(path).map_err(|_| YaftpError::UTF8FormatError)?;
+	let path = session.resolve(&path)?;
+
+	let _guard = try_write(&path, token)?;
+
+	/* never create the file , truncate only applies to an existing one */
+	let mut f = async_std::fs::OpenOptions::new().write(true).open(path.to_str().unwrap().to_string()).await.map_err(io_error)?;
+
+	let old_len = match f.metadata().await{
+		Ok(p) => p.len(),
+		Err(_) => 0,
+	};
+
+	/* growing a file counts its new bytes */
+	let charge = session.charge(&path);
+	charge.refresh().await;
+
+	charge.reserve(length.saturating_sub(old_len), 0)?;
+
+	charge.release(old_len.saturating_sub(length), 0);
+
+	if let Err(e) = f.set_len(length).await {
+		log::error!("truncate error : {}" , e);
+		return Err(YaftpError::WriteFileError);
+	}
+
+	f.close().await.unwrap();
+
+	let _ = send_reply(stream, 0 , 0).await;
+
+	Ok(error_retcode(YaftpError::OK))
+}
+
+async fn c_ln(stream :&mut  TcpStream, narg : u32, session : &Session) -> u8 {
+	match ln(stream, narg, session).await {
+		Ok(p) => p,
+		Err(e) => fail(stream, e).await,
+	}
+}
+
+async fn ln(stream :&mut  TcpStream, narg : u32, session : &Session) -> Result<u8 , YaftpError> {
+
+	if narg != 3 {
+		log::error!("command [{}] arguments count unvalid : {}" , "ln", narg);
+		return Err(YaftpError::ArgumentCountError);
+	}
+
+	session.check(PERM_WRITE)?;
+
+	let target = read_argument(stream, 1024).await?;
+	let link = read_argument(stream, 1024).await?;
+	let symbolic = read_u8_argument(stream).await? != 0;
+
+	let target = String::from_utf8(target).map_err(|_| YaftpError::UTF8FormatError)?;
+	let link = String::from_utf8(link).map_err(|_| YaftpError::UTF8FormatError)?;
+
+	let link = session.resolve_nofollow(&link)?;
+
+	/* a symlink target is kept as given (absolute in the root) , a hard link needs an existing file */
+	let result = if symbolic {
+		let target = session.link_target(&target);
+		task::spawn_blocking(move || make_symlink(&target, &link)).await
+	} else {
+		async_std::fs::hard_link(session.resolve(&target)?, &link).await
+	};
+
+	result.map_err(|e| match e.kind() {
+		std::io::ErrorKind::PermissionDenied => YaftpError::NoPermission,
+		std::io::ErrorKind::NotFound => YaftpError::NotFound,
+		_ => {
+			log::error!("error : {}" , e);
+			YaftpError::WriteFileError
+		},
+	})?;
+
+	let _ = send_reply(stream, 0 , 0).await;
+
+	Ok(error_retcode(YaftpError::OK))
+}
+
+async fn c_watch(stream :&mut  TcpStream, narg : u32, session : &Session) -> u8 {
+	match watch(stream, narg, session).await {
+		Ok(p) => p,
+		Err(e) => fail(stream, e).await,
+	}
+}
+
+/* events are streamed until the client cancels */
+async fn watch(stream :&mut  TcpStream, narg : u32, session : &Session) -> Result<u8 , YaftpError> {
+
+	if narg != 2 {
+		log::error!("command [{}] arguments count unvalid : {}" , "watch", narg);
+		return Err(YaftpError::ArgumentCountError);
+	}
+
+	session.check(PERM_LIST)?;
+
+	let path = read_argument(stream, 1024).await?;
+	let recursive = read_u8_argument(stream).await? != 0;
+
+	let path = String::from_utf8(path).map_err(|_| YaftpError::UTF8FormatError)?;
+	let path = session.resolve(&path)?;
+
+	fs::symlink_metadata(&path).map_err(|e| match e.kind() {
+		std::io::ErrorKind::PermissionDenied => YaftpError::NoPermission,
+		_ => YaftpError::NotFound,
+	})?;
+
+	let (sender , receiver) = channel::bounded::<Option<String>>(1024);
+
+	let watcher = start_watcher(&path, recursive, session.root(), sender.clone()).map_err(|e| {
+		log::error!("watch error : {}" , e);
+		YaftpError::UnknownError
+	})?;
+
+	let ret = error_retcode(YaftpError::OK);
+
+	if send_reply(stream, 0 , STREAM_NARG).await.is_err() {
+		return Ok(ret);
+	}
+
+	/* client cancel by sending any byte or closing the connection */
+	let mut reader = stream.clone();
+	task::spawn(async move {
+		let mut buf = [0u8;1];
+		let _ = reader.read(&mut buf).await;
+		let _ = sender.send(None).await;
+	});
+
+	while let Ok(Some(row)) = receiver.recv().await {
+		let row = match WatchEvent::from_row(&row){
+			Some(p) => [p.kind , session.display(Path::new(&p.path))].join("|"),
+			None => row,
 		};
+
+		if send_argument(stream, &mut row.into_bytes()).await.is_err() {
+			log::info!("watch client disconnected");
+			break;
+		}
 	}
 
+	drop(watcher);
 
+	let _ = send_argument(stream, &mut vec![]).await;
+
+	Ok(ret)
 }
 
-pub async fn yaftp_server_handle(mut stream : TcpStream){
+/* send the bytes of f from pos to its current end , pos follows the sent data */
+async fn send_file_from(stream :&mut  TcpStream , f : &mut File , pos : &mut u64 , session : &Session) -> Result<(), YaftpError> {
 
+	if f.seek(SeekFrom::Start(*pos)).await.is_err() {
+		return Err(YaftpError::ReadFileError);
+	}
+
+	let mut buf = vec![0u8 ; CHUNK_SIZE];
 	loop {
-		/*
-		+-------+----------+---------------+
-		|  VER  | NMETHODS | METHODS       |
-		+-------+----------+---------------+
-		| 1(u8) |   1(u8)  | 1 to 255 (u8) |
-		+-------+----------+---------------+
-		*/
-		let mut header = [0u8;2];
-		match stream.read_exact(&mut header).await{
-			Ok(_) => {},
-			Err(e) => {
-				log::error!("error : {}" , e);
+		let n = match f.read(&mut buf).await{
+			Ok(p) => p,
+			Err(_) => return Err(YaftpError::ReadFileError),
+		};
+
+		if n == 0 {
+			return Ok(());
+		}
+
+		send_argument(stream, &mut buf[..n].to_vec()).await?;
+		session.transferred(n as u64);
+		metrics::transferred(DOWNLOAD, n as u64);
+		*pos += n as u64;
+	}
+}
+
+async fn c_tail(stream :&mut  TcpStream, narg : u32, session : &Session) -> u8 {
+	match tail(stream, narg, session).await {
+		Ok(p) => p,
+		Err(e) => fail(stream, e).await,
+	}
+}
+
+/* the last lines are streamed , then with follow what is written until the client cancels */
+async fn tail(stream :&mut  TcpStream, narg : u32, session : &Session) -> Result<u8 , YaftpError> {
+
+	if narg != 3 {
+		log::error!("command [{}] arguments count unvalid : {}" , "tail", narg);
+		return Err(YaftpError::ArgumentCountError);
+	}
+
+	session.check(PERM_READ)?;
+
+	let path = read_argument(stream, 1024).await?;
+	let lines = read_u64_argument(stream).await?;
+	let follow = read_u8_argument(stream).await? != 0;
+
+	let path = String::from_utf8(path).map_err(|_| YaftpError::UTF8FormatError)?;
+	let path = session.resolve(&path)?.to_path_buf();
+
+	/* finding the last lines seeks backward with blocking reads */
+	let opened = path.clone();
+	let (f , meta , pos) = task::spawn_blocking(move || {
+		let mut f = fs::File::open(&opened).map_err(io_error)?;
+
+		let meta = f.metadata().map_err(|_| YaftpError::ReadFileError)?;
+		if !meta.is_file() {
+			return Err(YaftpError::ReadFileError);
+		}
+
+		let pos = tail_offset(&mut f, lines).map_err(|_| YaftpError::ReadFileError)?;
+		Ok((f , meta , pos))
+	}).await?;
+
+	let mut f = File::from(f);
+	let mut id = file_id(&meta);
+	let mut pos = pos;
+
+	let ret = error_retcode(YaftpError::OK);
+
+	if send_reply(stream, 0 , STREAM_NARG).await.is_err() {
+		return Ok(ret);
+	}
+
+	if send_file_from(stream, &mut f, &mut pos, session).await.is_err() || !follow {
+		let _ = send_argument(stream, &mut vec![]).await;
+		return Ok(ret);
+	}
+
+	/* client cancel by sending any byte or closing the connection */
+	let (sender , receiver) = channel::bounded::<()>(1);
+	let mut reader = stream.clone();
+	task::spawn(async move {
+		let mut buf = [0u8;1];
+		let _ = reader.read(&mut buf).await;
+		let _ = sender.send(()).await;
+	});
+
+	while async_std::future::timeout(TAIL_POLL_INTERVAL, receiver.recv()).await.is_err() {
+
+		/* the path may be missing for a moment while a log is rotated */
+		let meta = match async_std::fs::metadata(&path).await{
+			Ok(p) => p,
+			Err(_) => continue,
+		};
+
+		if file_id(&meta) != id {
+			/* rotated , drain what was written to the old file then follow the new one */
+			if send_file_from(stream, &mut f, &mut pos, session).await.is_err() {
 				break;
-			},
+			}
+			f = match File::open(&path).await{
+				Ok(p) => p,
+				Err(_) => continue,
+			};
+			log::info!("tail file rotated : {}" , path.display());
+			id = file_id(&meta);
+			pos = 0;
+		} else if meta.len() < pos {
+			log::info!("tail file truncated : {}" , path.display());
+			pos = 0;
+		}
+
+		if send_file_from(stream, &mut f, &mut pos, session).await.is_err() {
+			log::info!("tail client disconnected");
+			break;
+		}
+	}
+
+	let _ = send_argument(stream, &mut vec![]).await;
+
+	Ok(ret)
+}
+
+async fn c_lock(stream :&mut  TcpStream, narg : u32, session : &Session) -> u8 {
+	match lock_file(stream, narg, session).await {
+		Ok(p) => p,
+		Err(e) => fail(stream, e).await,
+	}
+}
+
+async fn lock_file(stream :&mut  TcpStream, narg : u32, session : &Session) -> Result<u8 , YaftpError> {
+
+	if narg != 2 && narg != 3 {
+		log::error!("command [{}] arguments count unvalid : {}" , "lock", narg);
+		return Err(YaftpError::ArgumentCountError);
+	}
+
+	session.check(PERM_WRITE)?;
+
+	let path = read_argument(stream, 1024).await?;
+	let lease = read_u64_argument(stream).await?;
+
+	let mut token = 0u64;
+
+	if narg == 3 {
+		token = read_u64_argument(stream).await?;
+	}
+
+	let path = String::from_utf8(path).map_err(|_| YaftpError::UTF8FormatError)?;
+	let path = session.resolve(&path)?;
+
+	let lease = match lease {
+		0 => DEFAULT_LEASE,
+		p => Duration::from_secs(p),
+	};
+
+	let token = lock(&path, lease, token)?;
+
+	send_reply(stream, 0 , 1).await?;
+
+	if send_argument(stream, &mut token.to_be_bytes().to_vec()).await.is_err() {
+		log::error!("yaftp send argument error");
+	}
+
+	Ok(error_retcode(YaftpError::OK))
+}
+
+async fn c_unlock(stream :&mut  TcpStream, narg : u32, session : &Session) -> u8 {
+	match unlock_file(stream, narg, session).await {
+		Ok(p) => p,
+		Err(e) => fail(stream, e).await,
+	}
+}
+
+async fn unlock_file(stream :&mut  TcpStream, narg : u32, session : &Session) -> Result<u8 , YaftpError> {
+
+	if narg != 2 {
+		log::error!("command [{}] arguments count unvalid : {}" , "unlock", narg);
+		return Err(YaftpError::ArgumentCountError);
+	}
+
+	session.check(PERM_WRITE)?;
+
+	let path = read_argument(stream, 1024).await?;
+	let token = read_u64_argument(stream).await?;
+
+	let path = String::from_utf8(path).map_err(|_| YaftpError::UTF8FormatError)?;
+	let path = session.resolve(&path)?;
+
+	unlock(&path, token)?;
+
+	let _ = send_reply(stream, 0 , 0).await;
+
+	Ok(error_retcode(YaftpError::OK))
+}
+
+/* usage and limits of the quotas covering path , the home folder by default */
+async fn c_quota(stream :&mut  TcpStream, narg : u32, session : &Session) -> u8 {
+	match quota(stream, narg, session).await {
+		Ok(p) => p,
+		Err(e) => fail(stream, e).await,
+	}
+}
+
+async fn quota(stream :&mut  TcpStream, narg : u32, session : &Session) -> Result<u8 , YaftpError> {
+
+	if narg > 1 {
+		log::error!("command [{}] arguments count unvalid : {}" , "quota", narg);
+		return Err(YaftpError::ArgumentCountError);
+	}
+
+	session.check(PERM_LIST)?;
+
+	let mut path = String::new();
+
+	if narg == 1 {
+		path = read_string_argument(stream).await?;
+	}
+
+	if path.is_empty() {
+		path = session.cwd()?;
+	}
+
+	let path = session.resolve(&path)?;
+
+	let quotas = session.quotas(&path);
+
+	send_reply(stream, 0 , quotas.len() as u32).await?;
+
+	/* a limit of - is unlimited */
+	for quota in quotas {
+		let (bytes , files) = quota.usage_async().await;
+		let limit = |p : Option<u64>| match p {
+			Some(p) => p.to_string(),
+			None => String::from("-"),
 		};
+		let row = [quota.kind.to_string() , session.display_folder(&quota.path) , bytes.to_string() , limit(quota.max_bytes) , files.to_string() , limit(quota.max_files)].join("|");
+		if send_argument(stream, &mut row.into_bytes()).await.is_err() {
+			log::error!("yaftp send argument error");
+			break;
+		}
+	}
+
+	Ok(error_retcode(YaftpError::OK))
+}
+
+async fn c_sessions(stream :&mut  TcpStream, narg : u32, session : &Session, registry : &Registry) -> u8 {
+	match sessions(stream, narg, session, registry).await {
+		Ok(p) => p,
+		Err(e) => fail(stream, e).await,
+	}
+}
+
+async fn sessions(stream :&mut  TcpStream, narg : u32, session : &Session, registry : &Registry) -> Result<u8 , YaftpError> {
+
+	if narg != 0 {
+		log::error!("command [{}] arguments count unvalid : {}" , "sessions", narg);
+		return Err(YaftpError::ArgumentCountError);
+	}
+
+	session.check(PERM_ADMIN)?;
+
+	let sessions = registry.list();
+
+	send_reply(stream, 0 , sessions.len() as u32).await?;
 
-		if header[0] != 0x01{
-			log::error!("not support version : {}" , header[0]);
+	for status in sessions {
+		if send_argument(stream, &mut status.row().into_bytes()).await.is_err() {
+			log::error!("yaftp send argument error");
 			break;
 		}
+	}
+
+	Ok(error_retcode(YaftpError::OK))
+}
+
+async fn c_kill(stream :&mut  TcpStream, narg : u32, session : &Session, registry : &Registry) -> u8 {
+	match kill(stream, narg, session, registry).await {
+		Ok(p) => p,
+		Err(e) => fail(stream, e).await,
+	}
+}
+
+async fn kill(stream :&mut  TcpStream, narg : u32, session : &Session, registry : &Registry) -> Result<u8 , YaftpError> {
 
-		let mut methods = vec![0u8;header[1].into()].into_boxed_slice();
+	if narg != 1 {
+		log::error!("command [{}] arguments count unvalid : {}" , "kill", narg);
+		return Err(YaftpError::ArgumentCountError);
+	}
+
+	let id = read_u64_argument(stream).await?;
+
+	session.check(PERM_ADMIN)?;
+
+	if !registry.kill(id) {
+		return Err(YaftpError::NotFound);
+	}
+
+	if let Err(e) = send_reply(stream, 0 , 0).await {
+		return Ok(error_retcode(e));
+	}
+
+	Ok(error_retcode(YaftpError::OK))
+}
+
+async fn c_shutdown(stream :&mut  TcpStream, narg : u32, session : &Session, registry : &Registry) -> u8 {
+
+	if narg != 0 {
+		log::error!("command [{}] arguments count unvalid : {}" , "shutdown", narg);
+		return fail(stream, YaftpError::ArgumentCountError).await;
+	}
 
-		match stream.read_exact(&mut methods).await{
-			Ok(_) => {},
+	if let Err(e) = session.check(PERM_ADMIN) {
+		return fail(stream, e).await;
+	}
+
+	/* the listeners stop , running sessions end on their own */
+	registry.shutdown();
+
+	let _ = send_reply(stream, 0 , 0).await;
+
+	error_retcode(YaftpError::OK)
+}
+
+async fn read_command(stream :&mut  TcpStream) -> Result<[u8;5], YaftpError> {
+	/*
+	+-------+--------+
+	|  CMD  | NARG   |
+	+-------+--------+
+	| 1(u8) | 4(u32) |
+	+-------+--------+
+	*/
+	let mut command = [0u8;5];
+	within(timeouts().handshake, stream.read_exact(&mut command)).await?;
+
+	if !check_support_methods(&[command[0]]){
+		log::error!("not support command : {}" , command[0]);
+		return Err(YaftpError::NoSupportCommand);
+	}
+
+	Ok(command)
+}
+
+fn command_name(command : u8) -> &'static str {
+	match command {
+		0x01 => "ls",
+		0x02 => "cwd",
+		0x03 => "cp",
+		0x04 => "mkd",
+		0x05 => "mv",
+		0x06 => "rm",
+		0x07 => "put",
+		0x08 => "get",
+		0x09 => "info",
+		0x0a => "hash",
+		0x0b => "find",
+		0x0c => "grep",
+		0x0d => "tar",
+		0x0e => "untar",
+		0x0f => "truncate",
+		0x10 => "ln",
+		0x11 => "watch",
+		0x12 => "tail",
+		0x13 => "lock",
+		0x14 => "unlock",
+		0x15 => "auth",
+		0x16 => "quota",
+		0x17 => "sessions",
+		0x18 => "kill",
+		0x19 => "shutdown",
+		_ => "unknown",
+	}
+}
+
+/* check user and password , a server without accounts accepts anyone */
+async fn c_auth(stream :&mut  TcpStream, narg : u32, accounts : &Option<Arc<Accounts>>) -> Option<Session> {
+	match auth(stream, narg, accounts).await {
+		Ok(p) => p,
+		Err(e) => {
+			fail(stream, e).await;
+			None
+		},
+	}
+}
+
+/* none when the reply of a login could not be sent */
+async fn auth(stream :&mut  TcpStream, narg : u32, accounts : &Option<Arc<Accounts>>) -> Result<Option<Session> , YaftpError> {
+
+	if narg != 2 {
+		log::error!("command [{}] arguments count unvalid : {}" , "auth", narg);
+		return Err(YaftpError::ArgumentCountError);
+	}
+
+	let user = read_argument(stream, 256).await?;
+	let user = String::from_utf8(user).map_err(|_| YaftpError::UTF8FormatError)?;
+
+	let password = read_argument(stream, 1024).await?;
+	let password = String::from_utf8_lossy(&password).to_string();
+
+	let peer = match stream.peer_addr(){
+		Ok(p) => p.to_string(),
+		Err(_) => String::from("unknown"),
+	};
+
+	let session = match accounts {
+		Some(p) => p.login(&user, &password),
+		None => Some(Session::anonymous()),
+	};
+
+	if session.is_none() {
+		log::warn!("user [{}] login faild from {}" , user , peer);
+		return Err(YaftpError::AuthFaild);
+	}
+
+	log::info!("user [{}] login from {}" , user , peer);
+
+	if send_reply(stream, 0 , 0).await.is_err() {
+		return Ok(None);
+	}
+
+	Ok(session)
+}
+
+async fn handshake(stream :&mut  TcpStream) -> Result<(), YaftpError> {
+	/*
+	+-------+----------+---------------+
+	|  VER  | NMETHODS | METHODS       |
+	+-------+----------+---------------+
+	| 1(u8) |   1(u8)  | 1 to 255 (u8) |
+	+-------+----------+---------------+
+	*/
+	let mut header = [0u8;2];
+	within(timeouts().handshake, stream.read_exact(&mut header)).await?;
+
+	if header[0] != 0x01{
+		log::error!("not support version : {}" , header[0]);
+		return Err(YaftpError::NoSupportVersion);
+	}
+
+	let mut methods = vec![0u8;header[1].into()].into_boxed_slice();
+
+	match within(timeouts().handshake, stream.read_exact(&mut methods)).await{
+		Ok(_) => {},
+		Err(_) => return Err(YaftpError::ArgumentError),
+	};
+
+	if !check_support_methods(&methods){
+		log::warn!("the client has not support method");
+	}
+
+	/*
+	+-------+----------+---------------+
+	|  VER  | NMETHODS | METHODS       |
+	+-------+----------+---------------+
+	| 1(u8) |   1(u8)  | 1 to 255 (u8) |
+	+-------+----------+---------------+
+	*/
+	within(timeouts().handshake, stream.write_all(&[1u8, 25u8 , 1u8 , 2u8 , 3u8 , 4u8 , 5u8 , 6u8 , 7u8, 8u8, 9u8 , 10u8 , 11u8 , 12u8 , 13u8 , 14u8 , 15u8 , 16u8 , 17u8 , 18u8 , 19u8 , 20u8 , 21u8 , 22u8 , 23u8 , 24u8 , 25u8])).await?;
+
+	Ok(())
+}
+
+/*
+	serve clients of listener until registry is shut down , the listener is
+	closed on return . the running sessions are left to registry.drain .
+*/
+pub async fn serve(listener : TcpListener , accounts : SharedAccounts , access : Arc<Access> , registry : Arc<Registry>) {
+	let mut backoff = ACCEPT_BACKOFF_MIN;
+
+	loop {
+		let accepted = match futures::future::select(Box::pin(listener.accept()), Box::pin(registry.stopped())).await{
+			Either::Left((p , _)) => p,
+			Either::Right(_) => break,
+		};
+
+		/* errors like too many open files pass , wait and accept again */
+		let (stream , peer) = match accepted{
+			Ok(p) => {
+				backoff = ACCEPT_BACKOFF_MIN;
+				p
+			},
 			Err(e) => {
-				log::error!("error : {}" , e);
-				break;
+				log::error!("accept error : {} , retry in {} ms" , e , backoff.as_millis());
+				task::sleep(backoff).await;
+				backoff = std::cmp::min(backoff * 2, ACCEPT_BACKOFF_MAX);
+				continue;
 			},
 		};
 
-		if !check_support_methods(&methods){
-			log::warn!("the client has not support method");
+		if !access.admit(&peer.ip()) {
+			let _ = stream.shutdown(std::net::Shutdown::Both);
+			continue;
 		}
 
-		/*
-		+-------+----------+---------------+
-		|  VER  | NMETHODS | METHODS       |
-		+-------+----------+---------------+
-		| 1(u8) |   1(u8)  | 1 to 255 (u8) |
-		+-------+----------+---------------+
-		*/
-		match stream.write_all(&[1u8, 10u8 , 1u8 , 2u8 , 3u8 , 4u8 , 5u8 , 6u8 , 7u8, 8u8, 9u8 , 10u8]).await{
-			Ok(_) => {},
-			Err(e) => {
-				log::error!("error : {}" , e);
-				break;
+		let slot = match access.enter(&peer.ip()){
+			Some(p) => p,
+			None => {
+				task::spawn(yaftp_server_busy(stream));
+				continue;
 			},
 		};
 
-		/*
-		+-------+--------+
-		|  CMD  | NARG   |
-		+-------+--------+
-		| 1(u8) | 4(u32) |
-		+-------+--------+
-		*/
-		let mut command = [0u8;5];
-		match stream.read_exact(&mut command).await{
-			Ok(_) => {},
-			Err(e) => {
-				log::error!("error : {}" , e);
-				break;
-			},
+		let accounts = accounts.read().unwrap().clone();
+		let access = access.clone();
+		registry.spawn(Some(peer), move |registry , id| async move {
+			yaftp_server_handle(stream , accounts , Some(access) , registry , id).await;
+			drop(slot);
+		});
+	}
+}
+
+/* refuse a connection while the server is busy , after the handshake so the client can read why */
+pub async fn yaftp_server_busy(mut stream : TcpStream){
+
+	/* under a flood , close at once instead of replying */
+	if BUSY_REPLIES.fetch_add(1, Ordering::Relaxed) < MAX_BUSY_REPLIES {
+		let reply = async {
+			handshake(&mut stream).await?;
+			read_command(&mut stream).await?;
+			send_reply(&mut stream, error_retcode(YaftpError::ServerBusy), 0).await
 		};
 
-		if !check_support_methods(&[command[0]]){
-			log::error!("not support command : {}" , command[0]);
-			break;
+		let _ = async_std::future::timeout(BUSY_REPLY_TIMEOUT, reply).await;
+	}
+
+	BUSY_REPLIES.fetch_sub(1, Ordering::Relaxed);
+
+	let _ = stream.shutdown(Shutdown::Both);
+}
+
+/* a command refused before any user is logged in */
+fn refused(peer : Option<SocketAddr> , command : u8 , start : Instant) {
+	metrics::command(command_name(command), error_retcode(YaftpError::AuthFaild));
+	audit::record(&Entry {
+		time : Utc::now(),
+		peer,
+		user : None,
+		command,
+		name : command_name(command),
+		trail : &Trail::default(),
+		duration : start.elapsed(),
+		retcode : error_retcode(YaftpError::AuthFaild)
+	});
+}
+
+/*
+	serve one connection . with access , a failed handshake or login is
+	counted against the peer address .
+*/
+pub async fn yaftp_server_handle(mut stream : TcpStream , accounts : Option<Arc<Accounts>> , access : Option<Arc<Access>> , registry : Arc<Registry> , id : u64){
+
+	let peer = stream.peer_addr().ok();
+	let _active = metrics::Active::new();
+
+	let failed = run_command(&mut stream, &accounts, &registry, id, peer).await;
+
+	if failed {
+		if let (Some(access) , Ok(peer)) = (&access , stream.peer_addr()) {
+			access.fail(&peer.ip());
 		}
+	}
 
-		let narg = u32::from_be_bytes(command[1..5].try_into().unwrap());
+	let _ = stream.shutdown(Shutdown::Both);
+}
 
-		match command[0] {
-			0x01 => {
-				let _ = c_ls(&mut stream , narg ).await;
-			},
-			0x02 => {
-				let _ = c_cwd(&mut stream , narg ).await;
-			},
-			0x03 => {
-				let _ = c_cp(&mut stream , narg ).await;
-			},
-			0x04 => {
-				let _ = c_mkd(&mut stream , narg ).await;
-			},
-			0x05 => {
-				let _ = c_mv(&mut stream , narg ).await;
-			},
-			0x06 => {
-				let _ = c_rm(&mut stream , narg ).await;
-			},
-			0x07 => {
-				let _ = c_put(&mut stream , narg ).await;
-			},
-			0x08 => {
-				let _ = c_get(&mut stream , narg ).await;
-			}
-			0x09 => {
-				let _ = c_info(&mut stream , narg ).await;
-			},
-			0x0a => {
-				let _ = c_hash(&mut stream , narg ).await;
-			},
-			_ => {
-				log::error!("not support command {}" , command[0]);
-				break;
-			}
+/* run the one command of a connection , returns whether the handshake or login failed */
+async fn run_command(stream :&mut  TcpStream , accounts : &Option<Arc<Accounts>> , registry : &Registry , id : u64 , peer : Option<SocketAddr>) -> bool {
+
+	if let Err(e) = handshake(stream).await {
+		/* a peer closing before the handshake is not a failure , one staying silent is */
+		let failed = !matches!(e , YaftpError::UnknownNetwordError);
+		if failed {
+			metrics::handshake_failed();
 		}
-		break;
+		return failed;
 	}
 
-	match stream.shutdown(Shutdown::Both){
-		Ok(_) => {},
-		Err(_) => {},
+	let mut command = match read_command(stream).await{
+		Ok(p) => p,
+		Err(_) => return false,
+	};
+
+	/* with accounts , auth comes first and the command follows in the same connection */
+	let mut session = match accounts {
+		Some(_) => None,
+		None => Some(Session::anonymous()),
 	};
 
+	if command[0] == 0x15 {
+		let narg = u32::from_be_bytes(command[1..5].try_into().unwrap());
+		let start = Instant::now();
+		session = c_auth(stream, narg, accounts).await;
+		if session.is_none() {
+			refused(peer, command[0], start);
+			return true;
+		}
+
+		command = match read_command(stream).await{
+			Ok(p) => p,
+			Err(_) => return false,
+		};
+	}
+
+	let session = match session {
+		Some(p) => p,
+		None => {
+			log::warn!("command [{}] without login" , command_name(command[0]));
+			let _ = send_reply(stream, error_retcode(YaftpError::AuthFaild), 0).await;
+			refused(peer, command[0], Instant::now());
+			return true;
+		},
+	};
+
+	log::info!("user [{}] command [{}]" , session.user , command_name(command[0]));
+
+	registry.login(id, &session.user);
+	registry.start(id, command_name(command[0]), session.trail());
+
+	let narg = u32::from_be_bytes(command[1..5].try_into().unwrap());
+	let start = Instant::now();
+
+	let ret = match command[0] {
+		0x01 => c_ls(stream , narg , &session).await,
+		0x02 => c_cwd(stream , narg , &session).await,
+		0x03 => c_cp(stream , narg , &session).await,
+		0x04 => c_mkd(stream , narg , &session).await,
+		0x05 => c_mv(stream , narg , &session).await,
+		0x06 => c_rm(stream , narg , &session).await,
+		0x07 => c_put(stream , narg , &session).await,
+		0x08 => c_get(stream , narg , &session).await,
+		0x09 => c_info(stream , narg , &session).await,
+		0x0a => c_hash(stream , narg , &session).await,
+		0x0b => c_find(stream , narg , &session).await,
+		0x0c => c_grep(stream , narg , &session).await,
+		0x0d => c_tar(stream , narg , &session).await,
+		0x0e => c_untar(stream , narg , &session).await,
+		0x0f => c_truncate(stream , narg , &session).await,
+		0x10 => c_ln(stream , narg , &session).await,
+		0x11 => c_watch(stream , narg , &session).await,
+		0x12 => c_tail(stream , narg , &session).await,
+		0x13 => c_lock(stream , narg , &session).await,
+		0x14 => c_unlock(stream , narg , &session).await,
+		0x16 => c_quota(stream , narg , &session).await,
+		0x17 => c_sessions(stream , narg , &session , registry).await,
+		0x18 => c_kill(stream , narg , &session , registry).await,
+		0x19 => c_shutdown(stream , narg , &session , registry).await,
+		_ => {
+			log::error!("not support command {}" , command[0]);
+			error_retcode(YaftpError::UnknownError)
+		}
+	};
+
+	metrics::command(command_name(command[0]), ret);
+	if matches!(command[0] , 0x07 | 0x08 | 0x0d | 0x0e) {
+		metrics::transfer_duration(command_name(command[0]), start.elapsed());
+	}
 
+	audit::record(&Entry {
+		time : Utc::now(),
+		peer,
+		user : Some(&session.user),
+		command : command[0],
+		name : command_name(command[0]),
+		trail : &session.take_trail(),
+		duration : start.elapsed(),
+		retcode : ret
+	});
+
+	false
 }
\ No newline at end of file
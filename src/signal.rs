@@ -0,0 +1,115 @@
+use std::{sync::{Arc, atomic::{AtomicUsize, Ordering}}, time::Duration};
+
+use async_std::task;
+
+use yaftp::{progress::Cancel, registry::Registry};
+
+/* the handler only counts signals , a task checks the count this often */
+const SIGNAL_POLL_INTERVAL : Duration = Duration::from_millis(200);
+
+static SIGNALS : AtomicUsize = AtomicUsize::new(0);
+static HANGUPS : AtomicUsize = AtomicUsize::new(0);
+
+#[cfg(unix)]
+extern "C" fn on_signal(signal : libc::c_int) {
+	if signal == libc::SIGHUP {
+		HANGUPS.fetch_add(1, Ordering::SeqCst);
+	} else {
+		SIGNALS.fetch_add(1, Ordering::SeqCst);
+	}
+}
+
+/* count SIGTERM and SIGINT instead of dying at once , SIGHUP too when the server can reload */
+#[cfg(unix)]
+fn install(hangup : bool) {
+	let signals : &[libc::c_int] = if hangup { &[libc::SIGTERM , libc::SIGINT , libc::SIGHUP] } else { &[libc::SIGTERM , libc::SIGINT] };
+	install_signals(signals);
+}
+
+#[cfg(unix)]
+fn install_signals(signals : &[libc::c_int]) {
+	for signal in signals {
+		/* the handler only touches an atomic , which is safe in a signal handler */
+		let handler = on_signal as extern "C" fn(libc::c_int) as libc::sighandler_t;
+		if unsafe { libc::signal(*signal, handler) } == libc::SIG_ERR {
+			log::error!("install handler of signal {} faild" , signal);
+		}
+	}
+}
+
+#[cfg(not(unix))]
+fn install(_hangup : bool) {}
+
+#[cfg(unix)]
+fn install_interrupt() {
+	install_signals(&[libc::SIGINT]);
+}
+
+#[cfg(not(unix))]
+fn install_interrupt() {}
+
+/*
+	a first SIGTERM or SIGINT shuts the server down gracefully : listeners
+	stop and running sessions are drained . a second one exits at once .
+	SIGHUP calls reload , when there is one .
+*/
+pub async fn watch(registry : Arc<Registry> , reload : Option<Box<dyn Fn() + Send>>) {
+	install(reload.is_some());
+
+	let mut seen = 0;
+	let mut hangups = 0;
+	loop {
+		task::sleep(SIGNAL_POLL_INTERVAL).await;
+
+		let count = HANGUPS.load(Ordering::SeqCst);
+		if count != hangups {
+			hangups = count;
+			if let Some(reload) = &reload {
+				log::info!("SIGHUP received , reload config");
+				reload();
+			}
+		}
+
+		let count = SIGNALS.load(Ordering::SeqCst);
+		if count == seen {
+			continue;
+		}
+
+		if seen == 0 {
+			log::warn!("signal received , shutdown , send it again to exit now");
+			registry.shutdown();
+		}
+
+		if count >= 2 {
+			log::warn!("signal received again , exit now");
+			std::process::exit(1);
+		}
+
+		seen = count;
+	}
+}
+
+/*
+	the shell in a terminal : SIGINT while busy cancels the running transfer ,
+	else it exits like without a handler .
+*/
+pub async fn interrupt(busy : impl Fn() -> bool , cancel : Cancel) {
+	install_interrupt();
+
+	let mut seen = 0;
+	loop {
+		task::sleep(SIGNAL_POLL_INTERVAL).await;
+
+		let count = SIGNALS.load(Ordering::SeqCst);
+		if count == seen {
+			continue;
+		}
+		seen = count;
+
+		if !busy() {
+			println!();
+			std::process::exit(130);
+		}
+		cancel.cancel();
+	}
+}
@@ -0,0 +1,122 @@
+use std::{sync::{Mutex, atomic::{AtomicU64, Ordering}}, time::{Duration, Instant}};
+
+use async_std::task;
+
+/* directions of a transfer , seen from the client */
+pub const UPLOAD : usize = 0;
+pub const DOWNLOAD : usize = 1;
+
+/* bytes let through at once after an idle time , a part of one second of rate */
+const BURST_DIVISOR : u64 = 10;
+
+/*
+	token bucket of rate bytes per second , 0 is unlimited . a taker going
+	over the tokens waits for its debt , takers sharing a bucket wait in turn .
+*/
+pub struct Bucket {
+	rate : AtomicU64,
+	state : Mutex<Option<(Instant , f64)>>
+}
+
+impl Bucket {
+
+	pub const fn new(rate : u64) -> Bucket {
+		Bucket { rate : AtomicU64::new(rate) , state : Mutex::new(None) }
+	}
+
+	pub fn rate(&self) -> u64 {
+		self.rate.load(Ordering::Relaxed)
+	}
+
+	pub fn set_rate(&self , rate : u64) {
+		self.rate.store(rate, Ordering::Relaxed);
+		*self.state.lock().unwrap() = None;
+	}
+
+	/* wait until n bytes may pass */
+	pub async fn take(&self , n : usize) {
+		let rate = self.rate();
+		if rate == 0 {
+			return;
+		}
+
+		let wait = {
+			let mut state = self.state.lock().unwrap();
+			let now = Instant::now();
+			let burst = std::cmp::max(rate / BURST_DIVISOR, 1) as f64;
+
+			let tokens = match *state {
+				Some((last , tokens)) => (tokens + now.duration_since(last).as_secs_f64() * rate as f64).min(burst),
+				None => burst,
+			} - n as f64;
+
+			*state = Some((now , tokens));
+
+			if tokens < 0.0 {
+				Duration::from_secs_f64(-tokens / rate as f64)
+			} else {
+				Duration::ZERO
+			}
+		};
+
+		if !wait.is_zero() {
+			task::sleep(wait).await;
+		}
+	}
+}
+
+/* server rates shared by every session , per direction */
+static GLOBAL : [Bucket ; 2] = [Bucket::new(0) , Bucket::new(0)];
+
+/* server rates of each session , per direction */
+static SESSION : [AtomicU64 ; 2] = [AtomicU64::new(0) , AtomicU64::new(0)];
+
+pub fn set_server_limits(global : [u64 ; 2] , session : [u64 ; 2]) {
+	for direction in [UPLOAD , DOWNLOAD] {
+		GLOBAL[direction].set_rate(global[direction]);
+		SESSION[direction].store(session[direction], Ordering::Relaxed);
+	}
+}
+
+/* the rate limit of one transfer */
+pub struct Throttle {
+	global : Option<&'static Bucket>,
+	session : Bucket
+}
+
+impl Throttle {
+
+	/* a server transfer , limited by the global and the session rates of direction */
+	pub fn server(direction : usize) -> Throttle {
+		Throttle { global : Some(&GLOBAL[direction]) , session : Bucket::new(SESSION[direction].load(Ordering::Relaxed)) }
+	}
+
+	/* a client transfer of rate bytes per second , 0 is unlimited */
+	pub fn new(rate : u64) -> Throttle {
+		Throttle { global : None , session : Bucket::new(rate) }
+	}
+
+	pub async fn take(&self , n : usize) {
+		if let Some(p) = self.global {
+			p.take(n).await;
+		}
+		self.session.take(n).await;
+	}
+}
+
+#[test]
+fn test_throttle() {
+	let throttle = Throttle::new(100_000);
+	let start = Instant::now();
+	task::block_on(async {
+		for _ in 0..30 {
+			throttle.take(1000).await;
+		}
+	});
+	/* 30000 bytes less the 10000 bytes burst at 100000 bytes/s */
+	assert!(start.elapsed() >= Duration::from_millis(180));
+
+	let start = Instant::now();
+	task::block_on(Throttle::new(0).take(usize::MAX));
+	assert!(start.elapsed() < Duration::from_millis(50));
+}
@@ -1,12 +1,13 @@
 use async_std::fs::File;
 use futures::AsyncReadExt;
 use md5::{Digest, Md5};
+use std::{io::{self, Read, Seek, SeekFrom}, path::Path};
 
 pub fn check_support_methods(methods : &[u8]) -> bool {
 	
 	let mut i = 0 ;
 	while i < methods.len() {
-		if methods[i] > 0x0a {
+		if methods[i] > 0x19 {
 			return false;
 		}
 		i += 1;
@@ -63,20 +64,124 @@ pub async fn calc_md5(f :&mut File , end_pos : u64) -> String {
 	md5_str
 }
 
-#[macro_export]
-macro_rules! println_err {
-    () => ($crate::print!("\n"));
-    ($($arg:tt)*) => ({
-		print!("[-] ");
-        println!($($arg)*);
-    })
+/*
+	create a symbolic link at link pointing to target , target is stored as
+	given so a relative target stays relative to the link folder .
+*/
+#[cfg(unix)]
+pub fn make_symlink(target : &Path , link : &Path) -> io::Result<()> {
+	std::os::unix::fs::symlink(target, link)
 }
 
-#[macro_export]
-macro_rules! println_info {
-    () => ($crate::print!("\n"));
-    ($($arg:tt)*) => ({
-		print!("[+] ");
-        println!($($arg)*);
-    })
-}
\ No newline at end of file
+#[cfg(windows)]
+pub fn make_symlink(target : &Path , link : &Path) -> io::Result<()> {
+	let resolved = match link.parent() {
+		Some(p) => p.join(target),
+		None => target.to_path_buf(),
+	};
+
+	if resolved.is_dir() {
+		std::os::windows::fs::symlink_dir(target, link)
+	} else {
+		std::os::windows::fs::symlink_file(target, link)
+	}
+}
+
+/* remove a symbolic link itself , never its target */
+pub fn remove_link(link : &Path) -> io::Result<()> {
+	match std::fs::remove_file(link) {
+		Ok(_) => Ok(()),
+		/* a folder link on windows is removed as a folder */
+		Err(e) => match std::fs::remove_dir(link) {
+			Ok(_) => Ok(()),
+			Err(_) => Err(e),
+		},
+	}
+}
+
+/*
+	offset where the last `lines` lines of f start , a newline ending the
+	file does not count as an empty last line . 0 lines gives the end.
+*/
+pub fn tail_offset<R : Read + Seek>(f : &mut R , lines : u64) -> io::Result<u64> {
+
+	let end = f.seek(SeekFrom::End(0))?;
+
+	if lines == 0 {
+		return Ok(end);
+	}
+
+	let mut buf = vec![0u8 ; 8192];
+	let mut pos = end;
+	let mut count = 0u64;
+
+	while pos > 0 {
+		let n = std::cmp::min(buf.len() as u64, pos) as usize;
+		pos -= n as u64;
+
+		f.seek(SeekFrom::Start(pos))?;
+		f.read_exact(&mut buf[..n])?;
+
+		for i in (0..n).rev() {
+			let at = pos + i as u64;
+			if buf[i] != b'\n' || at == end - 1 {
+				continue;
+			}
+			count += 1;
+			if count == lines {
+				return Ok(at + 1);
+			}
+		}
+	}
+
+	Ok(0)
+}
+
+/* identity of a file , changes when a path is replaced by another file */
+#[cfg(unix)]
+pub fn file_id(meta : &std::fs::Metadata) -> (u64 , u64) {
+	use std::os::unix::fs::MetadataExt;
+	(meta.dev() , meta.ino())
+}
+
+/* no stable file index on this system , rotation is seen as a truncation */
+#[cfg(not(unix))]
+pub fn file_id(_meta : &std::fs::Metadata) -> (u64 , u64) {
+	(0 , 0)
+}
+
+
+/* a byte count with an optional k , m or g unit */
+pub fn parse_size(s : &str) -> Option<u64> {
+
+	let (num , unit) = match s.char_indices().find(|(_, c)| !c.is_ascii_digit()){
+		Some((i , _)) => s.split_at(i),
+		None => (s , ""),
+	};
+
+	let num = match num.parse::<u64>(){
+		Ok(p) => p,
+		Err(_) => return None,
+	};
+
+	let unit : u64 = match unit.to_lowercase().as_str() {
+		"" | "b" => 1,
+		"k" | "kb" => 1024,
+		"m" | "mb" => 1024 * 1024,
+		"g" | "gb" => 1024 * 1024 * 1024,
+		_ => return None,
+	};
+
+	num.checked_mul(unit)
+}
+
+#[test]
+fn test_tail_offset() {
+	let data = b"a\nbb\nccc\n";
+	assert_eq!(tail_offset(&mut io::Cursor::new(data), 1).unwrap() , 5);
+	assert_eq!(tail_offset(&mut io::Cursor::new(data), 2).unwrap() , 2);
+	assert_eq!(tail_offset(&mut io::Cursor::new(data), 10).unwrap() , 0);
+	assert_eq!(tail_offset(&mut io::Cursor::new(data), 0).unwrap() , 9);
+	assert_eq!(tail_offset(&mut io::Cursor::new(b"a\nbb"), 1).unwrap() , 2);
+	assert_eq!(tail_offset(&mut io::Cursor::new(b""), 3).unwrap() , 0);
+}
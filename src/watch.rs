@@ -0,0 +1,237 @@
+use std::{fs, path::{Path, PathBuf}, sync::mpsc, thread, time::Duration};
+
+use async_std::channel::Sender;
+use notify::{Config, Event, EventKind, PollWatcher, RecommendedWatcher, RecursiveMode, Watcher, event::{ModifyKind, RenameMode}};
+
+pub const WATCH_CREATE : &str = "create";
+pub const WATCH_MODIFY : &str = "modify";
+pub const WATCH_DELETE : &str = "delete";
+pub const WATCH_RENAME : &str = "rename";
+pub const WATCH_RENAME_FROM : &str = "rename_from";
+pub const WATCH_RENAME_TO : &str = "rename_to";
+
+pub const POLL_INTERVAL : Duration = Duration::from_secs(1);
+
+pub struct WatchEvent {
+	pub kind : String,
+	pub path : String
+}
+
+impl WatchEvent {
+	/* parse a row `kind|path` sent by the server */
+	pub fn from_row(row : &str) -> Option<WatchEvent> {
+		let (kind , path) = row.split_once('|')?;
+		Some(WatchEvent { kind : kind.to_string() , path : path.to_string() })
+	}
+}
+
+fn row(kind : &str , path : &Path) -> String {
+	format!("{}|{}", kind , path.to_string_lossy())
+}
+
+/*
+	map a notify event to rows `kind|path` , a rename gives a `rename_from`
+	row for the old path and a `rename_to` row for the new one , or a
+	`rename` row when the backend can not tell the side . access events are
+	dropped .
+*/
+pub fn event_rows(event : &Event) -> Vec<String> {
+
+	let kind = match event.kind {
+		EventKind::Create(_) => WATCH_CREATE,
+		EventKind::Remove(_) => WATCH_DELETE,
+		/* backends reporting both paths also report each side on its own */
+		EventKind::Modify(ModifyKind::Name(RenameMode::Both)) => return vec![],
+		EventKind::Modify(ModifyKind::Name(RenameMode::From)) => WATCH_RENAME_FROM,
+		EventKind::Modify(ModifyKind::Name(RenameMode::To)) => WATCH_RENAME_TO,
+		EventKind::Modify(ModifyKind::Name(_)) => WATCH_RENAME,
+		EventKind::Modify(_) => WATCH_MODIFY,
+		_ => return vec![],
+	};
+
+	event.paths.iter().map(|p| row(kind, p)).collect()
+}
+
+/* folders to add to a recursive watch , or the end of the watch */
+enum Control {
+	Add(PathBuf),
+	Stop
+}
+
+/*
+	a running watch , events stop when it is dropped . the watcher lives in
+	its own thread which adds the folders created under a recursive watch .
+*/
+pub struct WatchHandle {
+	control : mpsc::Sender<Control>
+}
+
+impl Drop for WatchHandle {
+	fn drop(&mut self) {
+		let _ = self.control.send(Control::Stop);
+	}
+}
+
+/*
+	real location of an event path : the deepest existing folder above it is
+	resolved and the rest joined , the entry itself is never followed .
+*/
+fn location(path : &Path) -> Option<PathBuf> {
+	let mut rest = vec![path.file_name()?];
+	let mut dir = path.parent()?;
+	loop {
+		if let Ok(p) = dir.canonicalize() {
+			return Some(rest.iter().rev().fold(p, |p, name| p.join(name)));
+		}
+		rest.push(dir.file_name()?);
+		dir = dir.parent()?;
+	}
+}
+
+/* an event out of root (seen through a symlink) is never reported */
+fn inside(root : &Option<PathBuf> , path : &Path) -> bool {
+	match root {
+		Some(root) => location(path).is_some_and(|p| p.starts_with(root)),
+		None => true,
+	}
+}
+
+fn handler(sender : Sender<Option<String>> , control : Option<mpsc::Sender<Control>> , root : Option<PathBuf>) -> impl Fn(notify::Result<Event>) + Send + 'static {
+	move |res : notify::Result<Event>| {
+		if let Ok(mut event) = res {
+			event.paths.retain(|p| inside(&root, p));
+
+			/* a folder created or moved in is watched too , a symlink is not */
+			if let Some(control) = &control {
+				if matches!(event.kind , EventKind::Create(_) | EventKind::Modify(ModifyKind::Name(RenameMode::To))) {
+					for p in event.paths.iter().filter(|p| fs::symlink_metadata(p).is_ok_and(|m| m.is_dir())) {
+						let _ = control.send(Control::Add(p.clone()));
+					}
+				}
+			}
+
+			for row in event_rows(&event) {
+				let _ = sender.send_blocking(Some(row));
+			}
+		}
+	}
+}
+
+/* watch every folder under dir , symlinks are not followed */
+fn watch_tree(watcher : &mut dyn Watcher , dir : &Path) {
+	let entries = match fs::read_dir(dir) {
+		Ok(p) => p,
+		Err(_) => return,
+	};
+
+	for entry in entries.flatten() {
+		if !entry.file_type().is_ok_and(|p| p.is_dir()) {
+			continue;
+		}
+
+		let path = entry.path();
+		if let Err(e) = watcher.watch(&path, RecursiveMode::NonRecursive) {
+			log::warn!("watch {} faild : {}" , path.display() , e);
+			continue;
+		}
+		watch_tree(watcher, &path);
+	}
+}
+
+/*
+	watch path and forward every event row to sender , the native watcher
+	(inotify on linux) is used when available , else the path is polled .
+	a recursive watch adds each real folder itself instead of letting notify
+	follow symlinks , and with root events out of it are dropped . events
+	stop when the returned handle is dropped .
+*/
+pub fn start_watcher(path : &Path , recursive : bool , root : Option<&Path> , sender : Sender<Option<String>>) -> notify::Result<WatchHandle> {
+
+	let (control , commands) = mpsc::channel();
+	let adder = if recursive { Some(control.clone()) } else { None };
+	let root = root.map(Path::to_path_buf);
+
+	let mut watcher : Box<dyn Watcher + Send> = match RecommendedWatcher::new(handler(sender.clone(), adder.clone(), root.clone()), Config::default()){
+		Ok(p) => Box::new(p),
+		Err(e) => {
+			log::warn!("native watcher unavailable , fallback to polling : {}" , e);
+			Box::new(PollWatcher::new(handler(sender.clone(), adder.clone(), root.clone()), Config::default().with_poll_interval(POLL_INTERVAL))?)
+		},
+	};
+
+	if let Err(e) = watcher.watch(path, RecursiveMode::NonRecursive) {
+		log::warn!("native watch faild , fallback to polling : {}" , e);
+		watcher = Box::new(PollWatcher::new(handler(sender, adder, root), Config::default().with_poll_interval(POLL_INTERVAL))?);
+		watcher.watch(path, RecursiveMode::NonRecursive)?;
+	}
+
+	if recursive {
+		watch_tree(watcher.as_mut(), path);
+	}
+
+	thread::spawn(move || {
+		while let Ok(Control::Add(dir)) = commands.recv() {
+			if watcher.watch(&dir, RecursiveMode::NonRecursive).is_ok() {
+				watch_tree(watcher.as_mut(), &dir);
+			}
+		}
+	});
+
+	Ok(WatchHandle { control })
+}
+
+#[test]
+fn test_event_rows() {
+	use notify::event::{CreateKind, DataChange};
+	use std::path::PathBuf;
+
+	let event = Event::new(EventKind::Create(CreateKind::File)).add_path(PathBuf::from("/tmp/a"));
+	assert_eq!(event_rows(&event) , vec!["create|/tmp/a"]);
+
+	let event = Event::new(EventKind::Modify(ModifyKind::Data(DataChange::Any))).add_path(PathBuf::from("/tmp/a"));
+	assert_eq!(event_rows(&event) , vec!["modify|/tmp/a"]);
+
+	let event = Event::new(EventKind::Modify(ModifyKind::Name(RenameMode::Both)))
+		.add_path(PathBuf::from("/tmp/a"))
+		.add_path(PathBuf::from("/tmp/b"));
+	assert!(event_rows(&event).is_empty());
+
+	let event = Event::new(EventKind::Modify(ModifyKind::Name(RenameMode::From))).add_path(PathBuf::from("/tmp/a"));
+	assert_eq!(event_rows(&event) , vec!["rename_from|/tmp/a"]);
+
+	let event = Event::new(EventKind::Access(notify::event::AccessKind::Any)).add_path(PathBuf::from("/tmp/a"));
+	assert!(event_rows(&event).is_empty());
+
+	let ev = WatchEvent::from_row("delete|/tmp/a|b").unwrap();
+	assert_eq!(ev.kind , "delete");
+	assert_eq!(ev.path , "/tmp/a|b");
+
+	/* a symlink out of root is neither followed nor reported */
+	let base = std::env::temp_dir().join(format!("yaftp_watch_{}" , std::process::id()));
+	fs::create_dir_all(base.join("root/sub")).unwrap();
+	fs::create_dir_all(base.join("outside")).unwrap();
+	let root = base.join("root").canonicalize().unwrap();
+	crate::utils::make_symlink(&base.join("outside"), &root.join("link")).unwrap();
+
+	let (sender , receiver) = async_std::channel::bounded::<Option<String>>(1024);
+	let watcher = start_watcher(&root, true, Some(&root), sender).unwrap();
+
+	fs::write(base.join("outside/secret.txt"), b"x").unwrap();
+	fs::write(root.join("link/secret2.txt"), b"x").unwrap();
+	fs::write(root.join("sub/seen.txt"), b"x").unwrap();
+
+	let mut rows = vec![];
+	let deadline = std::time::Instant::now() + Duration::from_secs(5);
+	while !rows.iter().any(|p : &String| p.ends_with("seen.txt")) && std::time::Instant::now() < deadline {
+		match receiver.try_recv() {
+			Ok(Some(row)) => rows.push(row),
+			_ => thread::sleep(Duration::from_millis(50)),
+		}
+	}
+	drop(watcher);
+
+	assert!(rows.iter().any(|p| p.ends_with("seen.txt")));
+	assert!(!rows.iter().any(|p| p.contains("secret")));
+
+	fs::remove_dir_all(&base).unwrap();
+}